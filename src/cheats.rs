@@ -0,0 +1,316 @@
+use std::path::Path;
+
+use crate::mmu::{MemoryOperations, MMU};
+
+/// Game Genie codes sit between the cartridge & the console, so they can only patch the ROM
+const GAME_GENIE_MAX_ADDRESS: u16 = 0x7FFF;
+/// Rotated right by 2 and XORed with this to get the compare byte of a Game Genie code
+const GAME_GENIE_COMPARE_KEY: u8 = 0xBA;
+/// The only GameShark code type for the original Game Boy, a RAM write with bank 1
+const GAMESHARK_TYPE_WRITE: u8 = 0x01;
+
+/// Value returned in place of a ROM byte, see `MMU::set_rom_patches`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomPatch {
+    pub address: u16,
+    pub value: u8,
+    /// Only replace the byte if the ROM contains this value, so other banks at the same address stay untouched
+    pub compare: Option<u8>,
+}
+
+impl RomPatch {
+    /// Get the byte read from the address after applying the patch
+    pub fn apply(&self, address: u16, value: u8) -> u8 {
+        if address == self.address && self.compare.is_none_or(|compare| compare == value) {
+            self.value
+        } else {
+            value
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheatCode {
+    /// Patches a ROM read, formatted as `ABC-DEF` or `ABC-DEF-GHI` with a compare byte
+    /// See: https://gbdev.gg8.se/wiki/articles/Gameshark_and_Game_Genie_Codes
+    GameGenie(RomPatch),
+    /// Writes a byte to RAM once per frame, formatted as `01VVAAAA` with the address in little endian
+    GameShark { address: u16, value: u8 },
+}
+
+impl CheatCode {
+    /// Parse a Game Genie or GameShark code, whitespace & the case are ignored
+    pub fn parse(code: &str) -> Result<Self, String> {
+        let code: String = code.chars().filter(|c| !c.is_whitespace()).collect();
+
+        match code.len() {
+            7 | 11 => parse_game_genie(&code),
+            8 => parse_gameshark(&code),
+            _ => Err(format!(
+                "\"{}\" is neither a Game Genie code (ABC-DEF or ABC-DEF-GHI) nor a GameShark code (01VVAAAA)",
+                code
+            )),
+        }
+    }
+}
+
+/// Parse the hex digits of a code, each one as a separate nibble
+fn parse_nibbles(code: &str) -> Result<Vec<u8>, String> {
+    code.chars()
+        .map(|c| {
+            c.to_digit(16)
+                .map(|digit| digit as u8)
+                .ok_or_else(|| format!("Invalid character '{}' in code {}", c, code))
+        })
+        .collect()
+}
+
+fn parse_game_genie(code: &str) -> Result<CheatCode, String> {
+    let groups: Vec<&str> = code.split('-').collect();
+    if groups.len() < 2 || groups.iter().any(|group| group.len() != 3) {
+        return Err(format!("Game Genie code {} has to be formatted as ABC-DEF or ABC-DEF-GHI", code));
+    }
+
+    let n = parse_nibbles(&groups.concat())?;
+    let address = ((n[5] ^ 0xF) as u16) << 12 | (n[2] as u16) << 8 | (n[3] as u16) << 4 | n[4] as u16;
+    if address > GAME_GENIE_MAX_ADDRESS {
+        return Err(format!("Game Genie code {} patches {:#06X}, which isn't ROM", code, address));
+    }
+
+    // The middle digit of the last group is unused
+    let compare = (n.len() == 9).then(|| ((n[6] << 4) | n[8]).rotate_right(2) ^ GAME_GENIE_COMPARE_KEY);
+
+    Ok(CheatCode::GameGenie(RomPatch {
+        address,
+        value: (n[0] << 4) | n[1],
+        compare,
+    }))
+}
+
+fn parse_gameshark(code: &str) -> Result<CheatCode, String> {
+    let n = parse_nibbles(code)?;
+    let byte = |index: usize| (n[index] << 4) | n[index + 1];
+
+    if byte(0) != GAMESHARK_TYPE_WRITE {
+        return Err(format!("GameShark code {} has the unsupported type {:02X}, only 01 is supported", code, byte(0)));
+    }
+
+    let address = u16::from_le_bytes([byte(4), byte(6)]);
+    match address {
+        0xA000..=0xDFFF | 0xFF80..=0xFFFE => Ok(CheatCode::GameShark { address, value: byte(2) }),
+        _ => Err(format!("GameShark code {} writes to {:#06X}, which isn't RAM", code, address)),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cheat {
+    /// The code as entered, in upper case
+    pub code: String,
+    pub description: String,
+    pub enabled: bool,
+    parsed: CheatCode,
+}
+
+impl Cheat {
+    pub fn new(code: &str, description: &str) -> Result<Self, String> {
+        Ok(Self {
+            parsed: CheatCode::parse(code)?,
+            code: code.trim().to_ascii_uppercase(),
+            description: description.trim().to_string(),
+            enabled: true,
+        })
+    }
+
+    pub fn get_code(&self) -> CheatCode {
+        self.parsed
+    }
+}
+
+/// The cheats of a ROM, saved as a text file with one cheat per line, e.g.
+/// `on 00A-17B-C49 Infinite lives` or `off 01FF16D0 Max health`
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CheatList {
+    cheats: Vec<Cheat>,
+    /// No cheat is applied while set, e.g. during movies which don't contain them
+    suspended: bool,
+}
+
+impl CheatList {
+    /// Cheats added while the list is suspended start disabled
+    pub fn add(&mut self, mut cheat: Cheat) {
+        cheat.enabled &= !self.suspended;
+        self.cheats.push(cheat);
+    }
+
+    pub fn remove(&mut self, index: usize) -> Cheat {
+        self.cheats.remove(index)
+    }
+
+    pub fn get_cheats(&self) -> &[Cheat] {
+        &self.cheats
+    }
+
+    pub fn get_cheats_mut(&mut self) -> &mut [Cheat] {
+        &mut self.cheats
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cheats.is_empty()
+    }
+
+    /// Stop applying all cheats, `apply_rom_patches` has to be called afterwards
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended
+    }
+
+    /// Hand the enabled Game Genie codes to the MMU, has to be called whenever the list changes
+    pub fn apply_rom_patches(&self, mmu: &mut MMU) {
+        let patches = self
+            .cheats
+            .iter()
+            .filter(|cheat| cheat.enabled && !self.suspended)
+            .filter_map(|cheat| match cheat.parsed {
+                CheatCode::GameGenie(patch) => Some(patch),
+                CheatCode::GameShark { .. } => None,
+            })
+            .collect();
+
+        mmu.set_rom_patches(patches);
+    }
+
+    /// Write the enabled GameShark codes, call once per frame like the real device does on VBlank
+    /// Codes for cartridge RAM are skipped while it isn't mapped, so they don't send commands to the MBC
+    pub fn apply_ram_writes(&self, mmu: &mut MMU) {
+        for cheat in self.cheats.iter().filter(|cheat| cheat.enabled && !self.suspended) {
+            if let CheatCode::GameShark { address, value } = cheat.parsed {
+                if mmu.is_ram_mapped(address) {
+                    mmu.write_byte(address, value);
+                }
+            }
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        self.cheats
+            .iter()
+            .map(|cheat| {
+                let state = if cheat.enabled { "on" } else { "off" };
+                format!("{} {} {}\n", state, cheat.code, cheat.description).replace(" \n", "\n")
+            })
+            .collect()
+    }
+
+    /// Parse a list of `to_text`, empty lines & lines starting with `#` are skipped
+    pub fn from_text(text: &str) -> Result<Self, String> {
+        let mut list = Self::default();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(3, char::is_whitespace);
+            let enabled = match parts.next() {
+                Some("on") => true,
+                Some("off") => false,
+                _ => return Err(format!("Line {} has to start with \"on\" or \"off\"", number + 1)),
+            };
+            let code = parts.next().unwrap_or_default();
+            let description = parts.next().unwrap_or_default();
+
+            let mut cheat = Cheat::new(code, description).map_err(|e| format!("Line {}: {}", number + 1, e))?;
+            cheat.enabled = enabled;
+            list.add(cheat);
+        }
+
+        Ok(list)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_text()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Self::from_text(&text)
+    }
+}
+
+#[test]
+pub fn cheat_code_test() {
+    // Address 0x4A17 in any bank, replacing 0xC8 with 0x00
+    let code = CheatCode::parse("00A-17B-C49").unwrap();
+    let CheatCode::GameGenie(patch) = code else {
+        panic!("Not a Game Genie code: {:?}", code);
+    };
+    assert_eq!(patch.address, 0x4A17);
+    assert_eq!(patch.value, 0x00);
+    assert_eq!(patch.compare, Some(0xC8));
+    assert_eq!(
+        CheatCode::parse("3ea-b5f"),
+        Ok(CheatCode::GameGenie(RomPatch { address: 0x0AB5, value: 0x3E, compare: None }))
+    );
+
+    assert_eq!(
+        CheatCode::parse("01FF16D0"),
+        Ok(CheatCode::GameShark { address: 0xD016, value: 0xFF })
+    );
+
+    // Validation errors
+    assert!(CheatCode::parse("00A-17B-C4").is_err());
+    assert!(CheatCode::parse("00A-17G").is_err());
+    assert!(CheatCode::parse("00A-170").is_err()); // 0xFA17 isn't ROM
+    assert!(CheatCode::parse("02FF16D0").is_err());
+    assert!(CheatCode::parse("01FF0080").is_err()); // 0x8000 is VRAM
+
+    let text = "on 00A-17B-C49 Infinite lives\n# Comment\n\noff 01FF16D0\n";
+    let list = CheatList::from_text(text).unwrap();
+    assert_eq!(list.get_cheats().len(), 2);
+    assert!(!list.get_cheats()[1].enabled);
+    assert_eq!(CheatList::from_text(&list.to_text()), Ok(list));
+    assert!(CheatList::from_text("maybe 00A-17B-C49").is_err());
+}
+
+#[test]
+pub fn cheat_patch_test() {
+    use crate::cpu::CPU;
+
+    let mut rom = vec![0; 0x8000];
+    rom[0x4A17] = 0xC8;
+    rom[0x4A18] = 0x0D;
+    let mut cpu = CPU::new(rom);
+    cpu.skip_boot_rom();
+
+    let mut list = CheatList::default();
+    list.add(Cheat::new("00A-17B-C49", "").unwrap());
+    list.add(Cheat::new("11A-18B-C49", "Compare doesn't match").unwrap());
+    list.add(Cheat::new("014216D0", "").unwrap());
+    list.apply_rom_patches(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0x4A17), 0x00);
+    assert_eq!(cpu.mmu.read_byte(0x4A18), 0x0D);
+
+    list.apply_ram_writes(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0xD016), 0x42);
+    assert!(!cpu.mmu.is_ram_mapped(0xA000)); // There is no cartridge RAM
+
+    // Disabling the code restores the ROM
+    list.get_cheats_mut()[0].enabled = false;
+    list.apply_rom_patches(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0x4A17), 0xC8);
+
+    // Suspending the list keeps the codes enabled, but doesn't apply them
+    list.get_cheats_mut()[0].enabled = true;
+    list.set_suspended(true);
+    list.apply_rom_patches(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0x4A17), 0xC8);
+    cpu.mmu.write_byte(0xD016, 0x00);
+    list.apply_ram_writes(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0xD016), 0x00);
+    list.add(Cheat::new("01FF16D0", "").unwrap());
+    assert!(!list.get_cheats()[3].enabled);
+}
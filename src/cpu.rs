@@ -1,5 +1,7 @@
-use crate::{mmu::MMU};
-use self::instructions::{InstructionResult, Instructions};
+use crate::{mmu::MMU, rendering::line_rendering::Ppu};
+use self::{hardware_model::HardwareModel, instructions::InstructionResult, scheduler::{Event, Scheduler}};
+#[cfg(test)]
+use self::instructions::Instructions;
 
 pub mod decode;
 /// These are the actual abstractions and implementations of the CPU
@@ -8,11 +10,17 @@ pub mod instructions;
 pub mod registers;
 pub mod render_operations;
 mod step;
+mod dispatch;
 pub mod interrupts;
 mod joypad;
 mod timer;
 mod dma;
 mod helpers;
+mod clock;
+mod serial;
+mod snapshot;
+pub mod scheduler;
+pub mod hardware_model;
 
 /// 4.194304 MHz
 /// This is the frequency of the CPU
@@ -25,40 +33,79 @@ pub struct CPU {
     /// that can be combined to form 4 16-bit registers (AF, BC, DE, HL)
     /// and two purely 16-bit registers (SP, PC)
     registers: [u8; 12],
+    /// The emulated Game Boy model
+    model: HardwareModel,
     pub mmu: MMU,
-    next_instruction: Instructions,
+    /// Bytes of an instruction that is executed instead of the one at PC, see `set_instruction`
+    injected_instruction: Option<[u8; 3]>,
     last_step_result: InstructionResult,
     /// The global interrupt master enable flag
     ime_flag: bool, 
     /// 0 if nothing to do, 2 if ime needs to be set after next instruction, 1 if ime needs to be set after this instruction
     enable_ime: i32,
     last_execution_time: std::time::Instant,
+    /// M-cycles passed since power on
     cycles: u64,
+    /// M-cycles passed during the current step
+    instruction_cycles: u8,
+    /// The master clock & the pending events of all components
+    scheduler: Scheduler,
+    /// The master clock time the internal 16-bit counter of the timer was 0 at
+    divider_base: u64,
+    /// The last value written to TAC the timer is running with
+    timer_control: u8,
+    /// Bytes sent via the serial port, no link partner is connected
+    serial_output: Vec<u8>,
+    /// The PPU is ticked alongside the CPU, None while it is being stepped
+    ppu: Option<Ppu>,
+    /// The state of the STAT interrupt line, an interrupt is requested on its rising edge
+    stat_interrupt_line: bool,
     is_halted: bool,
     stop_mode: bool,
     pub instruction: i32,
     dma_active: bool, // Whether a DMA has been requested
-    dma_current_offset: u8, // The current line offset based on the DMA register being copied
+    dma_current_offset: u8, // The amount of bytes that have been copied to OAM so far
+    dma_source: u16, // The address the running DMA copies from
+    dma_start_time: u64, // The master clock time the running DMA started at
 }
 
 /// Note, please look at the relevant modules for the actual implementations
 impl CPU {
-    /// Create a new CPU
+    /// Create a new CPU, panics if the ROM can't be loaded
     pub fn new(rom: Vec<u8>) -> CPU {
-        CPU {
+        Self::from_rom(rom).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Create a new CPU, fails for ROMs without a header or with an unsupported cartridge type
+    pub fn from_rom(rom: Vec<u8>) -> Result<CPU, String> {
+        let mut cpu = CPU {
             registers: [0; 12],
-            next_instruction: Instructions::NOP,
+            model: HardwareModel::default(),
+            injected_instruction: None,
             last_step_result: InstructionResult::default(),
             enable_ime: 0,
             ime_flag: false,
-            mmu: MMU::new_from_vec(rom),
+            mmu: MMU::new_from_vec(rom)?,
             last_execution_time: std::time::Instant::now(),
             cycles: 0,
+            instruction_cycles: 0,
+            scheduler: Scheduler::new(),
+            divider_base: 0,
+            timer_control: 0,
+            serial_output: Vec::new(),
+            ppu: Some(Ppu::new()),
+            stat_interrupt_line: false,
             is_halted: false,
             stop_mode: false,
             instruction: 0,
             dma_active: false,
             dma_current_offset: 0,
-        }
+            dma_source: 0,
+            dma_start_time: 0,
+        };
+
+        cpu.set_divider(0);
+        cpu.scheduler.schedule(Event::Ppu, 0);
+        Ok(cpu)
     }
 }
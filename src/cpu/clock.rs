@@ -0,0 +1,139 @@
+use crate::mmu::MemoryOperations;
+
+use super::{scheduler::Event, CPU};
+
+/// T-cycles that pass in one M-cycle
+pub const T_CYCLES_PER_M_CYCLE: u64 = 4;
+
+impl CPU {
+    /// Advance the master clock by one M-cycle and handle all events that are due
+    /// The CPU calls this for every memory access and every internal cycle,
+    /// so accesses within a multi-cycle instruction see the correct state
+    /// Register writes are handled before the events, events are handled in the order of `Event`
+    pub fn tick(&mut self) {
+        self.cycles += 1;
+        self.instruction_cycles = self.instruction_cycles.wrapping_add(1);
+        self.scheduler.advance(T_CYCLES_PER_M_CYCLE);
+        self.sync_dma();
+
+        self.handle_io_requests();
+
+        while let Some(event) = self.scheduler.pop_due_event() {
+            self.handle_event(event);
+        }
+    }
+
+    /// Let the components react to registers the CPU has written to
+    fn handle_io_requests(&mut self) {
+        if self.mmu.IO.is_div_reset_requested() {
+            self.mmu.IO.reset_div_reset_request();
+            self.reset_divider();
+        }
+
+        if self.mmu.IO.is_timer_control_changed() {
+            self.mmu.IO.reset_timer_control_changed();
+            self.update_timer_control();
+        }
+
+        if self.mmu.IO.is_dma_requested() {
+            self.mmu.IO.reset_dma_request();
+            self.request_dma();
+        }
+
+        if self.mmu.IO.is_serial_transfer_requested() {
+            self.mmu.IO.reset_serial_transfer_request();
+            self.start_serial_transfer();
+        }
+
+        // The PPU has to turn on or off right away
+        if self.mmu.IO.is_lcd_toggled() {
+            self.mmu.IO.reset_lcd_toggled();
+            self.scheduler.schedule(Event::Ppu, 0);
+        }
+
+        if self.mmu.IO.is_stat_update_requested() {
+            self.mmu.IO.reset_stat_update_request();
+            self.update_stat_interrupt_line();
+        }
+    }
+
+    fn handle_event(&mut self, event: Event) {
+        match event {
+            Event::DivIncrement => self.div_increment(),
+            Event::TimerIncrement => self.timer_increment(),
+            Event::DmaStart => self.start_dma(),
+            Event::DmaEnd => self.finish_dma(),
+            Event::SerialTransfer => self.finish_serial_transfer(),
+            Event::Ppu => {
+                // The PPU needs access to the CPU (and thus memory), so it is taken out while stepping
+                if let Some(mut ppu) = self.ppu.take() {
+                    let dots = ppu.step(self);
+                    self.ppu = Some(ppu);
+                    self.scheduler.schedule(Event::Ppu, dots as u64);
+                }
+                self.update_stat_interrupt_line();
+            }
+        }
+    }
+
+    /// Skip the M-cycles till the next event is due, nothing can happen in between
+    /// Used while halted, so the CPU doesn't have to be stepped for every M-cycle
+    pub fn skip_to_next_event(&mut self) {
+        let m_cycles = self.scheduler.get_cycles_until_next_event().div_ceil(T_CYCLES_PER_M_CYCLE);
+
+        // The last M-cycle is ticked as usual, so the event is handled
+        if m_cycles > 1 {
+            self.cycles += m_cycles - 1;
+            self.scheduler.advance((m_cycles - 1) * T_CYCLES_PER_M_CYCLE);
+        }
+    }
+
+    /// An internal M-cycle of an instruction without any memory access
+    pub fn idle_cycle(&mut self) {
+        self.tick();
+    }
+
+    /// Read a byte from memory, taking one M-cycle
+    pub fn read_memory(&mut self, address: u16) -> u8 {
+        self.tick();
+
+        if let Some(value) = self.get_dma_conflict_value(address) {
+            return value;
+        }
+
+        self.mmu.read_byte(address)
+    }
+
+    /// Write a byte to memory, taking one M-cycle
+    /// Writes to addresses blocked by a running DMA are ignored
+    pub fn write_memory(&mut self, address: u16, value: u8) {
+        self.tick();
+
+        if self.get_dma_conflict_value(address).is_some() {
+            return;
+        }
+
+        self.mmu.write_byte(address, value);
+    }
+}
+
+#[test]
+pub fn memory_access_timing_test() {
+    use super::{instructions::{InstParam, Instructions}, registers::{Register16Bit, Register8Bit}};
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+    cpu.set_16bit_register(Register16Bit::HL, 0xFF04);
+
+    // DIV increments during the 2nd M-cycle, which is the read cycle of LD A, (HL)
+    cpu.set_divider(0x00F8);
+    cpu.set_instruction(Instructions::LD(
+        InstParam::Register8Bit(Register8Bit::A),
+        InstParam::Register16Bit(Register16Bit::HL),
+    ));
+    let cycles = cpu.get_cycles();
+    cpu.step().unwrap();
+
+    assert_eq!(cpu.get_8bit_register(Register8Bit::A), 1);
+    assert_eq!(cpu.get_cycles() - cycles, 2);
+}
@@ -1,4 +1,6 @@
-use super::{instructions::Instructions, CPU};
+use crate::mmu::MemoryOperations;
+
+use super::{instructions::Instructions, registers::Register16Bit, CPU};
 
 mod helpers;
 mod unprefixed_commons;
@@ -6,14 +8,30 @@ mod unprefixed;
 mod prefixed;
 mod test;
 
+/// Opcode prefixing a second table of opcodes
+pub const PREFIX_OPCODE: u8 = 0xCB;
+
 impl CPU {
     /// Decode an opcode, returning the instruction
+    /// The operands are read from the bytes following PC
     pub fn decode(&self, opcode: u8) -> Result<Instructions, String> {
+        let pc = self.get_16bit_register(Register16Bit::PC);
+
+        Self::decode_bytes([
+            opcode,
+            self.mmu.read_byte(pc.wrapping_add(1)),
+            self.mmu.read_byte(pc.wrapping_add(2)),
+        ])
+    }
+
+    /// Decode the instruction at the start of the bytes, e.g. for disassembly
+    /// Only the bytes belonging to the instruction are used, see `Instructions::length`
+    pub fn decode_bytes(bytes: [u8; 3]) -> Result<Instructions, String> {
         // 0xCB is a prefixed opcode with a completely different table
-        if opcode == 0xCB {
-            self.decode_prefixed()
+        if bytes[0] == PREFIX_OPCODE {
+            Self::decode_prefixed(bytes[1])
         } else {
-            self.decode_unprefixed(opcode)
+            Self::decode_unprefixed(bytes[0], [bytes[1], bytes[2]])
         }
     }
 }
\ No newline at end of file
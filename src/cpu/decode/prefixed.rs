@@ -1,12 +1,8 @@
-use crate::{cpu::{instructions::{InstParam, Instructions}, registers::{Register16Bit, Register8Bit}, CPU}, mmu::MemoryOperations};
+use crate::cpu::{instructions::{InstParam, Instructions}, registers::{Register16Bit, Register8Bit}, CPU};
 
 impl CPU {
-        /// Decode a prefixed opcode (0xCB)
-        pub fn decode_prefixed(&self) -> Result<Instructions, String> {
-            let opcode = self
-                .mmu
-                .read_byte(self.get_16bit_register(Register16Bit::PC) + 1);
-    
+        /// Decode a prefixed opcode, the byte following 0xCB
+        pub fn decode_prefixed(opcode: u8) -> Result<Instructions, String> {
             let head = opcode >> 4;
             let tail = opcode & 0xF;
     
@@ -30,22 +26,22 @@ impl CPU {
                 0x0 => match tail {
                     0x0..=0x7 => Instructions::RLC(register),
                     0x8..=0xF => Instructions::RRC(register),
-                    _ => return self.not_implemented(opcode),
+                    _ => return Self::not_implemented(opcode),
                 }
                 0x1 => match tail {
                     0x0..=0x7 => Instructions::RL(register),
                     0x8..=0xF => Instructions::RR(register),
-                    _ => return self.not_implemented(opcode),
+                    _ => return Self::not_implemented(opcode),
                 }
                 0x2 => match tail {
                     0x0..=0x7 => Instructions::SLA(register),
                     0x8..=0xF => Instructions::SRA(register),
-                    _ => return self.not_implemented(opcode),
+                    _ => return Self::not_implemented(opcode),
                 }
                 0x3 => match tail {
                     0x0..=0x7 => Instructions::SWAP(register),
                     0x8..=0xF => Instructions::SRL(register),
-                    _ => return self.not_implemented(opcode),
+                    _ => return Self::not_implemented(opcode),
                 }
                 0x4 => Instructions::BIT(InstParam::Unsigned3Bit(offset), register),
                 0x5 => Instructions::BIT(InstParam::Unsigned3Bit(2 + offset), register),
@@ -59,7 +55,7 @@ impl CPU {
                 0xD => Instructions::SET(InstParam::Unsigned3Bit(2 + offset), register),
                 0xE => Instructions::SET(InstParam::Unsigned3Bit(4 + offset), register),
                 0xF => Instructions::SET(InstParam::Unsigned3Bit(6 + offset), register),
-                _ => return self.not_implemented(opcode),
+                _ => return Self::not_implemented(opcode),
             })
         }
 }
\ No newline at end of file
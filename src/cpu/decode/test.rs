@@ -44,13 +44,13 @@ pub fn test_decode() {
     println!("🟢 Decoded values: {:?}", decoded_values);
     println!("🔴 Failed values: {:?}", failed_values);
 
-    // To file
+    // To files in the temp directory, so they don't end up in the repository
 
     use std::fs::File;
     use std::io::Write;
 
-    let mut file = File::create("decoded_values.txt").unwrap();
+    let mut file = File::create(std::env::temp_dir().join("decoded_values.txt")).unwrap();
     file.write_all(decoded_values.as_bytes()).unwrap();
-    let mut file = File::create("failed_values.txt").unwrap();
+    let mut file = File::create(std::env::temp_dir().join("failed_values.txt")).unwrap();
     file.write_all(failed_values.as_bytes()).unwrap();
 }
\ No newline at end of file
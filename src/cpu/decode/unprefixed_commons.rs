@@ -2,7 +2,7 @@ use crate::cpu::{instructions::{InstParam, Instructions}, registers::{Register16
 
 impl CPU {
     /// Decode the unprefixed common opcodes (0x0 - 0x3)
-    pub fn decode_0x0_to_0x3_commons(&self, opcode: u8) -> Result<Instructions, String> {
+    pub fn decode_0x0_to_0x3_commons(opcode: u8, operands: [u8; 2]) -> Result<Instructions, String> {
         let head = opcode >> 4;
         let tail = opcode & 0xF;
 
@@ -43,7 +43,7 @@ impl CPU {
         Ok(match tail {
             0x1 => Instructions::LD(
                 InstParam::Register16Bit(register_16bit),
-                InstParam::Number16Bit(self.get_16bit_from_pc()),
+                InstParam::Number16Bit(u16::from_le_bytes(operands)),
             ),
             0x3 => Instructions::INC(InstParam::Register16Bit(register_16bit),InstParam::Boolean(false)),
             0x4 => Instructions::INC(if head == 0x3 {
@@ -62,7 +62,7 @@ impl CPU {
                 } else {
                     InstParam::Register8Bit(register_8bit)
                 },
-                InstParam::Number8Bit(self.get_8bit_from_pc()),
+                InstParam::Number8Bit(operands[0]),
             ),
             0x9 => Instructions::ADD_HL(InstParam::Register16Bit(register_16bit)),
             0xB => Instructions::DEC(InstParam::Register16Bit(register_16bit),InstParam::Boolean(false)),
@@ -70,7 +70,7 @@ impl CPU {
             0xD => Instructions::DEC(InstParam::Register8Bit(register_8bit),InstParam::Boolean(false)),
             0xE => Instructions::LD(
                 InstParam::Register8Bit(register_8bit),
-                InstParam::Number8Bit(self.get_8bit_from_pc()),
+                InstParam::Number8Bit(operands[0]),
             ),
             _ => return Err(format!("Not covered in common {:#02X}", opcode)),
         })
@@ -0,0 +1,547 @@
+use super::{
+    instructions::{InstructionCondition, InstructionResult},
+    registers::{Register16Bit, Register8Bit},
+    CPU,
+};
+
+/// Executes an instruction
+/// Registers and conditions are taken from the opcode, immediates are fetched from the bytes following it
+pub type InstructionHandler = fn(&mut CPU, u8) -> InstructionResult;
+
+/// An entry of the opcode tables
+#[derive(Clone, Copy)]
+pub struct OpcodeEntry {
+    pub handler: InstructionHandler,
+    /// JP, CALL, RST, RET & RETI set PC themselves, every other instruction advances it by its length
+    pub sets_pc: bool,
+}
+
+impl OpcodeEntry {
+    const fn new(handler: InstructionHandler) -> Option<OpcodeEntry> {
+        Some(OpcodeEntry { handler, sets_pc: false })
+    }
+
+    const fn jump(handler: InstructionHandler) -> Option<OpcodeEntry> {
+        Some(OpcodeEntry { handler, sets_pc: true })
+    }
+}
+
+/// Handlers of all unprefixed opcodes, None for the opcodes that don't exist
+/// See: https://gbdev.io/gb-opcodes/optables/
+pub static UNPREFIXED_OPCODES: [Option<OpcodeEntry>; 256] = build_unprefixed_table();
+
+/// Handlers of the opcodes following 0xCB, every one of them exists
+pub static PREFIXED_OPCODES: [OpcodeEntry; 256] = build_prefixed_table();
+
+/// Index of the (HL) operand within the register encoding of the opcodes
+const HL_INDEX: u8 = 6;
+
+const fn build_unprefixed_table() -> [Option<OpcodeEntry>; 256] {
+    let mut table = [None; 256];
+
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode] = unprefixed_entry(opcode as u8);
+        opcode += 1;
+    }
+
+    table
+}
+
+const fn build_prefixed_table() -> [OpcodeEntry; 256] {
+    let mut table = [OpcodeEntry { handler: rlc, sets_pc: false }; 256];
+
+    let mut opcode = 0;
+    while opcode < table.len() {
+        table[opcode].handler = prefixed_handler(opcode as u8);
+        opcode += 1;
+    }
+
+    table
+}
+
+const fn unprefixed_entry(opcode: u8) -> Option<OpcodeEntry> {
+    match opcode {
+        0x00 => OpcodeEntry::new(nop),
+        0x10 => OpcodeEntry::new(stop),
+        0x76 => OpcodeEntry::new(halt),
+        0xF3 => OpcodeEntry::new(di),
+        0xFB => OpcodeEntry::new(ei),
+        0x27 => OpcodeEntry::new(daa),
+        0x2F => OpcodeEntry::new(cpl),
+        0x37 => OpcodeEntry::new(scf),
+        0x3F => OpcodeEntry::new(ccf),
+        0x07 => OpcodeEntry::new(rlca),
+        0x0F => OpcodeEntry::new(rrca),
+        0x17 => OpcodeEntry::new(rla),
+        0x1F => OpcodeEntry::new(rra),
+
+        0x01 | 0x11 | 0x21 | 0x31 => OpcodeEntry::new(ld_r16_n16),
+        0x02 | 0x12 => OpcodeEntry::new(ld_r16_a),
+        0x0A | 0x1A => OpcodeEntry::new(ld_a_r16),
+        0x22 => OpcodeEntry::new(ld_hli_a),
+        0x2A => OpcodeEntry::new(ld_a_hli),
+        0x32 => OpcodeEntry::new(ld_hld_a),
+        0x3A => OpcodeEntry::new(ld_a_hld),
+        0x03 | 0x13 | 0x23 | 0x33 => OpcodeEntry::new(inc_r16),
+        0x0B | 0x1B | 0x2B | 0x3B => OpcodeEntry::new(dec_r16),
+        0x09 | 0x19 | 0x29 | 0x39 => OpcodeEntry::new(add_hl_r16),
+        0x34 => OpcodeEntry::new(inc_hl),
+        0x35 => OpcodeEntry::new(dec_hl),
+        0x36 => OpcodeEntry::new(ld_hl_n8),
+        0x04 | 0x0C | 0x14 | 0x1C | 0x24 | 0x2C | 0x3C => OpcodeEntry::new(inc_r8),
+        0x05 | 0x0D | 0x15 | 0x1D | 0x25 | 0x2D | 0x3D => OpcodeEntry::new(dec_r8),
+        0x06 | 0x0E | 0x16 | 0x1E | 0x26 | 0x2E | 0x3E => OpcodeEntry::new(ld_r8_n8),
+        0x08 => OpcodeEntry::new(ld_n16_sp),
+        0x18 => OpcodeEntry::new(jr),
+        0x20 | 0x28 | 0x30 | 0x38 => OpcodeEntry::new(jr_cc),
+
+        // LD r8, r8 & LD r8, (HL) & LD (HL), r8
+        0x40..=0x7F => {
+            if opcode & 0b111 == HL_INDEX {
+                OpcodeEntry::new(ld_r8_hl)
+            } else if (opcode >> 3) & 0b111 == HL_INDEX {
+                OpcodeEntry::new(ld_hl_r8)
+            } else {
+                OpcodeEntry::new(ld_r8_r8)
+            }
+        }
+
+        // ADD, ADC, SUB, SBC, AND, XOR, OR & CP with a register or (HL)
+        0x80..=0xBF => OpcodeEntry::new(alu_handler((opcode >> 3) & 0b111, opcode & 0b111 == HL_INDEX)),
+        // The same with an immediate
+        0xC6 | 0xCE | 0xD6 | 0xDE | 0xE6 | 0xEE | 0xF6 | 0xFE => OpcodeEntry::new(alu_n8_handler((opcode >> 3) & 0b111)),
+
+        0xC0 | 0xC8 | 0xD0 | 0xD8 => OpcodeEntry::jump(ret_cc),
+        0xC9 => OpcodeEntry::jump(ret),
+        0xD9 => OpcodeEntry::jump(reti),
+        0xC2 | 0xCA | 0xD2 | 0xDA => OpcodeEntry::jump(jp_cc),
+        0xC3 => OpcodeEntry::jump(jp),
+        0xE9 => OpcodeEntry::jump(jp_hl),
+        0xC4 | 0xCC | 0xD4 | 0xDC => OpcodeEntry::jump(call_cc),
+        0xCD => OpcodeEntry::jump(call),
+        0xC7 | 0xCF | 0xD7 | 0xDF | 0xE7 | 0xEF | 0xF7 | 0xFF => OpcodeEntry::jump(rst),
+        0xC1 | 0xD1 | 0xE1 | 0xF1 => OpcodeEntry::new(pop),
+        0xC5 | 0xD5 | 0xE5 | 0xF5 => OpcodeEntry::new(push),
+
+        0xE0 => OpcodeEntry::new(ldh_a8_a),
+        0xF0 => OpcodeEntry::new(ldh_a_a8),
+        0xE2 => OpcodeEntry::new(ldh_c_a),
+        0xF2 => OpcodeEntry::new(ldh_a_c),
+        0xEA => OpcodeEntry::new(ld_n16_a),
+        0xFA => OpcodeEntry::new(ld_a_n16),
+        0xE8 => OpcodeEntry::new(add_sp_e8),
+        0xF8 => OpcodeEntry::new(ld_hl_sp_plus_e8),
+        0xF9 => OpcodeEntry::new(ld_sp_hl),
+
+        // 0xCB is handled by the prefixed table, the rest doesn't exist
+        _ => None,
+    }
+}
+
+const fn alu_handler(operation: u8, hl: bool) -> InstructionHandler {
+    match (operation, hl) {
+        (0, false) => add_a_r8,
+        (0, true) => add_a_hl,
+        (1, false) => adc_a_r8,
+        (1, true) => adc_a_hl,
+        (2, false) => sub_a_r8,
+        (2, true) => sub_a_hl,
+        (3, false) => sbc_a_r8,
+        (3, true) => sbc_a_hl,
+        (4, false) => and_a_r8,
+        (4, true) => and_a_hl,
+        (5, false) => xor_a_r8,
+        (5, true) => xor_a_hl,
+        (6, false) => or_a_r8,
+        (6, true) => or_a_hl,
+        (_, false) => cp_a_r8,
+        (_, true) => cp_a_hl,
+    }
+}
+
+const fn alu_n8_handler(operation: u8) -> InstructionHandler {
+    match operation {
+        0 => add_a_n8,
+        1 => adc_a_n8,
+        2 => sub_a_n8,
+        3 => sbc_a_n8,
+        4 => and_a_n8,
+        5 => xor_a_n8,
+        6 => or_a_n8,
+        _ => cp_a_n8,
+    }
+}
+
+const fn prefixed_handler(opcode: u8) -> InstructionHandler {
+    let hl = opcode & 0b111 == HL_INDEX;
+
+    match (opcode >> 3, hl) {
+        (0x00, false) => rlc,
+        (0x00, true) => rlc_hl,
+        (0x01, false) => rrc,
+        (0x01, true) => rrc_hl,
+        (0x02, false) => rl,
+        (0x02, true) => rl_hl,
+        (0x03, false) => rr,
+        (0x03, true) => rr_hl,
+        (0x04, false) => sla,
+        (0x04, true) => sla_hl,
+        (0x05, false) => sra,
+        (0x05, true) => sra_hl,
+        (0x06, false) => swap,
+        (0x06, true) => swap_hl,
+        (0x07, false) => srl,
+        (0x07, true) => srl_hl,
+        (0x08..=0x0F, false) => bit,
+        (0x08..=0x0F, true) => bit_hl,
+        (0x10..=0x17, false) => res,
+        (0x10..=0x17, true) => res_hl,
+        (_, false) => set,
+        (_, true) => set_hl,
+    }
+}
+
+/// The 8-bit register encoded in 3 bits of an opcode
+fn r8(index: u8) -> Register8Bit {
+    match index & 0b111 {
+        0 => Register8Bit::B,
+        1 => Register8Bit::C,
+        2 => Register8Bit::D,
+        3 => Register8Bit::E,
+        4 => Register8Bit::H,
+        5 => Register8Bit::L,
+        7 => Register8Bit::A,
+        _ => unreachable!("(HL) has its own handlers"),
+    }
+}
+
+/// The source register in bits 0-2
+fn source_r8(opcode: u8) -> Register8Bit {
+    r8(opcode)
+}
+
+/// The target register in bits 3-5
+fn target_r8(opcode: u8) -> Register8Bit {
+    r8(opcode >> 3)
+}
+
+/// The 16-bit register in bits 4-5
+fn r16(opcode: u8) -> Register16Bit {
+    match (opcode >> 4) & 0b11 {
+        0 => Register16Bit::BC,
+        1 => Register16Bit::DE,
+        2 => Register16Bit::HL,
+        _ => Register16Bit::SP,
+    }
+}
+
+/// The 16-bit register of PUSH & POP in bits 4-5, AF takes the place of SP
+fn r16_stack(opcode: u8) -> Register16Bit {
+    match r16(opcode) {
+        Register16Bit::SP => Register16Bit::AF,
+        register => register,
+    }
+}
+
+/// The condition in bits 3-4
+fn condition(opcode: u8) -> InstructionCondition {
+    match (opcode >> 3) & 0b11 {
+        0 => InstructionCondition::NotZero,
+        1 => InstructionCondition::Zero,
+        2 => InstructionCondition::NotCarry,
+        _ => InstructionCondition::Carry,
+    }
+}
+
+/// The bit of BIT, RES & SET in bits 3-5
+fn bit_index(opcode: u8) -> u8 {
+    (opcode >> 3) & 0b111
+}
+
+impl CPU {
+    /// Fetch a byte of the current instruction at PC + offset, taking one M-cycle
+    pub(super) fn fetch_instruction_byte(&mut self, offset: u16) -> u8 {
+        if let Some(bytes) = self.injected_instruction {
+            self.tick();
+            return bytes[offset as usize];
+        }
+
+        let pc = self.get_16bit_register(Register16Bit::PC);
+        self.read_memory(pc.wrapping_add(offset))
+    }
+
+    /// Fetch the 8-bit immediate following the opcode
+    fn fetch_n8(&mut self) -> u8 {
+        self.fetch_instruction_byte(1)
+    }
+
+    /// Fetch the 16-bit immediate following the opcode, low byte first
+    fn fetch_n16(&mut self) -> u16 {
+        let low = self.fetch_instruction_byte(1);
+        let high = self.fetch_instruction_byte(2);
+        u16::from_le_bytes([low, high])
+    }
+
+    /// Gets a 8-bit value from the address in HL
+    fn get_n8_from_hl(&mut self) -> u8 {
+        self.read_memory(self.get_16bit_register(Register16Bit::HL))
+    }
+}
+
+fn nop(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.nop() }
+fn stop(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.stop() }
+fn halt(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.halt() }
+fn di(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.di() }
+fn ei(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ei() }
+fn daa(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.daa() }
+fn cpl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.cpl() }
+fn scf(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.scf() }
+fn ccf(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ccf() }
+// RLCA and RLC A are two different instructions, the same goes for the other rotations
+fn rlca(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rl_c_a() }
+fn rrca(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rr_c_a() }
+fn rla(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rl_a() }
+fn rra(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rr_a() }
+
+fn ld_r16_n16(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.fetch_n16();
+    match r16(opcode) {
+        Register16Bit::SP => cpu.ld_sp_n16(value),
+        register => cpu.ld_r16_n16(register, value),
+    }
+}
+fn ld_r16_a(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.ld_r16_a(r16(opcode)) }
+fn ld_a_r16(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.ld_a_r16(r16(opcode)) }
+fn ld_hli_a(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ld_hli_a() }
+fn ld_a_hli(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ld_a_hli() }
+fn ld_hld_a(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ld_hld_a() }
+fn ld_a_hld(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ld_a_hld() }
+fn inc_r16(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    match r16(opcode) {
+        Register16Bit::SP => cpu.inc_sp(),
+        register => cpu.inc_r16(register),
+    }
+}
+fn dec_r16(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    match r16(opcode) {
+        Register16Bit::SP => cpu.dec_sp(),
+        register => cpu.dec_r16(register),
+    }
+}
+fn add_hl_r16(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.add_hl_r16(r16(opcode)) }
+fn inc_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.inc_hl() }
+fn dec_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.dec_hl() }
+fn inc_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.inc(target_r8(opcode)) }
+fn dec_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.dec_r8(target_r8(opcode)) }
+fn ld_hl_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.ld_hl_n8(value)
+}
+fn ld_r8_n8(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.ld_r8_n8(target_r8(opcode), value)
+}
+fn ld_n16_sp(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    cpu.ld_n16_sp(address)
+}
+fn jr(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let offset = cpu.fetch_n8() as i8;
+    cpu.jr_n16(offset)
+}
+fn jr_cc(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let offset = cpu.fetch_n8() as i8;
+    let cc = cpu.check_condition(&condition(opcode));
+    cpu.jr_cc_n16(cc, offset)
+}
+
+fn ld_r8_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.ld_r8_r8(target_r8(opcode), source_r8(opcode)) }
+fn ld_r8_hl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.ld_r8_hl(target_r8(opcode)) }
+fn ld_hl_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.ld_hl_r8(source_r8(opcode)) }
+
+fn add_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.add_a_r8(source_r8(opcode)) }
+fn add_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.add_a_hl() }
+fn add_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.add_a_n8(value)
+}
+fn adc_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.adc_a_r8(source_r8(opcode)) }
+fn adc_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.adc_a_hl() }
+fn adc_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.adc_a_n8(value)
+}
+fn sub_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.get_8bit_register(source_r8(opcode));
+    cpu.sub_and_subc(value, 1, 1, false)
+}
+fn sub_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.get_n8_from_hl();
+    cpu.sub_and_subc(value, 2, 1, false)
+}
+fn sub_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.sub_and_subc(value, 2, 2, false)
+}
+fn sbc_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.get_8bit_register(source_r8(opcode));
+    cpu.sub_and_subc(value, 1, 1, true)
+}
+fn sbc_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.get_n8_from_hl();
+    cpu.sub_and_subc(value, 2, 1, true)
+}
+fn sbc_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.sub_and_subc(value, 2, 2, true)
+}
+fn and_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.and_a_r8(source_r8(opcode)) }
+fn and_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.and_a_hl() }
+fn and_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.and_a_n8(value)
+}
+fn xor_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.get_8bit_register(source_r8(opcode));
+    cpu.xor(value, 1, 1)
+}
+fn xor_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.get_n8_from_hl();
+    cpu.xor(value, 2, 1)
+}
+fn xor_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.xor(value, 2, 2)
+}
+fn or_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let value = cpu.get_8bit_register(source_r8(opcode));
+    cpu.or(value, 1, 1)
+}
+fn or_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.get_n8_from_hl();
+    cpu.or(value, 2, 1)
+}
+fn or_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.or(value, 2, 2)
+}
+fn cp_a_r8(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.cp_a_r8(source_r8(opcode)) }
+fn cp_a_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.cp_a_hl() }
+fn cp_a_n8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8();
+    cpu.cp_a_n8(value)
+}
+
+fn ret_cc(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let cc = cpu.check_condition(&condition(opcode));
+    cpu.ret_cc(cc)
+}
+fn ret(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ret() }
+fn reti(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.reti() }
+fn jp_cc(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    let cc = cpu.check_condition(&condition(opcode));
+    cpu.jp_cc_n16(cc, address)
+}
+fn jp(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    cpu.jp_n16(address)
+}
+fn jp_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.jp_hl() }
+fn call_cc(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    let cc = cpu.check_condition(&condition(opcode));
+    cpu.call_cc_n16(cc, address)
+}
+fn call(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    cpu.call_n16(address)
+}
+// The vector is encoded in bits 3-5
+fn rst(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.rst_vec(opcode & 0b0011_1000) }
+fn pop(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    match r16_stack(opcode) {
+        Register16Bit::AF => cpu.pop_af(),
+        register => cpu.pop_r16(register),
+    }
+}
+fn push(cpu: &mut CPU, opcode: u8) -> InstructionResult {
+    match r16_stack(opcode) {
+        Register16Bit::AF => cpu.push_af(),
+        register => cpu.push_r16(register),
+    }
+}
+
+fn ldh_a8_a(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n8();
+    cpu.ldh_a8_a(address)
+}
+fn ldh_a_a8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n8();
+    cpu.ldh_a_a8(address)
+}
+fn ldh_c_a(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ldh_c_a() }
+fn ldh_a_c(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ldh_a_c() }
+fn ld_n16_a(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    cpu.ld_n16_a(address)
+}
+fn ld_a_n16(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let address = cpu.fetch_n16();
+    cpu.ld_a_n16(address)
+}
+fn add_sp_e8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8() as i8;
+    cpu.add_sp_e8(value)
+}
+fn ld_hl_sp_plus_e8(cpu: &mut CPU, _: u8) -> InstructionResult {
+    let value = cpu.fetch_n8() as i8;
+    cpu.ld_hl_sp_plus_e8(value)
+}
+fn ld_sp_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.ld_sp_hl() }
+
+fn rlc(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.rl_c_r8(source_r8(opcode)) }
+fn rlc_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rl_c_hl() }
+fn rrc(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.rr_c_r8(source_r8(opcode)) }
+fn rrc_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rr_c_hl() }
+fn rl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.rl_r8(source_r8(opcode)) }
+fn rl_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rl_hl() }
+fn rr(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.rr_r8(source_r8(opcode)) }
+fn rr_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.rr_hl() }
+fn sla(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.sla_r8(source_r8(opcode)) }
+fn sla_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.sla_hl() }
+fn sra(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.sra_r8(source_r8(opcode)) }
+fn sra_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.sra_hl() }
+fn swap(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.swap_r8(source_r8(opcode)) }
+fn swap_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.swap_hl() }
+fn srl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.srl_r8(source_r8(opcode)) }
+fn srl_hl(cpu: &mut CPU, _: u8) -> InstructionResult { cpu.srl_hl() }
+fn bit(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.bit_u3_r8(bit_index(opcode), source_r8(opcode)) }
+fn bit_hl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.bit_u3_hl(bit_index(opcode)) }
+fn res(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.res_u3_r8(bit_index(opcode), source_r8(opcode)) }
+fn res_hl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.res_u3_hl(bit_index(opcode)) }
+fn set(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.set_u3_r8(bit_index(opcode), source_r8(opcode)) }
+fn set_hl(cpu: &mut CPU, opcode: u8) -> InstructionResult { cpu.set_u3_hl(bit_index(opcode)) }
+
+#[test]
+pub fn opcode_table_test() {
+    use super::{decode::PREFIX_OPCODE, instructions::{InstParam, Instructions}};
+
+    // The table and the decoder agree on which opcodes exist
+    for opcode in 0..=0xFFu8 {
+        if opcode == PREFIX_OPCODE {
+            continue;
+        }
+
+        let decoded = CPU::decode_bytes([opcode, 0, 0]).unwrap();
+        let is_invalid = matches!(decoded, Instructions::INVALID(_));
+        assert_eq!(UNPREFIXED_OPCODES[opcode as usize].is_none(), is_invalid, "{:#04X}", opcode);
+    }
+
+    // Encoding is the inverse of decoding
+    let instruction = Instructions::LD(InstParam::Number16Bit(0xFF01), InstParam::Register8Bit(Register8Bit::A));
+    assert_eq!(instruction.encode(), Some([0xEA, 0x01, 0xFF]));
+    assert_eq!(Instructions::RST(InstParam::Number8Bit(0x18)).encode().map(|bytes| bytes[0]), Some(0xDF));
+    assert_eq!(Instructions::ADD(InstParam::Register16Bit(Register16Bit::DE)).encode(), None);
+}
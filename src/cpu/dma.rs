@@ -1,37 +1,202 @@
 use crate::mmu::MemoryOperations;
 
-use super::CPU;
+use super::{clock::T_CYCLES_PER_M_CYCLE, scheduler::Event, CPU};
 
 const DMA_REGISTER_ADDR: u16 = 0xFF46;
 const OAM_BASE: u16 = 0xFE00;
 
+/// Amount of bytes (and thus M-cycles) a DMA transfer takes
+const DMA_LENGTH: u8 = 0xA0;
+/// M-cycles between writing to the DMA register and the first byte being transferred
+const DMA_START_DELAY: u64 = 1;
+/// Value the CPU reads from OAM while a DMA transfer is running
+const DMA_OAM_BLOCKED_VALUE: u8 = 0xFF;
+
+/// The DMG has two separate buses the DMA can block
+/// See: https://gbdev.io/pandocs/OAM_DMA_Transfer.html#oam-dma-bus-conflicts
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum DmaBus {
+    /// ROM, External RAM & Working RAM
+    External,
+    /// VRAM
+    Video,
+}
+
+impl DmaBus {
+    /// Get the bus an address is accessed through, None for addresses within the CPU (OAM, IO & HRAM)
+    fn from_address(address: u16) -> Option<DmaBus> {
+        match address {
+            0x0000..=0x7FFF | 0xA000..=0xFDFF => Some(DmaBus::External),
+            0x8000..=0x9FFF => Some(DmaBus::Video),
+            _ => None,
+        }
+    }
+}
+
 impl CPU {
-    /// Handles the DMA circuit
+    /// The DMA register has been written to, the transfer starts after a delay
+    /// Writing to it while a transfer is running restarts it,
+    /// the old transfer keeps running until the new one has started
     /// See: https://gbdev.io/pandocs/OAM_DMA_Transfer.html
-    pub fn dma_routine(&mut self) {
-        if !self.dma_active {
-            return;
-        }
-
-        // Get DMA base from memory
-        let dma_base = (self.mmu.read_byte(DMA_REGISTER_ADDR) as u16) << 8;
-        
-        // Get the current byte to be read by combining the base + dma_current_offset
-        let source_addr = dma_base + self.dma_current_offset as u16;
-        let target_addr = OAM_BASE + self.dma_current_offset as u16;
-        
-        // Write from memory to OAM
-        self.mmu.write_byte(target_addr, self.mmu.read_byte(source_addr));
-
-        // Append offset
-        let (_, overflow) = self.dma_current_offset.overflowing_add(1);
-
-        // If the offset overflows we reached the end
-        if overflow {
-            self.dma_active = false;
-            self.dma_current_offset = 0;
+    pub fn request_dma(&mut self) {
+        self.scheduler.schedule(Event::DmaStart, DMA_START_DELAY * T_CYCLES_PER_M_CYCLE);
+    }
+
+    /// See `Event::DmaStart`
+    pub fn start_dma(&mut self) {
+        // The bytes of the old transfer up to this M-cycle have been transferred
+        if self.dma_active {
+            let transferred = self.get_dma_progress();
+            self.copy_dma_bytes(transferred);
+        }
+
+        self.dma_active = true;
+        self.dma_current_offset = 0;
+        self.dma_source = Self::get_dma_source(self.mmu.read_byte(DMA_REGISTER_ADDR));
+        self.dma_start_time = self.scheduler.get_now();
+        self.scheduler.schedule(Event::DmaEnd, DMA_LENGTH as u64 * T_CYCLES_PER_M_CYCLE);
+    }
+
+    /// See `Event::DmaEnd`
+    pub fn finish_dma(&mut self) {
+        self.copy_dma_bytes(DMA_LENGTH);
+        self.dma_active = false;
+        self.dma_current_offset = 0;
+    }
+
+    /// Transfer the bytes due up to the current M-cycle, called on every tick
+    /// The PPU reads OAM directly and the source can change with the bank, VRAM lock or patches,
+    /// so every byte has to be read & written in its own M-cycle
+    pub fn sync_dma(&mut self) {
+        if self.dma_active {
+            let transferred = self.get_dma_progress();
+            self.copy_dma_bytes(transferred);
+        }
+    }
+
+    /// Index of the byte the DMA transfers during the current M-cycle
+    fn get_dma_progress(&self) -> u8 {
+        let m_cycles = (self.scheduler.get_now() - self.dma_start_time) / T_CYCLES_PER_M_CYCLE;
+        m_cycles.min(DMA_LENGTH as u64) as u8
+    }
+
+    /// Copy the bytes that haven't been transferred yet up to `until` (exclusive)
+    fn copy_dma_bytes(&mut self, until: u8) {
+        for offset in self.dma_current_offset..until {
+            let source_addr = self.dma_source + offset as u16;
+            let target_addr = OAM_BASE + offset as u16;
+
+            // Write from memory to OAM, the DMA isn't blocked by the PPU
+            let value = self.mmu.read_byte(source_addr);
+            self.mmu.OAM.write_byte(target_addr, value);
+        }
+
+        self.dma_current_offset = self.dma_current_offset.max(until);
+    }
+
+    /// Get the address the DMA reads from based on the value written to the DMA register
+    /// Everything above 0xDFFF ends up in Working RAM
+    fn get_dma_source(value: u8) -> u16 {
+        let source = (value as u16) << 8;
+
+        if source >= 0xE000 {
+            source - 0x2000
         } else {
-            self.dma_current_offset += 1;
+            source
         }
     }
-}
\ No newline at end of file
+
+    pub fn is_dma_active(&self) -> bool {
+        self.dma_active
+    }
+
+    /// Get the value the CPU reads while the DMA blocks the address, None if the address is accessible
+    /// OAM is blocked entirely, other addresses only if they share the bus with the DMA source
+    pub fn get_dma_conflict_value(&self, address: u16) -> Option<u8> {
+        if !self.dma_active {
+            return None;
+        }
+
+        if (0xFE00..=0xFEFF).contains(&address) {
+            return Some(DMA_OAM_BLOCKED_VALUE);
+        }
+
+        match DmaBus::from_address(address) {
+            // The CPU reads the byte the DMA is currently transferring
+            Some(bus) if Some(bus) == DmaBus::from_address(self.dma_source) => {
+                Some(self.mmu.read_byte(self.dma_source + self.get_dma_progress() as u16))
+            }
+            _ => None,
+        }
+    }
+}
+
+#[test]
+pub fn dma_timing_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    for offset in 0..DMA_LENGTH as u16 {
+        cpu.mmu.write_byte(0xC000 + offset, offset as u8 + 1);
+    }
+    cpu.mmu.write_byte(0xFF80, 0x42);
+
+    cpu.write_memory(DMA_REGISTER_ADDR, 0xC0);
+
+    // Nothing is blocked during the start delay
+    assert_eq!(cpu.read_memory(OAM_BASE), 0);
+
+    // OAM & the external bus are blocked, HRAM & VRAM are not
+    assert_eq!(cpu.read_memory(OAM_BASE), DMA_OAM_BLOCKED_VALUE);
+    assert_eq!(cpu.read_memory(0x0000), 2);
+    assert_eq!(cpu.read_memory(0xFF80), 0x42);
+    assert_eq!(cpu.read_memory(0x8000), 0);
+
+    // Writes to OAM are ignored
+    cpu.write_memory(OAM_BASE, 0xAA);
+
+    // The PPU reads OAM directly, it sees the bytes transferred so far
+    assert_eq!(cpu.mmu.OAM.read_byte(OAM_BASE), 1);
+    assert_eq!(cpu.mmu.OAM.read_byte(OAM_BASE + 0x50), 0);
+
+    while cpu.is_dma_active() {
+        cpu.idle_cycle();
+    }
+
+    // The transfer ends 160 M-cycles after it started
+    assert_eq!(cpu.get_cycles(), 1 + 1 + DMA_LENGTH as u64 + 1);
+    for offset in 0..DMA_LENGTH as u16 {
+        assert_eq!(cpu.read_memory(OAM_BASE + offset), offset as u8 + 1);
+    }
+}
+
+#[test]
+pub fn dma_restart_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    cpu.mmu.write_byte(0xC000, 0x11);
+    cpu.mmu.write_byte(0xD000, 0x22);
+    cpu.mmu.write_byte(0xD001, 0x33);
+
+    cpu.write_memory(DMA_REGISTER_ADDR, 0xC0);
+    for _ in 0..10 {
+        cpu.idle_cycle();
+    }
+
+    // 0xF0 mirrors 0xD0, the old transfer keeps OAM blocked while the new one starts
+    cpu.write_memory(DMA_REGISTER_ADDR, 0xF0);
+    assert_eq!(cpu.read_memory(OAM_BASE), DMA_OAM_BLOCKED_VALUE);
+    assert_eq!(cpu.read_memory(OAM_BASE), DMA_OAM_BLOCKED_VALUE);
+
+    // The new transfer ends 160 M-cycles after it started
+    for _ in 0..DMA_LENGTH - 1 {
+        cpu.idle_cycle();
+    }
+    assert!(cpu.is_dma_active());
+    cpu.idle_cycle();
+    assert!(!cpu.is_dma_active());
+
+    assert_eq!(cpu.read_memory(OAM_BASE), 0x22);
+    assert_eq!(cpu.read_memory(OAM_BASE + 1), 0x33);
+}
@@ -0,0 +1,282 @@
+use crate::{
+    mmu::MemoryOperations,
+    save_state::{StateReader, StateValue, StateWriter},
+};
+
+use super::{registers::Register16Bit, scheduler::Event, CPU};
+
+const HEADER_CGB_FLAG_ADDRESS: u16 = 0x0143;
+const HEADER_CHECKSUM_ADDRESS: u16 = 0x014D;
+
+const DMG_BOOT_ROM_SIZE: usize = 0x100;
+const CGB_BOOT_ROM_SIZE: usize = 0x900;
+
+/// IO registers after the boot ROM of a DMG finished
+/// See: https://gbdev.io/pandocs/Power_Up_Sequence.html#hardware-registers
+const DMG_POST_BOOT_IO: [(u16, u8); 30] = [
+    (0xFF01, 0x00), // SB
+    (0xFF02, 0x7E), // SC
+    (0xFF05, 0x00), // TIMA
+    (0xFF06, 0x00), // TMA
+    (0xFF07, 0xF8), // TAC
+    (0xFF0F, 0xE1), // IF
+    (0xFF10, 0x80), // NR10
+    (0xFF11, 0xBF), // NR11
+    (0xFF12, 0xF3), // NR12
+    (0xFF13, 0xFF), // NR13
+    (0xFF14, 0xBF), // NR14
+    (0xFF16, 0x3F), // NR21
+    (0xFF17, 0x00), // NR22
+    (0xFF18, 0xFF), // NR23
+    (0xFF19, 0xBF), // NR24
+    (0xFF1A, 0x7F), // NR30
+    (0xFF1B, 0xFF), // NR31
+    (0xFF1C, 0x9F), // NR32
+    (0xFF1D, 0xFF), // NR33
+    (0xFF1E, 0xBF), // NR34
+    (0xFF20, 0xFF), // NR41
+    (0xFF21, 0x00), // NR42
+    (0xFF22, 0x00), // NR43
+    (0xFF23, 0xBF), // NR44
+    (0xFF24, 0x77), // NR50
+    (0xFF25, 0xF3), // NR51
+    (0xFF26, 0xF1), // NR52
+    (0xFF40, 0x91), // LCDC
+    (0xFF46, 0xFF), // DMA
+    (0xFF47, 0xFC), // BGP
+];
+
+/// The Game Boy model that is emulated
+/// See: https://gbdev.io/pandocs/Power_Up_Sequence.html
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum HardwareModel {
+    /// Early original Game Boy
+    DMG0,
+    /// Original Game Boy
+    #[default]
+    DMG,
+    /// Game Boy Pocket
+    MGB,
+    /// Super Game Boy
+    SGB,
+    /// Super Game Boy 2
+    SGB2,
+    /// Game Boy Color
+    CGB,
+    /// Game Boy Advance
+    AGB,
+}
+
+const HARDWARE_MODELS: [HardwareModel; 7] = [
+    HardwareModel::DMG0,
+    HardwareModel::DMG,
+    HardwareModel::MGB,
+    HardwareModel::SGB,
+    HardwareModel::SGB2,
+    HardwareModel::CGB,
+    HardwareModel::AGB,
+];
+
+impl StateValue for HardwareModel {
+    fn write_to(&self, state: &mut StateWriter) {
+        state.write(&(*self as u8));
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        let index = state.read::<u8>()?;
+        HARDWARE_MODELS
+            .get(index as usize)
+            .copied()
+            .ok_or_else(|| format!("Invalid hardware model {}", index))
+    }
+}
+
+impl HardwareModel {
+    /// Size of the boot ROM of this model
+    pub fn get_boot_rom_size(&self) -> usize {
+        match self {
+            HardwareModel::CGB | HardwareModel::AGB => CGB_BOOT_ROM_SIZE,
+            _ => DMG_BOOT_ROM_SIZE,
+        }
+    }
+
+    /// CRC32 checksums of the known boot ROM dumps of this model
+    /// The AGB uses a slightly modified CGB boot ROM
+    fn get_boot_rom_checksums(&self) -> &'static [u32] {
+        match self {
+            HardwareModel::DMG0 => &[0xC2F5CC97],
+            HardwareModel::DMG => &[0x59C8598E],
+            HardwareModel::MGB => &[0xE6920754],
+            HardwareModel::SGB => &[0xEC8A83B9],
+            HardwareModel::SGB2 => &[0x53D0DD63],
+            HardwareModel::CGB => &[0x41884E46],
+            HardwareModel::AGB => &[0xFFD6E9D2],
+        }
+    }
+
+    /// Check whether the boot ROM belongs to this model based on its size and checksum
+    pub fn check_boot_rom(&self, boot_rom: &[u8]) -> Result<(), String> {
+        if boot_rom.len() != self.get_boot_rom_size() {
+            return Err(format!(
+                "Boot ROM for {:?} must be {:#X} bytes, got {:#X}",
+                self,
+                self.get_boot_rom_size(),
+                boot_rom.len()
+            ));
+        }
+
+        let checksum = crc32fast::hash(boot_rom);
+        if !self.get_boot_rom_checksums().contains(&checksum) {
+            return Err(format!("Unknown boot ROM for {:?} (CRC32: {:#010X})", self, checksum));
+        }
+
+        Ok(())
+    }
+
+    /// AF, BC, DE & HL after the boot ROM finished
+    /// The DMG boot ROM sets H & C if the header checksum is not 0,
+    /// the color models only boot into CGB mode for cartridges supporting it
+    fn get_post_boot_registers(&self, header_checksum: u8, cgb_cartridge: bool) -> [u16; 4] {
+        let dmg_flags = if header_checksum == 0 { 0x80 } else { 0xB0 };
+
+        match self {
+            HardwareModel::DMG0 => [0x0100, 0xFF13, 0x00C1, 0x8403],
+            HardwareModel::DMG => [0x0100 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            HardwareModel::MGB => [0xFF00 | dmg_flags, 0x0013, 0x00D8, 0x014D],
+            HardwareModel::SGB => [0x0100, 0x0014, 0x0000, 0xC060],
+            HardwareModel::SGB2 => [0xFF00, 0x0014, 0x0000, 0xC060],
+            HardwareModel::CGB if cgb_cartridge => [0x1180, 0x0000, 0xFF56, 0x000D],
+            HardwareModel::CGB => [0x1180, 0x0000, 0x0008, 0x007C],
+            HardwareModel::AGB if cgb_cartridge => [0x1100, 0x0100, 0xFF56, 0x000D],
+            HardwareModel::AGB => [0x1100, 0x0100, 0x0008, 0x007C],
+        }
+    }
+
+    /// The internal counter of the timer after the boot ROM finished
+    /// Only DIV of the DMG models is documented, the others depend on the boot duration
+    fn get_post_boot_divider(&self) -> u16 {
+        match self {
+            HardwareModel::DMG0 => 0x1800,
+            HardwareModel::DMG | HardwareModel::MGB => 0xABCC,
+            _ => 0x0000,
+        }
+    }
+
+    /// IO registers after the boot ROM finished
+    fn get_post_boot_io(&self) -> Vec<(u16, u8)> {
+        let mut io = DMG_POST_BOOT_IO.to_vec();
+
+        for (address, value) in io.iter_mut() {
+            *value = match (self, *address) {
+                (HardwareModel::SGB | HardwareModel::SGB2, 0xFF26) => 0xF0,
+                (HardwareModel::CGB | HardwareModel::AGB, 0xFF02) => 0x7F,
+                (HardwareModel::CGB | HardwareModel::AGB, 0xFF46) => 0x00,
+                _ => *value,
+            };
+        }
+
+        io
+    }
+}
+
+impl CPU {
+    /// Set the emulated model, the embedded boot ROM is only used for the DMG
+    pub fn set_hardware_model(&mut self, model: HardwareModel) {
+        self.model = model;
+
+        if model != HardwareModel::DMG {
+            self.mmu.set_bootrom_enabled(false);
+        }
+    }
+
+    pub fn get_hardware_model(&self) -> HardwareModel {
+        self.model
+    }
+
+    /// Load a boot ROM dump, it has to match the current model
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) -> Result<(), String> {
+        self.model.check_boot_rom(&boot_rom)?;
+        self.mmu.bank_00.load_boot_rom(boot_rom);
+        self.mmu.set_bootrom_enabled(true);
+        Ok(())
+    }
+
+    /// Skip the bootrom
+    /// Set the registers to the state the boot ROM of the current model leaves behind
+    /// Used for: https://robertheaton.com/gameboy-doctor/
+    pub fn skip_boot_rom(&mut self) {
+        self.mmu.set_bootrom_enabled(false);
+
+        let header_checksum = self.mmu.read_byte(HEADER_CHECKSUM_ADDRESS);
+        let cgb_cartridge = self.mmu.read_byte(HEADER_CGB_FLAG_ADDRESS) & 0x80 != 0;
+        let [af, bc, de, hl] = self.model.get_post_boot_registers(header_checksum, cgb_cartridge);
+        self.set_16bit_register(Register16Bit::AF, af);
+        self.set_16bit_register(Register16Bit::BC, bc);
+        self.set_16bit_register(Register16Bit::DE, de);
+        self.set_16bit_register(Register16Bit::HL, hl);
+        self.set_16bit_register(Register16Bit::SP, 0xFFFE);
+        self.set_16bit_register(Register16Bit::PC, 0x0100);
+
+        for (address, value) in self.model.get_post_boot_io() {
+            self.mmu.IO.set_register(address, value);
+        }
+        self.mmu.interrupt_enable = 0x00;
+
+        // LCDC has been set without side effects, so the PPU has to pick it up itself
+        self.scheduler.schedule(Event::Ppu, 0);
+
+        // TAC has been set without side effects as well
+        self.timer_control = self.mmu.read_byte(0xFF07);
+        self.set_divider(self.model.get_post_boot_divider());
+
+        // Set Joypad register
+        self.mmu.write_byte(0xFF00, 0b1111_1111);
+    }
+}
+
+#[test]
+pub fn post_boot_state_test() {
+    use super::registers::Register8Bit;
+
+    let mut rom = vec![0; 0x8000];
+    rom[HEADER_CHECKSUM_ADDRESS as usize] = 0x12;
+
+    let mut cpu = CPU::new(rom.clone());
+    cpu.skip_boot_rom();
+    assert_eq!(cpu.get_16bit_register(Register16Bit::AF), 0x01B0);
+    assert_eq!(cpu.get_16bit_register(Register16Bit::HL), 0x014D);
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0xAB);
+    assert_eq!(cpu.mmu.read_byte(0xFF26), 0xF1);
+    assert_eq!(cpu.mmu.read_byte(0xFF40), 0x91);
+    assert_eq!(cpu.mmu.read_byte(0xFF47), 0xFC);
+    // Writing the DMA register through the MMU would have started a transfer
+    assert!(!cpu.mmu.IO.is_dma_requested());
+
+    let mut cpu = CPU::new(rom);
+    cpu.set_hardware_model(HardwareModel::SGB2);
+    assert!(!cpu.is_boot_rom_enabled());
+    cpu.skip_boot_rom();
+    assert_eq!(cpu.get_8bit_register(Register8Bit::A), 0xFF);
+    assert_eq!(cpu.get_16bit_register(Register16Bit::HL), 0xC060);
+    assert_eq!(cpu.mmu.read_byte(0xFF26), 0xF0);
+}
+
+#[test]
+pub fn boot_rom_check_test() {
+    let dmg_boot_rom = include_bytes!("../../bin/DMG_ROM.bin").to_vec();
+
+    assert!(HardwareModel::DMG.check_boot_rom(&dmg_boot_rom).is_ok());
+    assert!(HardwareModel::MGB.check_boot_rom(&dmg_boot_rom).is_err());
+    assert!(HardwareModel::CGB.check_boot_rom(&dmg_boot_rom).is_err());
+    assert!(HardwareModel::DMG.check_boot_rom(&dmg_boot_rom[..0x80]).is_err());
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.set_hardware_model(HardwareModel::MGB);
+    assert!(cpu.load_boot_rom(dmg_boot_rom.clone()).is_err());
+    assert!(!cpu.is_boot_rom_enabled());
+
+    cpu.set_hardware_model(HardwareModel::DMG);
+    assert!(cpu.load_boot_rom(dmg_boot_rom.clone()).is_ok());
+    assert!(cpu.is_boot_rom_enabled());
+    assert_eq!(cpu.mmu.read_byte(0x0000), dmg_boot_rom[0]);
+}
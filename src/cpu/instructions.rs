@@ -1,4 +1,4 @@
-use super::registers::{Register16Bit, Register8Bit};
+use super::{decode::PREFIX_OPCODE, registers::{Register16Bit, Register8Bit}, CPU};
 
 pub mod arithmetic_and_logic;
 pub mod bit_shift;
@@ -147,4 +147,98 @@ pub enum Instructions {
     STOP, 
     
     INVALID(u8), // Invalid instruction
-}
\ No newline at end of file
+}
+impl InstParam {
+    /// The amount of immediate bytes following the opcode for this parameter
+    fn immediate_bytes(&self) -> u8 {
+        match self {
+            InstParam::Number8Bit(_) | InstParam::SignedNumber8Bit(_) => 1,
+            InstParam::Number16Bit(_) => 2,
+            _ => 0,
+        }
+    }
+
+    /// The value of the immediate bytes, None if the parameter isn't an immediate
+    fn immediate_value(&self) -> Option<u16> {
+        match self {
+            InstParam::Number8Bit(value) => Some(*value as u16),
+            InstParam::SignedNumber8Bit(value) => Some(*value as u8 as u16),
+            InstParam::Number16Bit(value) => Some(*value),
+            _ => None,
+        }
+    }
+}
+
+impl Instructions {
+    /// The parameters of the instruction
+    fn get_params(&self) -> [Option<&InstParam>; 2] {
+        match self {
+            Instructions::ADD(param)
+            | Instructions::ADD_HL(param)
+            | Instructions::ADC(param)
+            | Instructions::SUB(param)
+            | Instructions::SBC(param)
+            | Instructions::AND(param)
+            | Instructions::XOR(param)
+            | Instructions::OR(param)
+            | Instructions::CP(param)
+            | Instructions::SWAP(param)
+            | Instructions::PUSH(param)
+            | Instructions::POP(param)
+            | Instructions::RET(param)
+            | Instructions::RST(param)
+            | Instructions::RL(param)
+            | Instructions::RLC(param)
+            | Instructions::RR(param)
+            | Instructions::RRC(param)
+            | Instructions::SLA(param)
+            | Instructions::SRL(param)
+            | Instructions::SRA(param) => [Some(param), None],
+            Instructions::INC(first, second)
+            | Instructions::DEC(first, second)
+            | Instructions::LD(first, second)
+            | Instructions::LDH(first, second)
+            | Instructions::BIT(first, second)
+            | Instructions::RES(first, second)
+            | Instructions::SET(first, second)
+            | Instructions::CALL(first, second)
+            | Instructions::JP(first, second)
+            | Instructions::JR(first, second) => [Some(first), Some(second)],
+            _ => [None, None],
+        }
+    }
+
+    /// The length of the instruction in bytes (opcode, prefix and immediates)
+    /// Every byte takes one M-cycle to be fetched
+    pub fn length(&self) -> u8 {
+        match self {
+            // The vector is encoded in the opcode
+            Instructions::RST(_) => 1,
+            // Prefixed instructions
+            Instructions::BIT(_, _)
+            | Instructions::RES(_, _)
+            | Instructions::SET(_, _)
+            | Instructions::SWAP(_)
+            | Instructions::RL(_)
+            | Instructions::RLC(_)
+            | Instructions::RR(_)
+            | Instructions::RRC(_)
+            | Instructions::SLA(_)
+            | Instructions::SRL(_)
+            | Instructions::SRA(_) => 2,
+            _ => 1 + self.get_params().iter().flatten().map(|param| param.immediate_bytes()).sum::<u8>(),
+        }
+    }
+
+    /// Encode the instruction into its bytes, the inverse of `CPU::decode_bytes`
+    /// Returns None if no opcode decodes to this instruction
+    pub fn encode(&self) -> Option<[u8; 3]> {
+        let immediate = self.get_params().iter().flatten().find_map(|param| param.immediate_value());
+        let [low, high] = immediate.unwrap_or(0).to_le_bytes();
+
+        (0..=0xFF)
+            .map(|opcode| [opcode, low, high])
+            .chain((0..=0xFF).map(|opcode| [PREFIX_OPCODE, opcode, 0]))
+            .find(|bytes| CPU::decode_bytes(*bytes).as_ref() == Ok(self))
+    }
+}
@@ -1,4 +1,4 @@
-use crate::{cpu::{instructions::{ConditionCodes, FlagState, InstructionResult}, registers::{Register16Bit, Register8Bit},CPU}, mmu::MemoryOperations};
+use crate::cpu::{instructions::{ConditionCodes, FlagState, InstructionResult}, registers::{Register16Bit, Register8Bit},CPU};
 impl CPU {
     /// subtract r8 from A without storing and set flags accordingly
     pub fn cp_a_r8(&mut self, register: Register8Bit) -> InstructionResult {
@@ -39,7 +39,7 @@ impl CPU {
         let addr = self.get_16bit_register(Register16Bit::HL);
         let a_value = self.get_8bit_register(Register8Bit::A);
         let _tail = a_value & 0xF;
-        let r8_value = self.mmu.read_byte(addr);
+        let r8_value = self.read_memory(addr);
         let (value,overflow) = a_value.overflowing_sub(r8_value);
 
         InstructionResult {
@@ -1,4 +1,4 @@
-use crate::{cpu::{instructions::{ConditionCodes, FlagState, InstructionResult}, registers::{Register16Bit, Register8Bit},CPU}, mmu::MemoryOperations};
+use crate::cpu::{instructions::{ConditionCodes, FlagState, InstructionResult}, registers::{Register16Bit, Register8Bit},CPU};
 
 impl CPU {
     pub fn inc(&mut self, register: Register8Bit) -> InstructionResult {
@@ -21,9 +21,9 @@ impl CPU {
     /// increments the byte pointed to by HL
     pub fn inc_hl(&mut self) -> InstructionResult {
         let addr = self.get_16bit_register(Register16Bit::HL);
-        let r8_value = self.mmu.read_byte(addr);
+        let r8_value = self.read_memory(addr);
         let (value,_overflow) = r8_value.overflowing_add(1);
-        self.mmu.write_byte(addr, value);
+        self.write_memory(addr, value);
         let tail = r8_value & 0xF;
 
         InstructionResult {
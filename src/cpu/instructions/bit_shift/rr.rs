@@ -1,8 +1,11 @@
-use crate::{cpu::{
+use crate::cpu::{
     instructions::{ConditionCodes, FlagState, InstructionResult},
     registers::{Register16Bit, Register8Bit},
     CPU,
-}, mmu::MemoryOperations};
+};
+
+#[cfg(test)]
+use crate::mmu::MemoryOperations;
 
 
 impl CPU {
@@ -59,9 +62,9 @@ impl CPU {
         set_zero: bool,
     ) -> ConditionCodes {
         let mem_addr = self.get_16bit_register(Register16Bit::HL);
-        let value = self.mmu.read_byte(mem_addr);
+        let value = self.read_memory(mem_addr);
         let (condition_codes_result, result) = self.rr_u8(value, through_carry, set_zero);
-        self.mmu.write_byte(mem_addr, result);
+        self.write_memory(mem_addr, result);
         condition_codes_result
     }
 
@@ -1,8 +1,11 @@
-use crate::{cpu::{
+use crate::cpu::{
     instructions::{ConditionCodes, FlagState, InstructionResult},
     registers::{Register16Bit, Register8Bit},
     CPU,
-}, mmu::MemoryOperations};
+};
+
+#[cfg(test)]
+use crate::mmu::MemoryOperations;
 
 impl CPU {
     fn sl_u8(&mut self, value: u8) -> (ConditionCodes, u8) {
@@ -41,9 +44,9 @@ impl CPU {
 
     pub fn sla_hl(&mut self) -> InstructionResult {
         let mem_addr = self.get_16bit_register(Register16Bit::HL);
-        let value = self.mmu.read_byte(mem_addr);
+        let value = self.read_memory(mem_addr);
         let (condition_codes_result, result) = self.sl_u8(value);
-        self.mmu.write_byte(mem_addr, result);
+        self.write_memory(mem_addr, result);
         InstructionResult {
             cycles: 4,
             bytes: 2,
@@ -1,9 +1,12 @@
 
-use crate::{cpu::{
+use crate::cpu::{
     instructions::{ConditionCodes, FlagState, InstructionResult},
     registers::{Register16Bit, Register8Bit},
     CPU,
-}, mmu::MemoryOperations};
+};
+
+#[cfg(test)]
+use crate::mmu::MemoryOperations;
 
 impl CPU{
     fn sr_u8(
@@ -61,10 +64,10 @@ impl CPU{
         &mut self,
     ) -> InstructionResult {
         let mem_addr = self.get_16bit_register(Register16Bit::HL);
-        let value = self.mmu.read_byte(mem_addr);
+        let value = self.read_memory(mem_addr);
         let shift_arithmetic = true;
         let (condition_codes_result, result) = self.sr_u8(value, shift_arithmetic);
-        self.mmu.write_byte(mem_addr, result);
+        self.write_memory(mem_addr, result);
         InstructionResult{
             cycles: 4,
             bytes: 2,
@@ -91,10 +94,10 @@ impl CPU{
         &mut self,
     ) -> InstructionResult {
         let mem_addr = self.get_16bit_register(Register16Bit::HL);
-        let value = self.mmu.read_byte(mem_addr);
+        let value = self.read_memory(mem_addr);
         let shift_arithmetic = false;
         let (condition_codes_result, result) = self.sr_u8(value, shift_arithmetic);
-        self.mmu.write_byte(mem_addr, result);
+        self.write_memory(mem_addr, result);
         InstructionResult{
             cycles: 4,
             bytes: 2,
@@ -1,8 +1,8 @@
-use crate::{cpu::{
+use crate::cpu::{
     instructions::{ConditionCodes, FlagState, InstructionResult},
     registers::Register16Bit,
     CPU,
-}, mmu::MemoryOperations};
+};
 
 #[cfg(test)]
 use crate::test_helpers::assert_correct_instruction_step;
@@ -70,15 +70,16 @@ impl CPU {
         pub fn call_n16(&mut self, target: u16) -> InstructionResult {
             let value = self.get_16bit_register(Register16Bit::PC)+3;
 
-            //push pc to stack
+            //push pc to stack, SP is decremented in an internal cycle first
+            self.idle_cycle();
             self.dec_sp();
             let memory_address = self.get_16bit_register(Register16Bit::SP);
             let value1:u8 = (value >> 8) as u8;
             let value2:u8 = value as u8;
 
-            self.mmu.write_byte(memory_address, value1);
+            self.write_memory(memory_address, value1);
             self.dec_sp();
-            self.mmu.write_byte(memory_address-1, value2);
+            self.write_memory(memory_address-1, value2);
 
 
             self.jp_n16(target);
@@ -106,7 +107,7 @@ impl CPU {
             }
 
             InstructionResult {
-                cycles: if cc {4} else {3},
+                cycles: if cc {6} else {3},
                 bytes: 3,
                 condition_codes: ConditionCodes {
                     zero: FlagState::NotAffected,
@@ -133,6 +134,8 @@ impl CPU {
         }
         /// if condtition cc is true: Pops PC from the stack, returning to the last pushed instruction
         pub fn ret_cc(&mut self, cc: bool) -> InstructionResult {
+            // The condition is checked in an internal cycle
+            self.idle_cycle();
             if cc {
                 self.pop_r16(Register16Bit::PC);
             } else {
@@ -176,15 +179,16 @@ impl CPU {
         pub fn rst_vec(&mut self,vec: u8) -> InstructionResult {
             let value = self.get_16bit_register(Register16Bit::PC)+1;
 
-            //push pc to stack
+            //push pc to stack, SP is decremented in an internal cycle first
+            self.idle_cycle();
             self.dec_sp();
             let memory_address = self.get_16bit_register(Register16Bit::SP);
             let value1:u8 = (value >> 8) as u8;
             let value2:u8 = value as u8;
 
-            self.mmu.write_byte(memory_address, value1);
+            self.write_memory(memory_address, value1);
             self.dec_sp();
-            self.mmu.write_byte(memory_address-1, value2);
+            self.write_memory(memory_address-1, value2);
 
 
             self.jp_n16(vec as u16);
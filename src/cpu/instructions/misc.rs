@@ -173,7 +173,8 @@ impl CPU {
     pub fn interrupt_pentding(&mut self) -> bool {
         let interrupt_enable = self.mmu.read_byte(0xFFFF);
         let interrupt_flag = self.mmu.read_byte(0xFF0F);
-        if interrupt_enable & interrupt_flag == 0 {
+        // The upper bits of IF always read as 1
+        if interrupt_enable & interrupt_flag & 0b1_1111 == 0 {
             return false;
         }
         true
@@ -194,6 +195,9 @@ impl CPU {
                 },
             }
         } else {
+            // Nothing happens till the next event, which might request an interrupt
+            self.skip_to_next_event();
+
             InstructionResult {
                 cycles: 0,
                 bytes: 0,
@@ -327,7 +331,7 @@ pub fn ei_test() {
     };
     assert_correct_instruction_step(&mut cpu, Instructions::EI, expected_result);
     assert_eq!(1, cpu.enable_ime);
-    cpu.next_instruction = Instructions::NOP;
+    cpu.set_instruction(Instructions::NOP);
     _ = cpu.step();
     assert_eq!(0, cpu.enable_ime);
     assert!(cpu.ime_flag);
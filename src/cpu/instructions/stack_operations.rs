@@ -1,8 +1,11 @@
-use crate::{cpu::{
+use crate::cpu::{
     instructions::{ConditionCodes, FlagState, InstructionResult},
     registers::{Register16Bit, Register8Bit},
     CPU,
-}, mmu::MemoryOperations};
+};
+
+#[cfg(test)]
+use crate::mmu::MemoryOperations;
 
 #[cfg(test)]
 use crate::cpu::instructions::Instructions;
@@ -65,8 +68,8 @@ impl CPU { //maybe move ld, dec and inc to their files?
         let sp = self.get_16bit_register(Register16Bit::SP);
         let value = (sp & 0xFF) as u8;
         let sp_shifted = (sp >> 8) as u8;
-        self.mmu.write_byte(target, value);
-        self.mmu.write_byte(target+1, sp_shifted);
+        self.write_memory(target, value);
+        self.write_memory(target+1, sp_shifted);
 
         InstructionResult {
             cycles: 5,
@@ -119,9 +122,9 @@ impl CPU { //maybe move ld, dec and inc to their files?
     /// Pops 16bit-register AF from the stack, incrementing the stack pointer twice during this.
     pub fn pop_af (&mut self) -> InstructionResult {
         let memory_address = self.get_16bit_register(Register16Bit::SP);
-        let low_value = self.mmu.read_byte(memory_address)&0xf0;
+        let low_value = self.read_memory(memory_address)&0xf0;
         self.inc_sp();
-        let high_value: u16 = (self.mmu.read_byte(memory_address+1) as u16) << 8;
+        let high_value: u16 = (self.read_memory(memory_address+1) as u16) << 8;
         self.inc_sp();
         let combined_value:u16 = high_value+(low_value as u16);
         self.set_16bit_register(Register16Bit::AF, combined_value);
@@ -156,9 +159,9 @@ impl CPU { //maybe move ld, dec and inc to their files?
     /// Pops 16bit-register target from the stack back into the register, incrementing the stack pointer twice during this.
     pub fn pop_r16 (&mut self, target:Register16Bit) -> InstructionResult {
         let memory_address = self.get_16bit_register(Register16Bit::SP);
-        let low_value = self.mmu.read_byte(memory_address);
+        let low_value = self.read_memory(memory_address);
         self.inc_sp();
-        let high_value: u16 = (self.mmu.read_byte(memory_address+1) as u16) << 8;
+        let high_value: u16 = (self.read_memory(memory_address+1) as u16) << 8;
         self.inc_sp();
         let combined_value: u16 = high_value + (low_value as u16);
         self.set_16bit_register(target, combined_value);
@@ -176,17 +179,19 @@ impl CPU { //maybe move ld, dec and inc to their files?
     }
     /// Pushes 16bit-register AF on the stack, decrementing the stack pointer twice during this.
     pub fn push_af (&mut self) -> InstructionResult {
+        // SP is decremented in an internal cycle before the first write
+        self.idle_cycle();
         self.dec_sp();
         let value = self.get_8bit_register(Register8Bit::A);
         let memory_address = self.get_16bit_register(Register16Bit::SP);
-        self.mmu.write_byte(memory_address, value);
+        self.write_memory(memory_address, value);
         self.dec_sp();
         let mut flags: u8 = 0;
         if self.is_zero_flag_set() {flags += 128;}
         if self.is_subtraction_flag_set() {flags += 64;}
         if self.is_half_carry_flag_set() {flags += 32;}
         if self.is_carry_flag_set() {flags += 16;}
-        self.mmu.write_byte(memory_address-1, flags);
+        self.write_memory(memory_address-1, flags);
 
         InstructionResult {
             cycles: 4,
@@ -201,6 +206,8 @@ impl CPU { //maybe move ld, dec and inc to their files?
     }
     /// Pushes 16bit-register target on the stack, decrementing the stack pointer twice during this.
     pub fn push_r16 (&mut self, target:Register16Bit) -> InstructionResult {
+        // SP is decremented in an internal cycle before the first write
+        self.idle_cycle();
         self.dec_sp();
         let memory_address = self.get_16bit_register(Register16Bit::SP);
         let value1;
@@ -220,9 +227,9 @@ impl CPU { //maybe move ld, dec and inc to their files?
             },
             _ => panic!("push with {:?} not intended", target),
         }
-        self.mmu.write_byte(memory_address, value1);
+        self.write_memory(memory_address, value1);
         self.dec_sp();
-        self.mmu.write_byte(memory_address-1, value2);
+        self.write_memory(memory_address-1, value2);
 
 
         InstructionResult {
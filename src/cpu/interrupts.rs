@@ -2,7 +2,7 @@ use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 use crate::{cpu::registers::Register16Bit, mmu::MemoryOperations};
 
-use super::{CPU};
+use super::{instructions::{ConditionCodes, FlagState, InstructionResult}, CPU};
 
 #[derive(Debug, IntoPrimitive, Clone, Copy)]
 #[repr(u8)]
@@ -17,6 +17,9 @@ pub enum InterruptTypes {
 const INTERRUPT_FLAG_ADDRESS: u16 = 0xFF0F;
 const INTERRUPT_ENABLE_ADDRESS: u16 = 0xFFFF;
 const INTERRUPT_CALL_ADDRESS: u16 = 0x0040;
+/// Where the CPU ends up when the IE push cancelled the dispatch
+const INTERRUPT_CANCELLED_ADDRESS: u16 = 0x0000;
+const INTERRUPT_DISPATCH_CYCLES: u8 = 5;
 const LCDY_ADDRESS: u16 = 0xFF44;
 const LYC_ADDRESS: u16 = 0xFF45;
 const STAT_ADDRESS: u16 = 0xFF41;
@@ -33,7 +36,7 @@ pub enum PpuMode {
 impl CPU {
     pub fn set_vblank_interrupt(&mut self) {
         log::debug!("VBlank interrupt set");
-        self.mmu.write_byte(0xFF44, 144);
+        self.mmu.IO.set_register(LCDY_ADDRESS, 144);
         self.set_interrupt_flag(InterruptTypes::VBlank);
     }
 
@@ -50,38 +53,48 @@ impl CPU {
 
     pub fn set_lcd_y_coordinate(&mut self, value: u8) {
         //log::info!("Setting LCD Y coordinate: {}", value);
-        self.mmu.write_byte(LCDY_ADDRESS, value);
-
-        if self.is_lyc_equal_ly() {
-            self.set_interrupt_flag(InterruptTypes::LCDC);
-
-            // Set bit 2 of STAT register
-            let stat = self.mmu.read_byte(STAT_ADDRESS);
-            self.mmu.write_byte(STAT_ADDRESS, stat | 0b100);
-        } 
+        // LY is read-only for the CPU
+        self.mmu.IO.set_register(LCDY_ADDRESS, value);
     }
 
-    /// Set the PPU mode and check if an interrupt should be triggered
+    /// Set the PPU mode, the STAT interrupt is handled by `update_stat_interrupt_line`
     pub fn set_ppu_mode(&mut self, mode: PpuMode) {
         //log::info!("Setting PPU mode: {}", mode);
 
         let stat = self.mmu.read_byte(STAT_ADDRESS) & 0b1111_1100;
-        self.mmu.write_byte(STAT_ADDRESS, stat | mode as u8);
+        self.mmu.IO.write_stat_register(stat | mode as u8);
+        self.mmu.set_ppu_mode(mode as u8);
+    }
 
-        // Check if the mode 0 interrupt is enabled
-        if mode as u8 == 0 && (stat & 0b1000) != 0 {
-            self.set_interrupt_flag(InterruptTypes::LCDC);
+    /// Update the LYC=LY flag and the STAT interrupt line
+    /// All enabled sources are ORed into a single line and the interrupt is only
+    /// requested on a rising edge, so one source blocks the others ("STAT blocking")
+    /// See: https://gbdev.io/pandocs/Interrupt_Sources.html#int-48--stat-interrupt
+    pub fn update_stat_interrupt_line(&mut self) {
+        // The PPU doesn't request any interrupts while the LCD is off
+        if !self.get_lcdc_ppu_enabled() {
+            self.stat_interrupt_line = false;
+            return;
         }
 
-        // Check if the mode 1 interrupt is enabled
-        if mode as u8 == 1 && (stat & 0b10000) != 0 {
-            self.set_interrupt_flag(InterruptTypes::LCDC);
+        let lyc_equal_ly = self.is_lyc_equal_ly();
+        let mut stat = self.mmu.read_byte(STAT_ADDRESS) & 0b1111_1011;
+        if lyc_equal_ly {
+            stat |= 0b100;
         }
+        self.mmu.IO.write_stat_register(stat);
+
+        let line = match PpuMode::try_from(stat & 0b11).expect("Invalid PPU Mode") {
+            PpuMode::HorizontalBlank => stat & 0b1000 != 0,
+            PpuMode::VerticalBlank => stat & 0b1_0000 != 0,
+            PpuMode::OamScan => stat & 0b10_0000 != 0,
+            PpuMode::Drawing => false,
+        } || (lyc_equal_ly && stat & 0b100_0000 != 0);
 
-        // Check if the mode 2 interrupt is enabled
-        if mode as u8 == 2 && (stat & 0b100000) != 0 {
+        if line && !self.stat_interrupt_line {
             self.set_interrupt_flag(InterruptTypes::LCDC);
         }
+        self.stat_interrupt_line = line;
     }
 
     pub fn get_ppu_mode(&self) -> u8 {
@@ -114,54 +127,167 @@ impl CPU {
         interrupt_type
     }
 
-    pub fn handle_interrupt(&mut self, interrupt: i32) {
+    /// Dispatch the highest priority pending interrupt
+    /// See: https://gbdev.io/pandocs/Interrupts.html#interrupt-handling
+    /// The dispatch takes 5 M-cycles:
+    /// 1. & 2. Two wait states, the already fetched opcode is discarded
+    /// 3. SP is decremented and the high byte of PC is pushed
+    /// 4. SP is decremented and the low byte of PC is pushed
+    /// 5. PC is set to the interrupt vector
+    ///
+    /// The vector is only chosen after the high byte has been pushed. If that push
+    /// lands on IE (0xFFFF) it can cancel the dispatch, which then jumps to 0x0000,
+    /// or redirect it to another pending interrupt. (See mooneye `ie_push`)
+    pub fn handle_interrupt(&mut self) -> InstructionResult {
         // Disable all interrupts
         self.ime_flag = false;
 
-        // Clear the interrupt flag
-        let interrupt_flag = self.mmu.read_byte(INTERRUPT_FLAG_ADDRESS);
-        self.mmu
-            .write_byte(INTERRUPT_FLAG_ADDRESS, interrupt_flag & !(1 << interrupt));
-        log::debug!("Previous flags: {:#X}, New flags: {:#X}, interrupt type: {:?}", interrupt_flag, self.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), interrupt);
-
-        // Call the interrupt handler at the appropriate address
-        // https://gbdev.io/pandocs/Interrupt_Sources.html
-        let interrupt_address = INTERRUPT_CALL_ADDRESS + (interrupt as u16 * 8);
-
         // Get current PC
         let mut current_pc = self.get_16bit_register(Register16Bit::PC);
 
         if self.is_halted {
-            //log::info!("Interrupted while in HALT state, type: {:?}", interrupt);
+            //log::info!("Interrupted while in HALT state");
             current_pc += 1;
             self.is_halted = false;
         }
 
-        // Push PC to Stack
+        // M-cycle 1 & 2: Wait states
+        self.idle_cycle();
+        self.idle_cycle();
+
+        // M-cycle 3: Push the high byte of PC, this may overwrite IE
         self.dec_sp();
-        let memory_address = self.get_16bit_register(Register16Bit::SP);
-        let value1 = (current_pc >> 8) as u8;
-        let value2 = current_pc as u8;
-        self.mmu.write_byte(memory_address, value1);
+        self.write_memory(self.get_16bit_register(Register16Bit::SP), (current_pc >> 8) as u8);
+
+        // The interrupt is only acknowledged now, so a changed IE is taken into account
+        let interrupt = self.check_interrupts(false);
+
+        // M-cycle 4: Push the low byte of PC
         self.dec_sp();
-        self.mmu.write_byte(memory_address-1, value2);
+        self.write_memory(self.get_16bit_register(Register16Bit::SP), current_pc as u8);
 
-        // Jump to interrupt address
+        // M-cycle 5: Jump to the interrupt vector
+        self.idle_cycle();
+        // https://gbdev.io/pandocs/Interrupt_Sources.html
+        let interrupt_address = match interrupt {
+            Some(interrupt) => {
+                // Clear the interrupt flag
+                let interrupt_flag = self.mmu.read_byte(INTERRUPT_FLAG_ADDRESS);
+                self.mmu
+                    .write_byte(INTERRUPT_FLAG_ADDRESS, interrupt_flag & !(1 << interrupt));
+                log::debug!("Previous flags: {:#X}, New flags: {:#X}, interrupt type: {:?}", interrupt_flag, self.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), interrupt);
+
+                INTERRUPT_CALL_ADDRESS + (interrupt as u16 * 8)
+            }
+            None => {
+                log::debug!("Interrupt dispatch cancelled by IE push");
+                INTERRUPT_CANCELLED_ADDRESS
+            }
+        };
         self.set_16bit_register(Register16Bit::PC, interrupt_address);
 
-        // Since the PC was changed, we need to decode the next instruction
-        self.prepare_and_decode_next_instruction().unwrap();
+        InstructionResult {
+            cycles: INTERRUPT_DISPATCH_CYCLES,
+            bytes: 0,
+            condition_codes: ConditionCodes {
+                zero: FlagState::NotAffected,
+                subtract: FlagState::NotAffected,
+                half_carry: FlagState::NotAffected,
+                carry: FlagState::NotAffected,
+            },
+        }
     }
 
     /// Check for interrupts and handle them
-    /// Returns true if an interrupt was handled
-    pub fn check_and_handle_interrupts(&mut self) -> bool {
-        match self.check_interrupts(true) {
-            Some(interrupt) => {
-                self.handle_interrupt(interrupt);
-                true
-            }
-            None => false,
-        }
+    /// Returns the result of the dispatch if an interrupt was handled
+    pub fn check_and_handle_interrupts(&mut self) -> Option<InstructionResult> {
+        self.check_interrupts(true).map(|_| self.handle_interrupt())
     }
-}
\ No newline at end of file
+}
+
+#[test]
+pub fn interrupt_dispatch_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+    cpu.set_16bit_register(Register16Bit::PC, 0x1234);
+    cpu.set_16bit_register(Register16Bit::SP, 0xD000);
+    cpu.ime_flag = true;
+    cpu.mmu.write_byte(INTERRUPT_ENABLE_ADDRESS, 0b0_0101);
+    cpu.mmu.write_byte(INTERRUPT_FLAG_ADDRESS, 0b0_0100);
+
+    let result = cpu.check_and_handle_interrupts().expect("Timer interrupt should be dispatched");
+    assert_eq!(result.cycles, 5);
+    assert_eq!(cpu.get_16bit_register(Register16Bit::PC), 0x0050);
+    assert_eq!(cpu.get_16bit_register(Register16Bit::SP), 0xCFFE);
+    assert_eq!(cpu.mmu.read_word(0xCFFE), 0x1234);
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0000);
+    assert!(!cpu.ime_flag);
+    assert!(cpu.check_and_handle_interrupts().is_none());
+}
+
+#[test]
+pub fn interrupt_ie_push_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    // Pushing 0x02 as high byte onto IE cancels the pending timer interrupt
+    cpu.set_16bit_register(Register16Bit::PC, 0x0200);
+    cpu.set_16bit_register(Register16Bit::SP, 0x0000);
+    cpu.ime_flag = true;
+    cpu.mmu.write_byte(INTERRUPT_ENABLE_ADDRESS, 0b0_0100);
+    cpu.mmu.write_byte(INTERRUPT_FLAG_ADDRESS, 0b0_0100);
+    cpu.check_and_handle_interrupts();
+    assert_eq!(cpu.get_16bit_register(Register16Bit::PC), 0x0000);
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_ENABLE_ADDRESS), 0x02);
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0100);
+
+    // Pushing 0x02 as high byte redirects the VBlank interrupt to LCDC
+    cpu.set_16bit_register(Register16Bit::PC, 0x0200);
+    cpu.set_16bit_register(Register16Bit::SP, 0x0000);
+    cpu.ime_flag = true;
+    cpu.mmu.write_byte(INTERRUPT_ENABLE_ADDRESS, 0b0_0001);
+    cpu.mmu.write_byte(INTERRUPT_FLAG_ADDRESS, 0b0_0011);
+    cpu.check_and_handle_interrupts();
+    assert_eq!(cpu.get_16bit_register(Register16Bit::PC), 0x0048);
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0001);
+}
+
+#[test]
+pub fn stat_interrupt_line_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    // LCD on, HBlank and LYC=LY sources enabled, LY = LYC = 0
+    cpu.mmu.write_byte(0xFF40, 0x80);
+    cpu.mmu.write_byte(STAT_ADDRESS, 0b0100_1000);
+    cpu.set_ppu_mode(PpuMode::HorizontalBlank);
+    cpu.update_stat_interrupt_line();
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0010);
+    assert_eq!(cpu.mmu.read_byte(STAT_ADDRESS) & 0b111, 0b100);
+
+    // The line stays high through HBlank, so no new interrupt is requested
+    cpu.mmu.write_byte(INTERRUPT_FLAG_ADDRESS, 0);
+    cpu.set_lcd_y_coordinate(1);
+    cpu.update_stat_interrupt_line();
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0000);
+    assert_eq!(cpu.mmu.read_byte(STAT_ADDRESS) & 0b111, 0b000);
+
+    // Drawing lowers the line, the next HBlank raises it again
+    cpu.set_ppu_mode(PpuMode::Drawing);
+    cpu.update_stat_interrupt_line();
+    cpu.set_ppu_mode(PpuMode::HorizontalBlank);
+    cpu.update_stat_interrupt_line();
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0010);
+
+    // Turning the LCD off lowers the line
+    cpu.mmu.write_byte(INTERRUPT_FLAG_ADDRESS, 0);
+    cpu.mmu.write_byte(0xFF40, 0x00);
+    cpu.update_stat_interrupt_line();
+    cpu.mmu.write_byte(0xFF40, 0x80);
+    cpu.update_stat_interrupt_line();
+    assert_eq!(cpu.mmu.read_byte(INTERRUPT_FLAG_ADDRESS), 0b1110_0010);
+
+    // The CPU can't overwrite the mode and LYC=LY bits, the unused bit 7 reads as 1
+    cpu.mmu.write_byte(STAT_ADDRESS, 0b0000_0111);
+    assert_eq!(cpu.mmu.read_byte(STAT_ADDRESS), 0b1000_0000);
+}
@@ -10,11 +10,13 @@ impl CPU {
     /// Joypad Key I/O Call
     /// stop_mode: If true, the CPU is in a STOP state and we should not set the interrupt flag
     pub fn update_key_input(&mut self) -> bool {
-        //get prev button states:
-        let action = self.mmu.IO.action_buttons;
-        let direction = self.mmu.IO.direction_buttons;
-        let mut new_action = action;
-        let mut new_direction = direction;
+        let buttons = Self::read_button_keys();
+        self.set_button_input(buttons)
+    }
+
+    /// Get the buttons held on the keyboard, a set bit means pressed
+    /// Bits 0-3 are Right, Left, Up & Down, bits 4-7 are A, B, Select & Start
+    pub fn read_button_keys() -> u8 {
         //get all 8 button inputs:
         let key_map: [(KeyCode, u8); 8] =  {
             [
@@ -29,17 +31,31 @@ impl CPU {
             ]
         };
 
-        for (key, bit) in key_map.iter() {
-            if is_key_down(*key) {
-                log::debug!("Key pressed: {:?}", key);
-                if bit < &4 {
+        key_map
+            .iter()
+            .filter(|(key, _)| is_key_down(*key))
+            .inspect(|(key, _)| log::debug!("Key pressed: {:?}", key))
+            .fold(0, |buttons, (_, bit)| buttons | 1 << bit)
+    }
+
+    /// Press the buttons set in `buttons`, e.g. from the keyboard or a movie, see `read_button_keys`
+    pub fn set_button_input(&mut self, buttons: u8) -> bool {
+        //get prev button states:
+        let action = self.mmu.IO.action_buttons;
+        let direction = self.mmu.IO.direction_buttons;
+        let mut new_action = action;
+        let mut new_direction = direction;
+
+        for bit in 0..8 {
+            if buttons & (1 << bit) != 0 {
+                if bit < 4 {
                     new_direction &= !(1 << bit);
                 }else {
                     new_action &= !(1 << (bit%4));
                 }
                  
             } else {
-                if bit < &4 {
+                if bit < 4 {
                     new_direction |= 1 << bit;
                 }else {
                     new_action |= 1 << (bit%4);
@@ -73,6 +89,22 @@ impl CPU {
         result
     }
 
+    /// Tilt the cartridge with I, J, K & L for cartridges with an accelerometer
+    pub fn update_tilt_input(&mut self) {
+        let (x, y) = Self::read_tilt_keys();
+        self.mmu.set_tilt(x, y);
+    }
+
+    /// Get the tilt held on the keyboard in g, see `MMU::set_tilt`
+    /// Tilting left and towards the player increases the measured values
+    pub fn read_tilt_keys() -> (f32, f32) {
+        let axis = |negative: KeyCode, positive: KeyCode| {
+            is_key_down(positive) as i8 as f32 - is_key_down(negative) as i8 as f32
+        };
+
+        (axis(KeyCode::L, KeyCode::J), axis(KeyCode::I, KeyCode::K))
+    }
+
     pub fn enable_buttons_debug(&mut self) {
         let mut joypad = self.mmu.read_byte(JOYPAD_REGISTER);
         // Enable button by setting the 5th bit to 0
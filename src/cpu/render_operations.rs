@@ -78,6 +78,7 @@ impl CPU {
     }
 
     // VRAM getters
+    // The PPU is never blocked from VRAM & OAM, so these read the regions directly
     pub fn get_vram_tile_line(
         &self,
         high_addressing: bool,
@@ -92,8 +93,8 @@ impl CPU {
             line_addr += 0x1000;
         }
 
-        let lo = self.mmu.read_byte(line_addr);
-        let hi = self.mmu.read_byte(line_addr + 1);
+        let lo = self.mmu.VRAM.read_byte(line_addr);
+        let hi = self.mmu.VRAM.read_byte(line_addr + 1);
 
         for i in 0..8 {
             line_data[7 - i] = ((lo >> i) & 1) + (((hi >> i) & 1) * 2);
@@ -104,19 +105,19 @@ impl CPU {
 
     pub fn get_vram_tile_map_entry(&self, high_map: bool, map_index: u16) -> u8 {
         let addr: u16 = if high_map { 0x9C00 } else { 0x9800 } + map_index;
-        self.mmu.read_byte(addr)
+        self.mmu.VRAM.read_byte(addr)
     }
 
     pub fn get_oam_entry(&self, index: u8) -> Sprite {
 
         let entry_base_addr = OAM_ADDRESS + index as u16 * SPRITE_SIZE;
 
-        let attribute_byte = self.mmu.read_byte(entry_base_addr + 3);
+        let attribute_byte = self.mmu.OAM.read_byte(entry_base_addr + 3);
 
         let sprite = Sprite {
-            y_pos: self.mmu.read_byte(entry_base_addr) as i32,
-            x_pos: self.mmu.read_byte(entry_base_addr + 1) as i32,
-            tile_idx: self.mmu.read_byte(entry_base_addr + 2) as u16,
+            y_pos: self.mmu.OAM.read_byte(entry_base_addr) as i32,
+            x_pos: self.mmu.OAM.read_byte(entry_base_addr + 1) as i32,
+            tile_idx: self.mmu.OAM.read_byte(entry_base_addr + 2) as u16,
             prio_bg: (attribute_byte >> 7) & 1 == 1,
             y_flip: (attribute_byte >> 6) & 1 == 1,
             x_flip: (attribute_byte >> 5) & 1 == 1,
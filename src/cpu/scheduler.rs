@@ -0,0 +1,152 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Events components can schedule instead of being polled every cycle
+/// Events due at the same time are handled in this order
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Event {
+    /// DIV, the upper byte of the timer's internal counter, changes
+    DivIncrement = 0,
+    /// Falling edge of the counter bit selected by TAC, TIMA is incremented
+    TimerIncrement = 1,
+    /// The OAM DMA starts after its start delay
+    DmaStart = 2,
+    /// The OAM DMA transferred its last byte
+    DmaEnd = 3,
+    /// A serial transfer finished
+    SerialTransfer = 4,
+    /// The PPU changes its mode or line
+    Ppu = 5,
+}
+
+const EVENT_COUNT: usize = 6;
+const EVENTS: [Event; EVENT_COUNT] = [
+    Event::DivIncrement,
+    Event::TimerIncrement,
+    Event::DmaStart,
+    Event::DmaEnd,
+    Event::SerialTransfer,
+    Event::Ppu,
+];
+
+/// Keeps the master clock (in T-cycles) and the pending events of all components
+/// Only one event of each kind can be pending, scheduling it again replaces it
+pub struct Scheduler {
+    /// T-cycles passed since power on
+    now: u64,
+    /// The time each event is due at, None if it isn't scheduled
+    events: [Option<u64>; EVENT_COUNT],
+    /// The time of the earliest pending event
+    next_event: u64,
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Scheduler {
+            now: 0,
+            events: [None; EVENT_COUNT],
+            next_event: u64::MAX,
+        }
+    }
+
+    /// Get the current time of the master clock in T-cycles
+    pub fn get_now(&self) -> u64 {
+        self.now
+    }
+
+    /// Advance the master clock, due events have to be handled via `pop_due_event`
+    pub fn advance(&mut self, t_cycles: u64) {
+        self.now += t_cycles;
+    }
+
+    /// Schedule an event to happen in `delay` T-cycles
+    pub fn schedule(&mut self, event: Event, delay: u64) {
+        self.events[event as usize] = Some(self.now + delay);
+        self.update_next_event();
+    }
+
+    pub fn cancel(&mut self, event: Event) {
+        self.events[event as usize] = None;
+        self.update_next_event();
+    }
+
+    /// Get the time an event is due at
+    pub fn get_event_time(&self, event: Event) -> Option<u64> {
+        self.events[event as usize]
+    }
+
+    /// Remove and return the next event that is due
+    pub fn pop_due_event(&mut self) -> Option<Event> {
+        if self.next_event > self.now {
+            return None;
+        }
+
+        // The first event with the earliest time wins, so ties are resolved by the event order
+        let (index, _) = self
+            .events
+            .iter()
+            .enumerate()
+            .filter_map(|(index, time)| time.map(|time| (index, time)))
+            .min_by_key(|(_, time)| *time)?;
+
+        self.events[index] = None;
+        self.update_next_event();
+
+        Some(EVENTS[index])
+    }
+
+    /// T-cycles until the next event is due
+    pub fn get_cycles_until_next_event(&self) -> u64 {
+        self.next_event.saturating_sub(self.now)
+    }
+
+    fn update_next_event(&mut self) {
+        self.next_event = self.events.iter().flatten().copied().min().unwrap_or(u64::MAX);
+    }
+}
+
+impl SaveState for Scheduler {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.now);
+        state.write(&self.events);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.now = state.read()?;
+        self.events = state.read()?;
+        self.update_next_event();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn scheduler_order_test() {
+    let mut scheduler = Scheduler::new();
+
+    scheduler.schedule(Event::Ppu, 8);
+    scheduler.schedule(Event::TimerIncrement, 8);
+    scheduler.schedule(Event::DmaStart, 4);
+    scheduler.schedule(Event::SerialTransfer, 12);
+    scheduler.cancel(Event::SerialTransfer);
+    assert_eq!(scheduler.get_cycles_until_next_event(), 4);
+
+    scheduler.advance(4);
+    assert_eq!(scheduler.pop_due_event(), Some(Event::DmaStart));
+    assert_eq!(scheduler.pop_due_event(), None);
+
+    // Events due at the same time are handled in a fixed order
+    scheduler.advance(8);
+    assert_eq!(scheduler.pop_due_event(), Some(Event::TimerIncrement));
+    assert_eq!(scheduler.pop_due_event(), Some(Event::Ppu));
+    assert_eq!(scheduler.pop_due_event(), None);
+
+    // Rescheduling replaces the pending event
+    scheduler.schedule(Event::Ppu, 4);
+    scheduler.schedule(Event::Ppu, 8);
+    assert_eq!(scheduler.get_event_time(Event::Ppu), Some(20));
+}
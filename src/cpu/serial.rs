@@ -0,0 +1,56 @@
+use crate::mmu::MemoryOperations;
+
+use super::{interrupts::InterruptTypes, scheduler::Event, CPU};
+
+const SERIAL_DATA_ADDRESS: u16 = 0xFF01;
+const SERIAL_CONTROL_ADDRESS: u16 = 0xFF02;
+
+/// With the internal clock of 8192 Hz, shifting out 8 bits takes 4096 T-cycles
+/// See: https://gbdev.io/pandocs/Serial_Data_Transfer_(Link_Cable).html
+const SERIAL_TRANSFER_CYCLES: u64 = 4096;
+
+impl CPU {
+    /// A transfer using the internal clock has been started via SC
+    pub fn start_serial_transfer(&mut self) {
+        self.scheduler.schedule(Event::SerialTransfer, SERIAL_TRANSFER_CYCLES);
+    }
+
+    /// See `Event::SerialTransfer`
+    /// Without a link partner every received bit is 1, so SB ends up as 0xFF
+    pub fn finish_serial_transfer(&mut self) {
+        let data = self.mmu.read_byte(SERIAL_DATA_ADDRESS);
+        self.serial_output.push(data);
+
+        self.mmu.IO.set_register(SERIAL_DATA_ADDRESS, 0xFF);
+        let control = self.mmu.read_byte(SERIAL_CONTROL_ADDRESS);
+        self.mmu.IO.set_register(SERIAL_CONTROL_ADDRESS, control & 0b0111_1111);
+
+        self.set_interrupt_flag(InterruptTypes::Serial);
+    }
+
+    /// Take the bytes that have been sent via the serial port since the last call
+    pub fn take_serial_output(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.serial_output)
+    }
+}
+
+#[test]
+pub fn serial_transfer_test() {
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    cpu.write_memory(SERIAL_DATA_ADDRESS, b'A');
+    cpu.write_memory(SERIAL_CONTROL_ADDRESS, 0x81);
+
+    // The transfer is handled in the M-cycle after SC has been written
+    for _ in 0..SERIAL_TRANSFER_CYCLES / 4 {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.mmu.read_byte(SERIAL_CONTROL_ADDRESS) & 0x80, 0x80);
+    cpu.idle_cycle();
+
+    assert_eq!(cpu.mmu.read_byte(SERIAL_CONTROL_ADDRESS) & 0x80, 0);
+    assert_eq!(cpu.mmu.read_byte(SERIAL_DATA_ADDRESS), 0xFF);
+    assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b1000, 0b1000);
+    assert_eq!(cpu.take_serial_output(), b"A");
+}
@@ -0,0 +1,97 @@
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::CPU;
+
+impl CPU {
+    /// Capture the whole machine state, only valid between steps
+    pub fn save_snapshot(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        self.save_state(&mut state);
+        state.into_bytes()
+    }
+
+    /// Restore a snapshot taken with the same ROM by `save_snapshot`
+    pub fn load_snapshot(&mut self, snapshot: &[u8]) -> Result<(), String> {
+        let mut state = StateReader::new(snapshot);
+        self.load_state(&mut state)?;
+
+        if !state.is_at_end() {
+            return Err("The snapshot is longer than expected".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The model and the serial output are left alone, they aren't part of the emulated machine
+impl SaveState for CPU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.registers);
+        state.write(&self.injected_instruction);
+        state.write(&self.ime_flag);
+        state.write(&self.enable_ime);
+        state.write(&self.cycles);
+        state.write(&self.instruction_cycles);
+        self.scheduler.save_state(state);
+        state.write(&self.divider_base);
+        state.write(&self.timer_control);
+        self.get_ppu().save_state(state);
+        state.write(&self.stat_interrupt_line);
+        state.write(&self.is_halted);
+        state.write(&self.stop_mode);
+        state.write(&self.instruction);
+        state.write(&self.dma_active);
+        state.write(&self.dma_current_offset);
+        state.write(&self.dma_source);
+        state.write(&self.dma_start_time);
+        self.mmu.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.registers = state.read()?;
+        self.injected_instruction = state.read()?;
+        self.ime_flag = state.read()?;
+        self.enable_ime = state.read()?;
+        self.cycles = state.read()?;
+        self.instruction_cycles = state.read()?;
+        self.scheduler.load_state(state)?;
+        self.divider_base = state.read()?;
+        self.timer_control = state.read()?;
+        self.get_ppu_mut().load_state(state)?;
+        self.stat_interrupt_line = state.read()?;
+        self.is_halted = state.read()?;
+        self.stop_mode = state.read()?;
+        self.instruction = state.read()?;
+        self.dma_active = state.read()?;
+        self.dma_current_offset = state.read()?;
+        self.dma_source = state.read()?;
+        self.dma_start_time = state.read()?;
+        self.mmu.load_state(state)
+    }
+}
+
+#[test]
+pub fn snapshot_round_trip_test() {
+    use crate::mmu::MemoryOperations;
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.skip_boot_rom();
+    cpu.mmu.write_byte(0xC000, 0x42);
+    for _ in 0..1000 {
+        cpu.idle_cycle();
+    }
+    let snapshot = cpu.save_snapshot();
+    let dot = cpu.get_ppu().get_dot();
+
+    cpu.mmu.write_byte(0xC000, 0x00);
+    for _ in 0..1000 {
+        cpu.idle_cycle();
+    }
+    assert_ne!(cpu.get_ppu().get_dot(), dot);
+
+    cpu.load_snapshot(&snapshot).unwrap();
+    assert_eq!(cpu.mmu.read_byte(0xC000), 0x42);
+    assert_eq!(cpu.get_ppu().get_dot(), dot);
+    assert_eq!(cpu.save_snapshot(), snapshot);
+
+    assert!(cpu.load_snapshot(&snapshot[..snapshot.len() - 1]).is_err());
+}
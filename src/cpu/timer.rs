@@ -1,25 +1,87 @@
 use crate::mmu::MemoryOperations;
 
-use super::{interrupts::InterruptTypes, CPU};
+use super::{interrupts::InterruptTypes, scheduler::Event, CPU};
 
 const TIMER_COUNTER_ADDRESS: u16 = 0xFF05;
 const TIMER_MODULO_ADDRESS: u16 = 0xFF06;
+const TIMER_CONTROL_ADDRESS: u16 = 0xFF07;
+
+/// DIV changes every 256 T-cycles
+const DIV_PERIOD: u64 = 0x100;
 
 impl CPU {
-    /// Increment the timer counter
-    /// Note, this doesn't check for cycles, it's just a simple increment
-    pub fn increment_timer(&mut self) {
-        // Check whether FF07 is enabled [Pos 2]
-        if self.mmu.read_byte(0xFF07) & 0b100 == 0 {
-            return;
+    /// Get the internal 16-bit counter of the timer, DIV is the upper byte
+    /// It is derived from the master clock, so it doesn't have to be incremented every cycle
+    /// See: https://gbdev.io/pandocs/Timer_Obscure_Behaviour.html
+    pub fn get_divider(&self) -> u16 {
+        self.scheduler.get_now().wrapping_sub(self.divider_base) as u16
+    }
+
+    /// Set the internal counter of the timer and reschedule the timer events
+    pub fn set_divider(&mut self, value: u16) {
+        self.divider_base = self.scheduler.get_now().wrapping_sub(value as u64);
+        self.mmu.IO.write_div_register((value >> 8) as u8);
+        self.schedule_timer_events();
+    }
+
+    /// Writing to DIV resets the whole internal counter
+    /// If the bit selected by TAC was set, this is a falling edge and TIMA is incremented
+    pub fn reset_divider(&mut self) {
+        if self.get_timer_signal(self.timer_control) {
+            self.increment_timer();
+        }
+
+        self.set_divider(0);
+    }
+
+    /// Apply a new value of TAC
+    /// Disabling the timer or selecting another bit can cause a falling edge as well
+    pub fn update_timer_control(&mut self) {
+        let timer_control = self.mmu.read_byte(TIMER_CONTROL_ADDRESS);
+
+        if self.get_timer_signal(self.timer_control) && !self.get_timer_signal(timer_control) {
+            self.increment_timer();
+        }
+
+        self.timer_control = timer_control;
+        self.schedule_timer_events();
+    }
+
+    /// DIV changed, see `Event::DivIncrement`
+    pub fn div_increment(&mut self) {
+        self.mmu.IO.write_div_register((self.get_divider() >> 8) as u8);
+        self.scheduler.schedule(Event::DivIncrement, DIV_PERIOD);
+    }
+
+    /// The bit selected by TAC had a falling edge, see `Event::TimerIncrement`
+    pub fn timer_increment(&mut self) {
+        self.increment_timer();
+        self.scheduler.schedule(Event::TimerIncrement, Self::get_timer_period(self.timer_control));
+    }
+
+    /// Schedule the next DIV change and the next falling edge of the bit selected by TAC
+    fn schedule_timer_events(&mut self) {
+        let divider = self.get_divider() as u64;
+
+        self.scheduler.schedule(Event::DivIncrement, DIV_PERIOD - divider % DIV_PERIOD);
+
+        if self.timer_control & 0b100 == 0 {
+            self.scheduler.cancel(Event::TimerIncrement);
+        } else {
+            let period = Self::get_timer_period(self.timer_control);
+            self.scheduler.schedule(Event::TimerIncrement, period - divider % period);
         }
+    }
 
+    /// Increment the timer counter
+    /// Note, this doesn't check whether the timer is enabled, the callers only do so on a falling edge
+    pub fn increment_timer(&mut self) {
         let previous_val = self.mmu.read_byte(TIMER_COUNTER_ADDRESS);
         let (new_val, overflow) = previous_val.overflowing_add(1);
 
         if overflow {
             log::debug!("Timer overflow at Speed: {:#?} - resetting to modulo & setting interrupt flag",
-                self.get_timer_modulo());
+                self.get_timer_bit());
             self.mmu.write_byte(
                 TIMER_COUNTER_ADDRESS,
                 self.mmu.read_byte(TIMER_MODULO_ADDRESS),
@@ -30,25 +92,71 @@ impl CPU {
         }
     }
 
-    /// Increment the divider register
-    pub fn increment_div(&mut self) {
-        let previous_val = self.mmu.read_byte(0xFF04);
-        let (new_val, _overflow) = previous_val.overflowing_add(1);
-        // Set to 0 if overflow
-        self.mmu.IO.write_div_register(new_val);
+    /// Whether the timer is enabled and the bit selected by the timer control is set
+    fn get_timer_signal(&self, timer_control: u8) -> bool {
+        timer_control & 0b100 != 0 && self.get_divider() & Self::get_timer_bit_for(timer_control) != 0
+    }
+
+    /// T-cycles between two increments of TIMA
+    fn get_timer_period(timer_control: u8) -> u64 {
+        (Self::get_timer_bit_for(timer_control) as u64) << 1
+    }
+
+    /// Get the bit of the internal counter that clocks TIMA based on the timer speed
+    pub fn get_timer_bit(&self) -> u16 {
+        Self::get_timer_bit_for(self.mmu.read_byte(TIMER_CONTROL_ADDRESS))
     }
 
-    /// Get the timer modulo based on the timer speed
-    pub fn get_timer_modulo(&mut self) -> u64 {
-        let timer_speed = self.mmu.read_byte(0xFF07) & 0b11;
+    fn get_timer_bit_for(timer_control: u8) -> u16 {
+        let timer_speed = timer_control & 0b11;
 
         // See: https://gbdev.io/pandocs/Timer_and_Divider_Registers.html#ff07--tac-timer-control
         match timer_speed {
-            0b00 => 256,
-            0b01 => 4,
-            0b10 => 16,
-            0b11 => 64,
+            0b00 => 1 << 9, // Every 256 M-cycles
+            0b01 => 1 << 3, // Every 4 M-cycles
+            0b10 => 1 << 5, // Every 16 M-cycles
+            0b11 => 1 << 7, // Every 64 M-cycles
             _ => panic!("Invalid timer speed"),
         }
     }
 }
+
+#[test]
+pub fn timer_tick_test() {
+    use super::clock::T_CYCLES_PER_M_CYCLE;
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+
+    // DIV increments every 64 M-cycles
+    for _ in 0..DIV_PERIOD / T_CYCLES_PER_M_CYCLE {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 1);
+
+    // Writing DIV resets it
+    cpu.mmu.write_byte(0xFF04, 0x12);
+    cpu.idle_cycle();
+    assert_eq!(cpu.mmu.read_byte(0xFF04), 0);
+
+    // TIMA increments every 4 M-cycles and reloads TMA on overflow
+    cpu.mmu.write_byte(TIMER_CONTROL_ADDRESS, 0b101);
+    cpu.mmu.write_byte(TIMER_MODULO_ADDRESS, 0xAB);
+    cpu.mmu.write_byte(TIMER_COUNTER_ADDRESS, 0xFE);
+    for _ in 0..4 {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.mmu.read_byte(TIMER_COUNTER_ADDRESS), 0xFF);
+    for _ in 0..4 {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.mmu.read_byte(TIMER_COUNTER_ADDRESS), 0xAB);
+    assert_eq!(cpu.mmu.read_byte(0xFF0F) & 0b100, 0b100);
+
+    // Disabling the timer while the selected bit is set increments TIMA
+    cpu.idle_cycle();
+    cpu.idle_cycle();
+    cpu.mmu.write_byte(TIMER_CONTROL_ADDRESS, 0b001);
+    cpu.idle_cycle();
+    assert_eq!(cpu.mmu.read_byte(TIMER_COUNTER_ADDRESS), 0xAC);
+}
@@ -0,0 +1,202 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{cpu::CPU_FREQUENCY, rendering::line_rendering::DOTS_PER_FRAME};
+
+/// Frames that can be skipped in a row before one is rendered anyway, only while the speed is capped
+const MAX_FRAME_SKIP: u32 = 8;
+/// Rate frames are rendered at while the speed is uncapped
+const UNCAPPED_RENDER_INTERVAL: Duration = Duration::from_millis(1000 / 60);
+/// Falling further behind than this drops the lost time instead of catching up
+const MAX_LAG: Duration = Duration::from_millis(250);
+/// The emulation speed is measured over this time
+const MEASURE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Duration of a frame on the emulated clock, 59.7275 frames per second
+pub fn get_frame_duration() -> Duration {
+    Duration::from_secs_f64(DOTS_PER_FRAME as f64 / CPU_FREQUENCY as f64)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Speed {
+    Quarter,
+    Half,
+    Normal,
+    Double,
+    /// As fast as the host allows
+    Uncapped,
+}
+
+const SPEEDS: [Speed; 5] = [Speed::Quarter, Speed::Half, Speed::Normal, Speed::Double, Speed::Uncapped];
+
+impl Speed {
+    /// Factor of the real hardware's speed, None if uncapped
+    pub fn get_multiplier(self) -> Option<f64> {
+        match self {
+            Speed::Quarter => Some(0.25),
+            Speed::Half => Some(0.5),
+            Speed::Normal => Some(1.0),
+            Speed::Double => Some(2.0),
+            Speed::Uncapped => None,
+        }
+    }
+
+    pub fn faster(self) -> Self {
+        SPEEDS[(self as usize + 1).min(SPEEDS.len() - 1)]
+    }
+
+    pub fn slower(self) -> Self {
+        SPEEDS[(self as usize).saturating_sub(1)]
+    }
+}
+
+/// Paces the emulation against the emulated clock instead of the wall time a frame took
+/// Frames are skipped while the emulation is behind, so rendering doesn't limit higher speeds
+pub struct FramePacer {
+    speed: Speed,
+    /// Uncaps the speed while the fast-forward key is held
+    fast_forward: bool,
+    /// The wall time frame 0 is due at, reset whenever the speed changes
+    reference_time: Instant,
+    /// Frames emulated since `reference_time`
+    frames: u32,
+    last_render_time: Instant,
+    skipped_frames: u32,
+    measure_start: Instant,
+    measured_frames: u32,
+    /// Emulation speed in percent of the real hardware, measured over the last second
+    emulation_speed: f64,
+}
+
+impl Default for FramePacer {
+    fn default() -> Self {
+        Self::new(Speed::Normal)
+    }
+}
+
+impl FramePacer {
+    pub fn new(speed: Speed) -> Self {
+        let now = Instant::now();
+
+        Self {
+            speed,
+            fast_forward: false,
+            reference_time: now,
+            frames: 0,
+            last_render_time: now,
+            skipped_frames: 0,
+            measure_start: now,
+            measured_frames: 0,
+            emulation_speed: 0.0,
+        }
+    }
+
+    pub fn get_speed(&self) -> Speed {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: Speed) {
+        if speed != self.speed {
+            log::info!("⏩ Speed: {:?}", speed);
+            self.speed = speed;
+            self.reset();
+        }
+    }
+
+    pub fn set_fast_forward(&mut self, fast_forward: bool) {
+        if fast_forward != self.fast_forward {
+            self.fast_forward = fast_forward;
+            self.reset();
+        }
+    }
+
+    pub fn get_emulation_speed(&self) -> f64 {
+        self.emulation_speed
+    }
+
+    /// Call once per emulated frame, returns whether the frame should be rendered
+    pub fn on_frame(&mut self) -> bool {
+        let now = Instant::now();
+        self.frames += 1;
+
+        self.measured_frames += 1;
+        let measured_time = now - self.measure_start;
+        if measured_time >= MEASURE_INTERVAL {
+            let emulated_time = get_frame_duration() * self.measured_frames;
+            self.emulation_speed = 100.0 * emulated_time.as_secs_f64() / measured_time.as_secs_f64();
+            self.measure_start = now;
+            self.measured_frames = 0;
+        }
+
+        // Every rendered frame waits for vsync, so uncapped frames are only rendered by the interval
+        let render = match self.get_due_time() {
+            Some(due_time) => now <= due_time || self.skipped_frames >= MAX_FRAME_SKIP,
+            None => now - self.last_render_time >= UNCAPPED_RENDER_INTERVAL,
+        };
+
+        if render {
+            self.skipped_frames = 0;
+            self.last_render_time = now;
+            true
+        } else {
+            self.skipped_frames += 1;
+            false
+        }
+    }
+
+    /// Sleep until the current frame is due on the emulated clock
+    pub fn wait(&mut self) {
+        let Some(due_time) = self.get_due_time() else {
+            return;
+        };
+
+        let now = Instant::now();
+        if due_time > now {
+            thread::sleep(due_time - now);
+        } else if now - due_time > MAX_LAG {
+            // The host can't keep up, continue from here instead of rushing through the lost time
+            self.reset();
+        }
+    }
+
+    fn get_multiplier(&self) -> Option<f64> {
+        match self.fast_forward {
+            true => None,
+            false => self.speed.get_multiplier(),
+        }
+    }
+
+    fn get_due_time(&self) -> Option<Instant> {
+        let multiplier = self.get_multiplier()?;
+        Some(self.reference_time + get_frame_duration().mul_f64(self.frames as f64 / multiplier))
+    }
+
+    fn reset(&mut self) {
+        self.reference_time = Instant::now();
+        self.frames = 0;
+    }
+}
+
+#[test]
+pub fn speed_test() {
+    assert!((1.0 / get_frame_duration().as_secs_f64() - 59.7275).abs() < 0.0001);
+
+    assert_eq!(Speed::Quarter.slower(), Speed::Quarter);
+    assert_eq!(Speed::Normal.faster(), Speed::Double);
+    assert_eq!(Speed::Uncapped.faster(), Speed::Uncapped);
+
+    // Fast-forward uncaps any speed
+    let mut pacer = FramePacer::new(Speed::Half);
+    assert!(pacer.get_due_time().is_some());
+    pacer.set_fast_forward(true);
+    assert!(pacer.get_due_time().is_none());
+    pacer.set_fast_forward(false);
+    pacer.set_speed(Speed::Uncapped);
+    assert!(pacer.get_due_time().is_none());
+
+    // Uncapped frames aren't forced to render after skipping a few
+    pacer.last_render_time = Instant::now();
+    assert!((0..4 * MAX_FRAME_SKIP).all(|_| !pacer.on_frame()));
+}
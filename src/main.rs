@@ -4,27 +4,83 @@ pub mod test_helpers;
 pub mod cpu;
 pub mod rendering;
 pub mod mmu;
-
-use std::{fs::File, io::Write, ops::Sub, thread, time};
-
-use cpu::CPU;
-use macroquad::{prelude::*, ui::root_ui};
-use mmu::MemoryOperations;
+pub mod save_state;
+pub mod rewind;
+pub mod frame_pacing;
+pub mod movie;
+pub mod cheats;
+pub mod ram_search;
+
+use std::{fs::File, io::{BufWriter, Write}, ops::Sub, path::Path, time};
+
+use cheats::{Cheat, CheatList};
+use cpu::{hardware_model::HardwareModel, CPU};
+use macroquad::{hash, prelude::*, ui::root_ui};
+use mmu::{
+    image_source::{FrameSequence, ImageSource, StillImage, TestPattern},
+    MemoryOperations, MMU,
+};
 use rendering::{
-    line_rendering::{self},
+    line_rendering::PALETTE,
+    recording::{VideoFormat, VideoRecorder},
+    screenshot,
     tiles::{self, *},
     views::*,
 };
-use rfd::FileDialog;
+use frame_pacing::{FramePacer, Speed};
+use movie::{Movie, MovieStart, MovieState};
+use ram_search::{Filter, RamSearch, ValueFormat, VALUE_FORMATS};
+use rewind::RewindBuffer;
+use rfd::{FileDialog, MessageDialog, MessageLevel};
 use simple_log::LogConfigBuilder;
 
 extern crate simple_log;
 
 use crate::cpu::registers::{Register16Bit, Register8Bit};
 
-const TIME_PER_FRAME: f32 = 1000.0 / 59.73;
-
 const DUMP_GAMEBOY_DOCTOR_LOG: bool = false;
+/// The emulated model, see `HardwareModel`
+const HARDWARE_MODEL: HardwareModel = HardwareModel::DMG;
+/// Boot ROM dump for the model, only the DMG boot ROM is embedded
+/// Without one the boot ROM is skipped
+const BOOT_ROM_PATH: Option<&str> = None;
+/// Images the Pocket Camera sees, a PNG or PGM file or a sequence of them changing with every capture
+/// Without any the camera sees a moving gradient
+const CAMERA_IMAGE_PATHS: &[&str] = &[];
+/// Holding this key steps back through the snapshots of the rewind buffer
+const REWIND_KEY: KeyCode = KeyCode::Backspace;
+/// Frames between two rewind snapshots, rewinding goes back this many frames per frame
+const REWIND_INTERVAL: u32 = 2;
+/// Memory the rewind buffer may use, about a minute of gameplay
+const REWIND_MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+/// Holding this key runs the emulation as fast as possible
+const FAST_FORWARD_KEY: KeyCode = KeyCode::Space;
+/// Step through 0.25x, 0.5x, 1x, 2x and uncapped
+const SPEED_UP_KEY: KeyCode = KeyCode::Equal;
+const SPEED_DOWN_KEY: KeyCode = KeyCode::Minus;
+/// Saves the current frame as PNG, named after the ROM title & the current time
+const SCREENSHOT_KEY: KeyCode = KeyCode::F12;
+const SCREENSHOT_DIRECTORY: &str = "screenshots";
+/// 1 for the native 160x144, the debug views are scaled by the same factor
+const SCREENSHOT_SCALE: u32 = 1;
+/// Also save the tile atlas & background map
+const SCREENSHOT_DEBUG_VIEWS: bool = true;
+/// Starts & stops recording every emulated frame, named like the screenshots
+const RECORDING_KEY: KeyCode = KeyCode::F9;
+const RECORDING_DIRECTORY: &str = "recordings";
+const RECORDING_FORMAT: VideoFormat = VideoFormat::Y4m;
+/// Input movie to play back, it has to be recorded with the same ROM
+const MOVIE_PLAYBACK_PATH: Option<&str> = None;
+/// Record a movie from power-on, the save isn't loaded so the run can be reproduced
+const MOVIE_RECORD_FROM_POWER_ON: bool = false;
+/// Starts recording a movie from the current state, stops & saves the recording or stops the playback
+const MOVIE_KEY: KeyCode = KeyCode::F7;
+const MOVIE_DIRECTORY: &str = "movies";
+/// While paused the frame advance key runs a single frame
+const PAUSE_KEY: KeyCode = KeyCode::P;
+const FRAME_ADVANCE_KEY: KeyCode = KeyCode::N;
+/// The RAM search only lists this many candidates, narrow the search down further to see the others
+const MAX_LISTED_CANDIDATES: usize = 32;
 #[cfg(target_os = "linux")]
 const WINDOWS: bool = false;
 #[cfg(target_os = "windows")]
@@ -41,12 +97,6 @@ async fn main() {
         .build();
     simple_log::new(config).unwrap();
 
-    const PALETTE: [Color; 4] = [
-        Color::new(232.0/255.0, 252.0/255.0, 204.0/255.0, 1.00),
-        Color::new(172.0/255.0, 212.0/255.0, 144.0/255.0, 1.00),
-        Color::new(084.0/255.0, 140.0/255.0, 112.0/255.0, 1.00),
-        Color::new(020.0/255.0, 044.0/255.0, 056.0/255.0, 1.00),
-    ];
     const SCALING: f32 = 4.0;
 
     let mut gb_display = GbDisplay::new(5.0, 5.0, SCALING);
@@ -70,40 +120,119 @@ async fn main() {
 
     let rom = std::fs::read(filepath.expect("No file was found")).expect("Unable to read file");
 
-    let mut cpu = cpu::CPU::new(rom);
-    let mut ppu = line_rendering::Ppu::new();
+    // Unsupported cartridges are reported instead of crashing
+    let mut cpu = match cpu::CPU::from_rom(rom) {
+        Ok(cpu) => cpu,
+        Err(e) => {
+            log::error!("❌ Unable to load {}: {}", filedialog.display(), e);
+            MessageDialog::new()
+                .set_level(MessageLevel::Error)
+                .set_title("Unable to load ROM")
+                .set_description(e)
+                .show();
+            return;
+        }
+    };
+    cpu.set_hardware_model(HARDWARE_MODEL);
+
+    // The movie decides the model, so it has to be set up before anything else
+    let playback_movie = MOVIE_PLAYBACK_PATH.and_then(|path| {
+        Movie::load(Path::new(path))
+            .and_then(|movie| movie.prepare_playback(&mut cpu).map(|_| movie))
+            .inspect_err(|e| log::warn!("Unable to play movie: {}", e))
+            .ok()
+    });
+    let movie_from_power_on = playback_movie.is_some() || MOVIE_RECORD_FROM_POWER_ON;
+
+    // Battery buffered RAM is saved next to the ROM
+    let save_path = filedialog.with_extension("sav");
+    if cpu.mmu.has_battery() && !movie_from_power_on {
+        if let Ok(save) = std::fs::read(&save_path) {
+            if let Err(e) = cpu.mmu.load_cartridge_ram(&save) {
+                log::warn!("Unable to load save: {}", e);
+            }
+        }
+    }
+
+    // Cheats are kept per ROM in a text file next to it
+    let cheats_path = filedialog.with_extension("cht");
+    let mut cheats = match cheats_path.exists() {
+        true => CheatList::load(&cheats_path)
+            .inspect_err(|e| log::warn!("Unable to load cheats: {}", e))
+            .unwrap_or_default(),
+        false => CheatList::default(),
+    };
+    cheats.apply_rom_patches(&mut cpu.mmu);
+    let mut cheat_input = String::new();
+    let mut cheat_message = String::new();
+    let mut ram_search = RamSearch::new(ValueFormat::Byte);
+    let mut ram_search_format = 0;
+    let mut ram_search_input = String::new();
+    let mut ram_search_message = String::new();
+
+    let camera_image_source: Result<Box<dyn ImageSource>, String> = match CAMERA_IMAGE_PATHS {
+        [] => Ok(Box::new(TestPattern::gradient())),
+        [path] => StillImage::from_file(Path::new(path)).map(|image| Box::new(image) as _),
+        paths => {
+            let paths: Vec<&Path> = paths.iter().map(Path::new).collect();
+            FrameSequence::from_files(&paths).map(|sequence| Box::new(sequence) as _)
+        }
+    };
+    match camera_image_source {
+        Ok(source) => cpu.mmu.set_camera_image_source(source),
+        Err(e) => log::warn!("Unable to load camera images: {}", e),
+    }
+
+    if let Some(boot_rom_path) = BOOT_ROM_PATH {
+        let boot_rom = std::fs::read(boot_rom_path).expect("Unable to read boot ROM");
+        cpu.load_boot_rom(boot_rom).expect("Invalid boot ROM");
+    }
 
     // Get start time
-    let mut last_frame_time = time::Instant::now();
-    let mut ppu_time = time::Instant::now();
     let mut fps_time = time::Instant::now();
     let mut fps = 0;
     let mut dump_time = time::Instant::now();
     let mut frame = 0;
+    let mut rewind_buffer = RewindBuffer::new(REWIND_INTERVAL, REWIND_MEMORY_LIMIT);
+    let mut frame_pacer = FramePacer::new(Speed::Normal);
+    let mut recorder: Option<VideoRecorder<BufWriter<File>>> = None;
 
     // Open "registers.txt" file for Gameboy Doctor
     let mut gb_doctor_file = std::fs::File::create("gameboy_doctor_log.txt").unwrap();
-    if DUMP_GAMEBOY_DOCTOR_LOG {
+    if DUMP_GAMEBOY_DOCTOR_LOG || !cpu.is_boot_rom_enabled() {
         cpu.skip_boot_rom();
     }
 
-    cpu.set_ppu_mode(cpu::interrupts::PpuMode::OamScan);
+    let mut movie_state = match playback_movie {
+        Some(movie) => match movie.load_start_state(&mut cpu) {
+            Ok(()) => MovieState::Playing { movie, frame: 0 },
+            Err(e) => {
+                log::warn!("Unable to play movie: {}", e);
+                MovieState::Idle
+            }
+        },
+        None if MOVIE_RECORD_FROM_POWER_ON => MovieState::Recording(Movie::new(&cpu, MovieStart::PowerOn)),
+        None => MovieState::Idle,
+    };
+    movie_state.sync_clock(&mut cpu);
+    suspend_cheats(&mut cheats, &mut ram_search, &mut cpu.mmu, movie_state.is_active());
+    let mut paused = false;
 
     loop {
         // Check whether PC is at the end of the bootrom
-        if cpu.get_16bit_register(Register16Bit::PC) == 0x0100 {
+        if cpu.get_16bit_register(Register16Bit::PC) == 0x0100 && cpu.is_boot_rom_enabled() {
             log::info!("🚀 Bootrom finished");
             cpu.skip_boot_rom();
         }
 
-        cpu.increment_div();
-
         if DUMP_GAMEBOY_DOCTOR_LOG {
             dump_cpu_info(&cpu, &mut gb_doctor_file);
         }
 
-        let instruction = cpu.prepare_and_decode_next_instruction();
-        log::debug!("🔠 Instruction: {:?}", instruction);
+        // Decoding is only needed for the log, the CPU executes the opcodes directly
+        if log::log_enabled!(log::Level::Debug) {
+            log::debug!("🔠 Instruction: {:?}", cpu.decode_next_instruction());
+        }
         let is_bootrom_enabled = cpu.is_boot_rom_enabled();
         let result = cpu.step();
         match result {
@@ -114,61 +243,386 @@ async fn main() {
             }
         }
         log::debug!("➡️ Result: {:?} | Bootrom: {:?}", result, is_bootrom_enabled);
-        let cpu_cycles_taken = result.unwrap().cycles;
 
         let pc_following_word = cpu
             .mmu
             .read_word(cpu.get_16bit_register(Register16Bit::PC) + 1);
         log::debug!("🔢 Following Word (PC): {:#06X}", pc_following_word);
 
-        for _ in 0..=cpu_cycles_taken {
-            ppu.step(&mut cpu, &mut gb_display.get_gb_image(), &PALETTE);
-
-            // Alternatively Redraw UI at 30 frames per second: (ppu_time.elapsed().as_millis() as f32) >= TIME_PER_FRAME
-            // Draw when a frame is done
-            if ppu.get_frame_cycles() == 0 {
-                // Check whether 1 second has passed to update the FPS
-                if fps_time.elapsed().as_secs() >= 1 {
-                    fps_time = time::Instant::now();
-                    fps = frame;
-                    frame = 0;
+        // The PPU has been ticked by the CPU, draw when a frame is done
+        if cpu.get_ppu_mut().take_frame_ready() {
+            // While rewinding the emulated frame is replaced by the previous snapshot
+            // Movies can't be rewound, their input has to stay in sync with the frames
+            if is_key_down(REWIND_KEY) && !movie_state.is_active() {
+                rewind_buffer.rewind(&mut cpu);
+            } else {
+                rewind_buffer.on_frame(&cpu);
+            }
+
+            // Every frame is recorded, also the ones skipped below
+            if let Some(video) = &mut recorder {
+                if let Err(e) = video.write_frame(&cpu.get_ppu().get_image().bytes) {
+                    log::warn!("Unable to record frame, stopping the recording: {}", e);
+                    recorder = None;
                 }
+            }
 
-                // Poll inputs
-                cpu.poll_inputs();
-                cpu.blarg_print();
-                ppu_time = time::Instant::now();
-
-                // Inform about the time it took to render the frame
-                root_ui().label(
-                None,
-                format!(
-                    "FPS: {:?} | Dots: {:?} | CPU Cycle: {:?} | Frame: {:?}",
-                    fps,
-                    ppu.get_dot(),
-                    cpu.get_cycles(),
-                    frame,
-                )
-                .as_str(),
-                );
+            // Check whether 1 second has passed to update the FPS
+            if fps_time.elapsed().as_secs() >= 1 {
+                fps_time = time::Instant::now();
+                fps = frame;
+                frame = 0;
+
+                // There is no proper exit, so the save is written regularly
+                if cpu.mmu.has_battery() && !movie_state.is_active() {
+                    if let Err(e) = std::fs::write(&save_path, cpu.mmu.get_cartridge_ram()) {
+                        log::warn!("Unable to write save: {}", e);
+                    }
+                }
+            }
 
-                // Update Debugging Views
-                update_atlas_from_memory(&cpu, 16 * 24, &mut tile_viewer.get_atlas(), &PALETTE);
-                update_background_from_memory(&cpu, &mut background_viewer.get_image(), &PALETTE, false, true);
+            // Poll inputs, movies record or replace them
+            movie_state.poll_inputs(&mut cpu);
+            suspend_cheats(&mut cheats, &mut ram_search, &mut cpu.mmu, movie_state.is_active());
+            cheats.apply_ram_writes(&mut cpu.mmu);
+            ram_search.apply_freezes(&mut cpu.mmu);
+            cpu.blarg_print();
+
+            // Frames are skipped while the emulation is behind, e.g. at higher speeds
+            frame_pacer.set_fast_forward(is_key_down(FAST_FORWARD_KEY));
+            if !frame_pacer.on_frame() && !paused {
+                frame_pacer.wait();
+                continue;
+            }
+
+            // Key presses are only updated by `next_frame`, so they are checked on rendered frames
+            if is_key_pressed(SPEED_UP_KEY) {
+                frame_pacer.set_speed(frame_pacer.get_speed().faster());
+            } else if is_key_pressed(SPEED_DOWN_KEY) {
+                frame_pacer.set_speed(frame_pacer.get_speed().slower());
+            }
+
+            // Inform about the time it took to render the frame
+            root_ui().label(
+            None,
+            format!(
+                "FPS: {:?} | Speed: {:.0}% | Dots: {:?} | CPU Cycle: {:?} | Frame: {:?}",
+                fps,
+                frame_pacer.get_emulation_speed(),
+                cpu.get_ppu().get_dot(),
+                cpu.get_cycles(),
+                frame,
+            )
+            .as_str(),
+            );
+
+            // Update Debugging Views
+            update_atlas_from_memory(&cpu, 16 * 24, &mut tile_viewer.get_atlas(), &PALETTE);
+            update_background_from_memory(&cpu, &mut background_viewer.get_image(), &PALETTE, false, true);
+            background_viewer.draw();
+            tile_viewer.draw();
+
+            gb_display
+                .get_gb_image()
+                .get_image_data_mut()
+                .copy_from_slice(cpu.get_ppu().get_image().get_image_data());
+            gb_display.draw();
+
+            let cheat_window_position = vec2(5.0, gb_display.size().y + 10.0);
+            let cheat_window_size = vec2(gb_display.size().x, tile_viewer.size().y - gb_display.size().y - 5.0);
+            let mut cheats_changed =
+                draw_cheat_window(&mut cheats, &mut cheat_input, &mut cheat_message, cheat_window_position, cheat_window_size);
+
+            let ram_search_position = vec2(gb_display.size().x + 10.0, background_viewer.size().y + 10.0);
+            let ram_search_size = vec2(background_viewer.size().x, tile_viewer.size().y - background_viewer.size().y - 5.0);
+            let exported = draw_ram_search_window(
+                &mut ram_search,
+                &mut cpu.mmu,
+                &mut ram_search_format,
+                &mut ram_search_input,
+                &mut ram_search_message,
+                ram_search_position,
+                ram_search_size,
+            );
+            cheats_changed |= !exported.is_empty();
+            for cheat in exported {
+                cheats.add(cheat);
+            }
+
+            if cheats_changed {
+                cheats.apply_rom_patches(&mut cpu.mmu);
+                if let Err(e) = cheats.save(&cheats_path) {
+                    log::warn!("Unable to save cheats: {}", e);
+                }
+            }
+
+            if is_key_pressed(SCREENSHOT_KEY) {
+                let debug_views = [("tiles", &*tile_viewer.get_atlas()), ("background", &*background_viewer.get_image())];
+                let debug_views = if SCREENSHOT_DEBUG_VIEWS { &debug_views[..] } else { &[] };
+
+                match screenshot::save_screenshot(
+                    Path::new(SCREENSHOT_DIRECTORY),
+                    &cpu.mmu.get_title(),
+                    SCREENSHOT_SCALE,
+                    gb_display.get_gb_image(),
+                    debug_views,
+                ) {
+                    Ok(paths) => log::info!("📷 Screenshot saved to {:?}", paths),
+                    Err(e) => log::warn!("Unable to save screenshot: {}", e),
+                }
+            }
+
+            if is_key_pressed(RECORDING_KEY) {
+                match recorder.take() {
+                    Some(video) => match video.finish() {
+                        Ok(_) => log::info!("🎬 Recording stopped"),
+                        Err(e) => log::warn!("Unable to finish recording: {}", e),
+                    },
+                    None => {
+                        let directory = Path::new(RECORDING_DIRECTORY);
+                        let extension = RECORDING_FORMAT.get_extension();
+                        let name = screenshot::get_unused_base_name(directory, &cpu.mmu.get_title(), extension);
+                        let path = directory.join(format!("{}.{}", name, extension));
+
+                        recorder = std::fs::create_dir_all(directory)
+                            .map_err(|e| e.to_string())
+                            .and_then(|_| VideoRecorder::create(&path, RECORDING_FORMAT))
+                            .inspect(|_| log::info!("🎬 Recording to {}", path.display()))
+                            .inspect_err(|e| log::warn!("Unable to start recording: {}", e))
+                            .ok();
+                    }
+                }
+            }
+
+            // Recordings start after the inputs of this frame have been polled, so playback starts with the next one
+            if is_key_pressed(MOVIE_KEY) {
+                match std::mem::take(&mut movie_state) {
+                    MovieState::Idle => {
+                        log::info!("🎞️ Recording movie");
+                        movie_state = MovieState::Recording(Movie::new(&cpu, MovieStart::Snapshot(cpu.save_snapshot())));
+                    }
+                    MovieState::Recording(movie) => {
+                        let directory = Path::new(MOVIE_DIRECTORY);
+                        let name = screenshot::get_unused_base_name(directory, &cpu.mmu.get_title(), "gbm");
+                        let path = directory.join(format!("{}.gbm", name));
+
+                        let saved = std::fs::create_dir_all(directory)
+                            .map_err(|e| e.to_string())
+                            .and_then(|_| movie.save(&path));
+                        match saved {
+                            Ok(()) => log::info!("🎞️ Movie of {} frames saved to {}", movie.len(), path.display()),
+                            Err(e) => log::warn!("Unable to save movie: {}", e),
+                        }
+                    }
+                    MovieState::Playing { frame, .. } => log::info!("🎞️ Movie stopped after {} frames", frame),
+                }
+                movie_state.sync_clock(&mut cpu);
+                suspend_cheats(&mut cheats, &mut ram_search, &mut cpu.mmu, movie_state.is_active());
+            }
+
+            paused ^= is_key_pressed(PAUSE_KEY);
+
+            next_frame().await;
+            frame += 1;
+
+            // Keep showing the frame till the next one is requested
+            while paused {
                 background_viewer.draw();
                 tile_viewer.draw();
-
                 gb_display.draw();
                 next_frame().await;
-                frame += 1;
 
-                thread::sleep(time::Duration::from_millis(
-                    (TIME_PER_FRAME - last_frame_time.elapsed().as_millis() as f32) as u64,
-                ));
-                last_frame_time = time::Instant::now();
+                if is_key_pressed(PAUSE_KEY) {
+                    paused = false;
+                } else if is_key_pressed(FRAME_ADVANCE_KEY) {
+                    break;
+                }
+            }
+
+            frame_pacer.wait();
+        }
+    }
+}
+
+/// Lists the cheats with a toggle & a remove button each, new codes are entered as `CODE description`
+/// Returns whether the list has been changed
+fn draw_cheat_window(cheats: &mut CheatList, input: &mut String, message: &mut String, position: Vec2, size: Vec2) -> bool {
+    let mut changed = false;
+
+    root_ui().window(hash!(), position, size, |ui| {
+        let mut removed = None;
+        let suspended = cheats.is_suspended();
+        for (index, cheat) in cheats.get_cheats_mut().iter_mut().enumerate() {
+            let enabled = cheat.enabled;
+            ui.checkbox(hash!("cheat", index), &format!("{} {}", cheat.code, cheat.description), &mut cheat.enabled);
+            if cheat.enabled && !enabled && suspended {
+                cheat.enabled = false;
+                *message = "Cheats can't be enabled while a movie is recording or playing".to_string();
             }
+            changed |= enabled != cheat.enabled;
+
+            ui.same_line(0.0);
+            if ui.button(None, "Remove") {
+                removed = Some(index);
+            }
+        }
+
+        if let Some(index) = removed {
+            let cheat = cheats.remove(index);
+            log::info!("Removed cheat {}", cheat.code);
+            changed = true;
         }
+
+        ui.separator();
+        ui.input_text(hash!(), "Code", input);
+        if ui.button(None, "Add cheat") {
+            let (code, description) = input.trim().split_once(char::is_whitespace).unwrap_or((input.trim(), ""));
+            match Cheat::new(code, description) {
+                Ok(cheat) => {
+                    cheats.add(cheat);
+                    input.clear();
+                    message.clear();
+                    changed = true;
+                }
+                Err(e) => *message = e,
+            }
+        }
+        ui.label(None, message);
+    });
+
+    changed
+}
+
+/// Cheats & frozen values aren't part of movies, so they are suspended while one is recording or playing
+fn suspend_cheats(cheats: &mut CheatList, ram_search: &mut RamSearch, mmu: &mut MMU, suspended: bool) {
+    if cheats.is_suspended() == suspended {
+        return;
     }
+
+    match suspended {
+        true => log::info!("Cheats are suspended while the movie is active"),
+        false => log::info!("Cheats are active again"),
+    }
+    cheats.set_suspended(suspended);
+    cheats.apply_rom_patches(mmu);
+    ram_search.set_suspended(suspended);
+}
+
+/// Searches the RAM with the filters on the value entered, found addresses can be watched, frozen
+/// and exported to the cheat list as GameShark codes
+/// Returns the GameShark cheats exported this frame
+fn draw_ram_search_window(
+    search: &mut RamSearch,
+    mmu: &mut MMU,
+    format_index: &mut usize,
+    input: &mut String,
+    message: &mut String,
+    position: Vec2,
+    size: Vec2,
+) -> Vec<Cheat> {
+    let mut exported = Vec::new();
+
+    root_ui().window(hash!(), position, size, |ui| {
+        let format_names: Vec<&str> = VALUE_FORMATS.iter().map(|format| format.get_name()).collect();
+        ui.combo_box(hash!(), "Format", &format_names, &mut *format_index);
+        ui.input_text(hash!(), "Value", input);
+
+        if ui.button(None, "New search") {
+            search.start(mmu, VALUE_FORMATS[*format_index]);
+        }
+
+        let value = input.trim().parse::<u32>().ok();
+        let difference = input.trim().parse::<i64>().ok();
+        let filters: [(&str, Option<Filter>); 9] = [
+            ("=", value.map(Filter::Equal)),
+            ("!=", value.map(Filter::NotEqual)),
+            (">", value.map(Filter::Greater)),
+            ("<", value.map(Filter::Less)),
+            ("Increased", Some(Filter::Increased)),
+            ("Decreased", Some(Filter::Decreased)),
+            ("Changed", Some(Filter::Changed)),
+            ("Unchanged", Some(Filter::Unchanged)),
+            ("Changed by", difference.map(Filter::ChangedBy)),
+        ];
+        for (label, filter) in filters {
+            ui.same_line(0.0);
+            if ui.button(None, label) {
+                match filter {
+                    Some(filter) => {
+                        search.filter(mmu, filter);
+                        message.clear();
+                    }
+                    None => *message = "Enter a number to filter by".to_string(),
+                }
+            }
+        }
+
+        let format = search.get_format();
+        ui.label(None, &format!("{} candidates ({})", search.get_candidates().len(), format.get_name()));
+        for candidate in search.get_candidates().iter().take(MAX_LISTED_CANDIDATES).copied().collect::<Vec<_>>() {
+            let current = format.read(mmu, candidate.address);
+            ui.label(None, &format!("{:04X}: {} (was {})", candidate.address, format_value(current), candidate.previous));
+            ui.same_line(0.0);
+            if ui.button(None, "Watch") {
+                search.watch(candidate.address);
+            }
+        }
+
+        ui.separator();
+        let mut removed = None;
+        for index in 0..search.get_watches().len() {
+            let watch = search.get_watches()[index];
+            let current = watch.format.read(mmu, watch.address);
+            ui.label(None, &format!("{:04X} {}: {}", watch.address, watch.format.get_name(), format_value(current)));
+
+            let mut frozen = watch.frozen.is_some();
+            ui.same_line(0.0);
+            ui.checkbox(hash!("freeze", index), "Freeze", &mut frozen);
+            if frozen != watch.frozen.is_some() {
+                match search.set_frozen(mmu, index, frozen) {
+                    Ok(()) => message.clear(),
+                    Err(e) => {
+                        log::warn!("Unable to freeze: {}", e);
+                        *message = e;
+                    }
+                }
+            }
+
+            ui.same_line(0.0);
+            if ui.button(None, "Export") {
+                for code in search.export_gameshark_codes(mmu, index) {
+                    match Cheat::new(&code, &format!("RAM search {:04X}", watch.address)) {
+                        Ok(cheat) => {
+                            log::info!("🔍 Exported {}", code);
+                            exported.push(cheat);
+                        }
+                        Err(e) => log::warn!("Unable to export: {}", e),
+                    }
+                }
+            }
+
+            ui.same_line(0.0);
+            if ui.button(None, "Remove") {
+                removed = Some(index);
+            }
+        }
+
+        if let Some(index) = removed {
+            search.unwatch(index);
+        }
+
+        if !message.is_empty() {
+            ui.separator();
+            ui.label(None, message);
+        }
+    });
+
+    exported
+}
+
+/// Show a RAM search value, BCD values with digits above 9 are invalid
+fn format_value(value: Option<u32>) -> String {
+    value.map_or_else(|| "invalid".to_string(), |value| value.to_string())
 }
 
 fn info_to_string(cpu: &CPU) -> String {
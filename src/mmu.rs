@@ -1,7 +1,26 @@
 use bank_00::Bank00;
 use debugging::mbc_type_to_string;
 use input_output::InputOutput;
-use mbc::{mbc1::Mbc1, no_mbc::NoMbc};
+use mbc::{
+    huc1::Huc1,
+    huc3::Huc3,
+    infrared::Infrared,
+    mbc1::Mbc1,
+    mbc2::Mbc2,
+    mbc3::Mbc3,
+    mbc5::Mbc5,
+    mbc6::Mbc6,
+    mbc7::Mbc7,
+    mmm01::Mmm01,
+    no_mbc::NoMbc,
+    pocket_camera::{image_source::ImageSource, PocketCamera},
+    sachen::Sachen,
+    tama5::Tama5,
+    wisdom_tree::WisdomTree,
+};
+use page_table::{get_page_offset, Page, PageTable, PAGE_SIZE};
+use crate::cheats::RomPatch;
+use crate::save_state::{SaveState, StateReader, StateWriter};
 use simple::SimpleRegion;
 
 mod simple;
@@ -9,12 +28,37 @@ mod input_output;
 mod mbc;
 mod bank_00;
 mod debugging;
+mod page_table;
+
+pub use mbc::infrared;
+pub use mbc::pocket_camera::image_source;
 
 static MBC_INFO_ADDRESS: usize = 0x0147;
 static MBC_ROM_SIZE_ADDRESS: usize = 0x0148;
 static MBC_RAM_SIZE_ADDRESS: usize = 0x0149;
-static ROM1_START: usize = 0x4000;
-static RAM_START: usize = 0xA000;
+/// Newer cartridges use the end of the title for the manufacturer code & CGB flag
+/// See: https://gbdev.io/pandocs/The_Cartridge_Header.html#0134-0143--title
+static TITLE_ADDRESS: std::ops::Range<usize> = 0x0134..0x0144;
+/// The logo every licensed cartridge has in its header
+pub const NINTENDO_LOGO: [u8; 0x30] = [
+    0xCE, 0xED, 0x66, 0x66, 0xCC, 0x0D, 0x00, 0x0B, 0x03, 0x73, 0x00, 0x83, 0x00, 0x0C, 0x00, 0x0D,
+    0x00, 0x08, 0x11, 0x1F, 0x88, 0x89, 0x00, 0x0E, 0xDC, 0xCC, 0x6E, 0xE6, 0xDD, 0xDD, 0xD9, 0x99,
+    0xBB, 0xBB, 0x67, 0x63, 0x6E, 0x0E, 0xEC, 0xCC, 0xDD, 0xDC, 0x99, 0x9F, 0xBB, 0xB9, 0x33, 0x3E,
+];
+pub const LOGO_ADDRESS: usize = 0x0104;
+/// Checksum of the header bytes from the title to the version number
+/// See: https://gbdev.io/pandocs/The_Cartridge_Header.html#014d--header-checksum
+static HEADER_CHECKSUM_RANGE: std::ops::Range<usize> = 0x0134..0x014D;
+static HEADER_CHECKSUM_ADDRESS: usize = 0x014D;
+pub const ROM_BANK_SIZE: usize = 0x4000;
+pub const RAM_BANK_SIZE: usize = 0x2000;
+
+/// Value read from VRAM or OAM while the PPU is using it
+const PPU_BLOCKED_VALUE: u8 = 0xFF;
+/// STAT modes in which the PPU blocks the CPU from accessing memory
+/// See: https://gbdev.io/pandocs/Rendering.html#ppu-modes
+const PPU_MODE_OAM_SCAN: u8 = 2;
+const PPU_MODE_DRAWING: u8 = 3;
 
 pub trait MemoryOperations {
     /// Read a byte from the memory region
@@ -36,15 +80,62 @@ pub trait NonMbcOperations: MemoryOperations {
     fn fill_from_slice(&mut self, data: &[u8]);
 }
 
-pub trait MemoryBankControllerOperations: MemoryOperations {
+/// The MBC only decides which banks are mapped, the MMU owns the ROM and RAM of the cartridge
+/// Its `MemoryOperations` are only used for accesses the page table doesn't map directly,
+/// i.e. writes to its registers and RAM accesses while `get_ram_address` returns None
+pub trait MemoryBankControllerOperations: MemoryOperations + SaveState {
     /// Initialize the Memory Bank Controller
     fn init(&mut self, rom_size: u8, cartridge_type: u8, ram_size: u8);
 
-    /// Fill a specific rom bank with the data from the slice
-    fn fill_rom_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x4000]);
+    /// Get the physical address within the ROM an address from 0x0000 to 0x7FFF is mapped to
+    /// Addresses beyond the ROM size are mirrored by the MMU
+    fn get_rom_address(&self, address: u16) -> usize;
+
+    /// Whether the ROM is mapped to the address, otherwise reads go to the MBC, e.g. for flash memory
+    fn is_rom_mapped(&self, _address: u16) -> bool {
+        true
+    }
+
+    /// Whether the page containing the address maps to consecutive ROM addresses
+    /// Otherwise `get_rom_address` is used for every read, e.g. for scrambled address lines
+    fn is_rom_page_linear(&self, _address: u16) -> bool {
+        true
+    }
+
+    /// Get the physical address within the RAM an address from 0xA000 to 0xBFFF is mapped to
+    /// None if the MBC handles the access itself, e.g. while the RAM is disabled
+    fn get_ram_address(&self, address: u16) -> Option<usize>;
+
+    /// Whether writes go to the RAM returned by `get_ram_address`, otherwise they go to the MBC
+    fn is_ram_writable(&self) -> bool {
+        true
+    }
+
+    /// Whether a battery keeps the RAM contents, so it should be saved
+    fn has_battery(&self) -> bool {
+        false
+    }
+
+    /// Storage the MBC manages itself instead of the cartridge RAM, e.g. an EEPROM
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        None
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        None
+    }
+
+    /// Pin the clock of cartridges with an RTC to minutes since the unix epoch, None follows the host clock
+    fn set_clock_minutes(&mut self, _minutes: Option<i64>) {}
 
-    /// Fill a specific ram bank with the data from the slice
-    fn fill_ram_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x2000]);
+    /// Tilt of the cartridge in g for MBCs with an accelerometer, from -1.0 to 1.0 per axis
+    fn set_tilt(&mut self, _x: f32, _y: f32) {}
+
+    /// Where cartridges with a camera get their images from
+    fn set_image_source(&mut self, _source: Box<dyn ImageSource>) {}
+
+    /// Connect the IR transceiver of cartridges with one
+    fn set_infrared(&mut self, _infrared: Box<dyn Infrared>) {}
 
     /// Switch the ROM bank
     fn switch_rom_bank(&mut self, bank: u8);
@@ -54,32 +145,24 @@ pub trait MemoryBankControllerOperations: MemoryOperations {
 
     /// Enable or disable the RAM
     fn enable_ram(&mut self, enable: bool);
-
-    /// Get physical address within the memory region
-    fn calc_physical_rom_address(&self, address: u16) -> usize {
-        (address as usize).wrapping_sub(ROM1_START)
-    }
-
-    /// Check if the MBC is in advanced banking mode
-    /// This means that the MBC can overwrite BANK00 with a different bank
-    fn is_advanced_banking_mode(&self) -> bool;
-    
-    /// Get physical address within the memory region
-    fn calc_physical_ram_address(&self, address: u16) -> usize {
-        log::debug!("RAM Address: {:#X}", address);
-        address as usize - RAM_START
-    }
 }
 
 pub struct MMU {
-    /// 0x0000 to 0x3FFF - ROM Bank 00
+    /// 0x0000 to 0x00FF - Boot ROM, mapped over ROM Bank 00 while enabled
     pub bank_00: Bank00,
 
     /// The cartridge is a separate memory region from 
-    /// 0x4000 to 0x7FFF for ROM 
+    /// 0x0000 to 0x7FFF for ROM 
     /// 0xA000 to 0xBFFF for RAM
+    /// The MBC maps the banks of `rom` and `cartridge_ram` into these regions
     pub mbc: Box<dyn MemoryBankControllerOperations>,
 
+    /// The whole ROM of the cartridge
+    rom: Vec<u8>,
+
+    /// The whole RAM of the cartridge, empty if it has none
+    cartridge_ram: Vec<u8>,
+
     /// 0x8000 to 0x9FFF - Graphics RAM
     pub VRAM: SimpleRegion,
 
@@ -102,130 +185,711 @@ pub struct MMU {
     pub HRAM: SimpleRegion,
 
     /// 0xFFFF - Interrupt Enable Register
-    pub interrupt_enable: u8
+    pub interrupt_enable: u8,
+
+    /// The current STAT mode of the PPU, used to block access to VRAM and OAM
+    ppu_mode: u8,
+
+    /// Whether VRAM and OAM are blocked based on the PPU mode
+    /// Debugging tools can disable this to get unrestricted access
+    ppu_access_blocking: bool,
+
+    /// Game Genie codes applied to every ROM read, pages containing one of them go through the handlers
+    rom_patches: Vec<RomPatch>,
+
+    /// Fast path for plain memory accesses, has to be updated whenever the mapping changes
+    pages: PageTable,
 }
 
 impl MMU {
-    pub fn new_from_vec(rom: Vec<u8>) -> Self {
-        let mbc_info = match rom.get(MBC_INFO_ADDRESS) {
-            Some(&mbc_info) => mbc_info,
-            None => panic!("No MBC info found in ROM")
-        };
+    /// Fails for ROMs too short to contain a header and for unsupported cartridge types
+    pub fn new_from_vec(rom: Vec<u8>) -> Result<Self, String> {
+        if read_header(&rom, MBC_RAM_SIZE_ADDRESS).is_none() {
+            return Err(format!("The ROM is too short to contain a cartridge header ({:#X} bytes)", rom.len()));
+        }
 
-        let mut mmu = MMU::new_from_mbc_info(mbc_info);
+        let mbc_info = read_header(&rom, MBC_INFO_ADDRESS).unwrap();
+        let mut mmu = MMU::new_with_mbc(create_mbc(mbc_info, &rom)?);
 
         mmu.fill_from_slice(rom.as_slice());
 
-        mmu
+        Ok(mmu)
     }
 
-    pub fn new_from_mbc_info(mbc_info: u8) -> Self {
-        let cartridge: Box<dyn MemoryBankControllerOperations> = match mbc_info {
-            0x00 => Box::new(NoMbc::default()),
-            0x01..=0x03 => Box::new(Mbc1::default()),
-            _ => panic!("Unsupported MBC type: {}", mbc_type_to_string(mbc_info))
-        };
+    pub fn new_from_mbc_info(mbc_info: u8) -> Result<Self, String> {
+        Ok(MMU::new_with_mbc(create_mbc(mbc_info, &[])?))
+    }
 
-        MMU {
+    fn new_with_mbc(cartridge: Box<dyn MemoryBankControllerOperations>) -> Self {
+        let mut mmu = MMU {
             bank_00: Bank00::default(),
             mbc: cartridge,
+            rom: vec![0; 2 * ROM_BANK_SIZE],
+            cartridge_ram: Vec::new(),
             VRAM: SimpleRegion::new(0x2000, true, 0x8000),
             WRAM: SimpleRegion::new(0x2000, true, 0xC000),
             OAM: SimpleRegion::new(0x00A0, true, 0xFE00),
             IO: InputOutput::new(0x0080, 0xFF00),
             HRAM: SimpleRegion::new(0x007F, true, 0xFF80),
-            interrupt_enable: 0
-        }
+            interrupt_enable: 0,
+            ppu_mode: 0,
+            ppu_access_blocking: true,
+            rom_patches: Vec::new(),
+            pages: PageTable::default(),
+        };
+
+        mmu.update_pages();
+
+        mmu
     }
 
     pub fn set_bootrom_enabled(&mut self, enabled: bool) {
-        self.bank_00.boot_rom_enabled = enabled;
+        self.bank_00.set_boot_rom_enabled(enabled);
+        self.update_cartridge_pages();
     }
-}
 
-impl NonMbcOperations for MMU {
-    fn fill_from_slice(&mut self, data: &[u8]) {
-        // Get relevant information from the ROM for the mbc
-        let rom_size = match data.get(MBC_ROM_SIZE_ADDRESS) {
-            Some(&rom_size) => rom_size,
-            None => panic!("No ROM size found in ROM")
-        };
-        let ram_size = match data.get(MBC_RAM_SIZE_ADDRESS) {
-            Some(&ram_size) => ram_size,
-            None => panic!("No RAM size found in ROM")
-        };
-        let mbc_info = match data.get(MBC_INFO_ADDRESS) {
-            Some(&mbc_info) => mbc_info,
-            None => panic!("No MBC info found in ROM")
+    pub fn has_battery(&self) -> bool {
+        self.mbc.has_battery()
+    }
+
+    /// Get the title of the cartridge header, up to the first non-printable character
+    pub fn get_title(&self) -> String {
+        TITLE_ADDRESS
+            .clone()
+            .map_while(|address| read_header(&self.rom, address))
+            .take_while(|byte| byte.is_ascii_graphic() || *byte == b' ')
+            .map(char::from)
+            .collect::<String>()
+            .trim_end()
+            .to_string()
+    }
+
+    /// CRC32 of the whole ROM, e.g. to check a movie belongs to this cartridge
+    pub fn get_rom_checksum(&self) -> u32 {
+        crc32fast::hash(&self.rom)
+    }
+
+    /// Get the RAM of the cartridge, e.g. to save it if it has a battery
+    pub fn get_cartridge_ram(&self) -> &[u8] {
+        self.mbc.get_internal_ram().unwrap_or(&self.cartridge_ram)
+    }
+
+    /// Restore the RAM of the cartridge from a save
+    pub fn load_cartridge_ram(&mut self, data: &[u8]) -> Result<(), String> {
+        let ram = match self.mbc.get_internal_ram_mut() {
+            Some(ram) => ram,
+            None => &mut self.cartridge_ram,
         };
 
-        log::info!("ROM Size: {:#X} RAM Size: {:#X} MBC Info: {:#X}", rom_size, ram_size, mbc_info);
-        log::info!("MBC Type: {}", mbc_type_to_string(mbc_info));
+        if data.len() != ram.len() {
+            return Err(format!(
+                "Save has {:#X} bytes, but the cartridge RAM has {:#X}",
+                data.len(),
+                ram.len()
+            ));
+        }
 
-        // Initialize the MBC
-        self.mbc.init(rom_size, mbc_info, ram_size);
+        ram.copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Whether a write to the address currently ends up in RAM instead of a register
+    /// Cartridge RAM addresses reach the MBC while the RAM is disabled, e.g. the RTC commands of HuC3
+    pub fn is_ram_mapped(&self, address: u16) -> bool {
+        match address {
+            0xA000..=0xBFFF => {
+                let has_ram = !self.cartridge_ram.is_empty();
+                has_ram && self.mbc.get_ram_address(address).is_some() && self.mbc.is_ram_writable()
+            }
+            0xC000..=0xDFFF | 0xFF80..=0xFFFE => true,
+            _ => false,
+        }
+    }
 
-        // Fill the ROM bank 00
-        self.bank_00.fill_from_slice(&data[0x0000..0x4000]);
+    /// Replace the Game Genie codes, they patch ROM reads until they are replaced again
+    pub fn set_rom_patches(&mut self, patches: Vec<RomPatch>) {
+        self.rom_patches = patches;
+        self.update_cartridge_pages();
+    }
+
+    /// Drive the clock of cartridges with an RTC (HuC3) by the given time instead of the host clock,
+    /// e.g. so movies reproduce the run, None returns to the host clock
+    pub fn set_clock_minutes(&mut self, minutes: Option<i64>) {
+        self.mbc.set_clock_minutes(minutes);
+    }
 
-        // Fill the mbc with n banks of ROM based on the ROM size
-        // The rom bank amount is 2^(n+1) where n is the value in the ROM
-        // One bank is 16 KiB
-        // https://gbdev.io/pandocs/The_Cartridge_Header.html#0148--rom-size
-        let total_banks = 2_usize.pow(rom_size as u32 + 1);
-        let rom_bank_size = 0x4000;
-        let total_rom_size = total_banks * rom_bank_size;
+    /// Tilt the cartridge, only has an effect on cartridges with an accelerometer (MBC7)
+    /// `x` and `y` are in g from -1.0 to 1.0, positive values increase the measured value
+    pub fn set_tilt(&mut self, x: f32, y: f32) {
+        self.mbc.set_tilt(x, y);
+    }
 
-        log::info!("Total ROM banks: {} Banks: {} Total Size: {}", total_banks, rom_bank_size, total_rom_size);
+    /// Replace the images the camera sees, only has an effect on the Pocket Camera
+    pub fn set_camera_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.mbc.set_image_source(source);
+    }
 
-        for bank in 0..total_banks {
-            let start = bank * rom_bank_size;
-            let end = start + rom_bank_size;
-            let slice: [u8; 0x4000] = data[start..end].try_into().unwrap();
-            self.mbc.fill_rom_bank_from_slice(bank as u8, &slice);
+    /// Connect the IR transceiver of the cartridge, e.g. to another emulator instance
+    /// Only has an effect on cartridges with IR (HuC1 & HuC3)
+    pub fn connect_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.mbc.set_infrared(infrared);
+    }
+
+    /// Inform the MMU about the current PPU mode
+    pub fn set_ppu_mode(&mut self, mode: u8) {
+        let vram_blocked = self.is_vram_blocked();
+        self.ppu_mode = mode;
+
+        if vram_blocked != self.is_vram_blocked() {
+            self.update_vram_pages();
         }
     }
-}
 
-impl MemoryOperations for MMU {    
-    fn read_byte(&self, address: u16) -> u8 {
+    pub fn set_ppu_access_blocking(&mut self, enabled: bool) {
+        self.ppu_access_blocking = enabled;
+        self.update_vram_pages();
+    }
+
+    pub fn is_ppu_access_blocking(&self) -> bool {
+        self.ppu_access_blocking
+    }
+
+    /// VRAM can't be accessed while the PPU is drawing
+    /// See: https://gbdev.io/pandocs/Accessing_VRAM_and_OAM.html
+    fn is_vram_blocked(&self) -> bool {
+        self.ppu_access_blocking && self.ppu_mode == PPU_MODE_DRAWING
+    }
+
+    /// OAM can't be accessed while the PPU is scanning OAM or drawing
+    fn is_oam_blocked(&self) -> bool {
+        self.ppu_access_blocking
+            && (self.ppu_mode == PPU_MODE_OAM_SCAN || self.ppu_mode == PPU_MODE_DRAWING)
+    }
+
+    fn is_rom_page_patched(&self, address: u16) -> bool {
+        self.rom_patches
+            .iter()
+            .any(|patch| patch.address as usize / PAGE_SIZE == address as usize / PAGE_SIZE)
+    }
+
+    /// The Game Genie sits between the cartridge & the console, so it sees every value the cartridge puts on the bus
+    fn patch_rom_read(&self, address: u16, value: u8) -> u8 {
+        self.rom_patches.iter().fold(value, |value, patch| patch.apply(address, value))
+    }
+
+    /// Map all pages, OAM, the unused region, IO registers & HRAM always go through the handlers
+    fn update_pages(&mut self) {
+        self.update_cartridge_pages();
+        self.update_vram_pages();
+
+        // Echo RAM mirrors the Working RAM
+        for address in (0xC000..0xFE00).step_by(PAGE_SIZE) {
+            let physical_address = (address - 0xC000) % 0x2000;
+            self.pages.map(address as u16, Page::Wram(physical_address));
+        }
+    }
+
+    /// Map the banks the MBC currently selected, has to be called after every bank switch
+    fn update_cartridge_pages(&mut self) {
+        for address in (0x0000..0x8000).step_by(PAGE_SIZE) {
+            let address = address as u16;
+
+            let page = if self.bank_00.is_boot_rom_mapped(address)
+                || !self.mbc.is_rom_mapped(address)
+                || !self.mbc.is_rom_page_linear(address)
+                || self.is_rom_page_patched(address)
+            {
+                Page::Handler
+            } else {
+                Page::Rom(self.mbc.get_rom_address(address) % self.rom.len())
+            };
+
+            // Writes to ROM go to the MBC registers
+            self.pages.map_read(address, page);
+            self.pages.map_write(address, Page::Handler);
+        }
+
+        for address in (0xA000..0xC000).step_by(PAGE_SIZE) {
+            let address = address as u16;
+
+            let page = match self.mbc.get_ram_address(address) {
+                Some(physical_address) if !self.cartridge_ram.is_empty() => {
+                    Page::CartridgeRam(physical_address % self.cartridge_ram.len())
+                }
+                _ => Page::Handler,
+            };
+
+            self.pages.map_read(address, page);
+            self.pages.map_write(address, if self.mbc.is_ram_writable() { page } else { Page::Handler });
+        }
+    }
+
+    /// VRAM goes through the handlers while the PPU blocks it
+    fn update_vram_pages(&mut self) {
+        for address in (0x8000..0xA000).step_by(PAGE_SIZE) {
+            let page = if self.is_vram_blocked() {
+                Page::Handler
+            } else {
+                Page::Vram(address - 0x8000)
+            };
+
+            self.pages.map(address as u16, page);
+        }
+    }
+
+    /// Read a byte the page table doesn't map directly
+    fn read_handler(&self, address: u16) -> u8 {
         match address {
-            0x0000..=0x3FFF => {
-                if self.mbc.is_advanced_banking_mode() {
-                    self.mbc.read_byte(address)
-                } else {
-                    self.bank_00.read_byte(address)
+            0x0000..=0x3FFF if self.bank_00.is_boot_rom_mapped(address) => self.bank_00.read_byte(address),
+            0x0000..=0x7FFF if !self.mbc.is_rom_mapped(address) => self.patch_rom_read(address, self.mbc.read_byte(address)),
+            0x0000..=0x7FFF => self.patch_rom_read(address, self.rom[self.mbc.get_rom_address(address) % self.rom.len()]),
+            0x8000..=0x9FFF if self.is_vram_blocked() => PPU_BLOCKED_VALUE,
+            0x8000..=0x9FFF => self.VRAM.read_byte(address),
+            0xA000..=0xBFFF => match self.mbc.get_ram_address(address) {
+                Some(physical_address) if !self.cartridge_ram.is_empty() => {
+                    self.cartridge_ram[physical_address % self.cartridge_ram.len()]
                 }
+                _ => self.mbc.read_byte(address),
             },
-            0x4000..=0x7FFF => self.mbc.read_byte(address),
-            0x8000..=0x9FFF => self.VRAM.read_byte(address),
-            0xA000..=0xBFFF => self.mbc.read_byte(address),
             0xC000..=0xDFFF => self.WRAM.read_byte(address),
             0xE000..=0xFDFF => self.WRAM.read_byte(address - 0x2000),
+            0xFE00..=0xFE9F if self.is_oam_blocked() => PPU_BLOCKED_VALUE,
             0xFE00..=0xFE9F => self.OAM.read_byte(address),
             0xFEA0..=0xFEFF => 0, // Unused
             0xFF00..=0xFF7F => self.IO.read_byte(address),
             0xFF80..=0xFFFE => self.HRAM.read_byte(address),
             0xFFFF => self.interrupt_enable,
-            _ => panic!("Address out of bounds: {:#X}", address)
         }
     }
 
-    fn write_byte(&mut self, address: u16, value: u8) {
+    /// Write a byte the page table doesn't map directly
+    fn write_handler(&mut self, address: u16, value: u8) {
         match address {
             // The MBC uses this for its own purposes
-            0x0000..=0x3FFF => self.mbc.write_byte(address, value),
-            0x4000..=0x7FFF => self.mbc.write_byte(address, value),
+            0x0000..=0x7FFF => {
+                self.mbc.write_byte(address, value);
+                self.update_cartridge_pages();
+            }
+            0x8000..=0x9FFF if self.is_vram_blocked() => {}
             0x8000..=0x9FFF => self.VRAM.write_byte(address, value),
-            0xA000..=0xBFFF => self.mbc.write_byte(address, value),
+            0xA000..=0xBFFF => match self.mbc.get_ram_address(address) {
+                Some(physical_address) if !self.cartridge_ram.is_empty() && self.mbc.is_ram_writable() => {
+                    let physical_address = physical_address % self.cartridge_ram.len();
+                    self.cartridge_ram[physical_address] = value;
+                }
+                _ => self.mbc.write_byte(address, value),
+            },
             0xC000..=0xDFFF => self.WRAM.write_byte(address, value),
             0xE000..=0xFDFF => self.WRAM.write_byte(address - 0x2000, value),
+            0xFE00..=0xFE9F if self.is_oam_blocked() => {}
             0xFE00..=0xFE9F => self.OAM.write_byte(address, value),
             0xFEA0..=0xFEFF => {} // Unused
             0xFF00..=0xFF7F => self.IO.write_byte(address, value),
             0xFF80..=0xFFFE => self.HRAM.write_byte(address, value),
             0xFFFF => self.interrupt_enable = value,
-            _ => panic!("Address out of bounds: {:#X}", address)
         }
     }
-}
\ No newline at end of file
+}
+
+/// Create the MBC for the cartridge type, some variants can only be told apart by the ROM
+/// Unlicensed cartridges often have a wrong header, so they are detected first
+fn create_mbc(mbc_info: u8, rom: &[u8]) -> Result<Box<dyn MemoryBankControllerOperations>, String> {
+    let mbc: Box<dyn MemoryBankControllerOperations> = match mbc_info {
+        _ if Sachen::is_sachen(rom) => {
+            log::info!("Sachen cartridge detected");
+            Box::new(Sachen::default())
+        }
+        0x00 if WisdomTree::is_wisdom_tree(rom) => {
+            log::info!("Wisdom Tree cartridge detected");
+            Box::new(WisdomTree::default())
+        }
+        0x00 | 0x08 | 0x09 => Box::new(NoMbc::default()),
+        0x01..=0x03 if Mbc1::is_multicart(rom) => {
+            log::info!("MBC1M multicart detected");
+            Box::new(Mbc1::new_multicart())
+        }
+        0x01..=0x03 => Box::new(Mbc1::default()),
+        0x05 | 0x06 => Box::new(Mbc2::default()),
+        0x0B..=0x0D => Box::new(Mmm01::default()),
+        0x0F..=0x13 => Box::new(Mbc3::default()),
+        0x19..=0x1E => Box::new(Mbc5::default()),
+        0x20 => Box::new(Mbc6::default()),
+        0x22 => Box::new(Mbc7::default()),
+        0xFC => Box::new(PocketCamera::default()),
+        0xFD => Box::new(Tama5::default()),
+        0xFE => Box::new(Huc3::default()),
+        0xFF => Box::new(Huc1::default()),
+        _ => return Err(format!("Unsupported MBC type: {}", mbc_type_to_string(mbc_info))),
+    };
+
+    Ok(mbc)
+}
+
+/// Read a byte of the cartridge header, which isn't at the usual place on some cartridges
+/// The address lines of Sachen cartridges are scrambled and MMM01 menus have a header at the end of the ROM
+fn read_header(rom: &[u8], address: usize) -> Option<u8> {
+    let address = if Sachen::is_sachen(rom) {
+        Sachen::unscramble(address as u16) as usize
+    } else {
+        Mmm01::get_menu_offset(rom).unwrap_or(0) + address
+    };
+
+    rom.get(address).copied()
+}
+
+/// Whether the header starting at `offset` has the Nintendo logo and a matching header checksum
+pub fn is_header_valid(rom: &[u8], offset: usize) -> bool {
+    let Some(header) = rom.get(offset..offset + HEADER_CHECKSUM_ADDRESS + 1) else {
+        return false;
+    };
+
+    header[LOGO_ADDRESS..LOGO_ADDRESS + NINTENDO_LOGO.len()] == NINTENDO_LOGO
+        && header[HEADER_CHECKSUM_ADDRESS] == get_header_checksum(header)
+}
+
+/// The checksum the boot ROM verifies, `header` starts at the beginning of the ROM or the MMM01 menu
+fn get_header_checksum(header: &[u8]) -> u8 {
+    header[HEADER_CHECKSUM_RANGE.clone()]
+        .iter()
+        .fold(0u8, |checksum, byte| checksum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/// Write the logo and the header checksum of the header starting at `offset`, so it passes `is_header_valid`
+#[cfg(test)]
+pub fn sign_header(rom: &mut [u8], offset: usize) {
+    rom[offset + LOGO_ADDRESS..][..NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+    rom[offset + HEADER_CHECKSUM_ADDRESS] = get_header_checksum(&rom[offset..]);
+}
+
+/// Size of the ROM based on the cartridge header
+/// See: https://gbdev.io/pandocs/The_Cartridge_Header.html#0148--rom-size
+fn get_rom_size(rom_size: u8) -> usize {
+    match rom_size {
+        0x00..=0x08 => (2 * ROM_BANK_SIZE) << rom_size,
+        _ => 0,
+    }
+}
+
+/// Size of the RAM based on the cartridge header
+/// See: https://gbdev.io/pandocs/The_Cartridge_Header.html#0149--ram-size
+fn get_ram_size(ram_size: u8) -> usize {
+    match ram_size {
+        // Unofficial, but used by some homebrew
+        0x01 => RAM_BANK_SIZE / 4,
+        0x02 => RAM_BANK_SIZE,
+        0x03 => 4 * RAM_BANK_SIZE,
+        0x04 => 16 * RAM_BANK_SIZE,
+        0x05 => 8 * RAM_BANK_SIZE,
+        _ => 0,
+    }
+}
+
+impl NonMbcOperations for MMU {
+    fn fill_from_slice(&mut self, data: &[u8]) {
+        // Get relevant information from the ROM for the mbc
+        let rom_size = match read_header(data, MBC_ROM_SIZE_ADDRESS) {
+            Some(rom_size) => rom_size,
+            None => panic!("No ROM size found in ROM")
+        };
+        let ram_size = match read_header(data, MBC_RAM_SIZE_ADDRESS) {
+            Some(ram_size) => ram_size,
+            None => panic!("No RAM size found in ROM")
+        };
+        let mbc_info = match read_header(data, MBC_INFO_ADDRESS) {
+            Some(mbc_info) => mbc_info,
+            None => panic!("No MBC info found in ROM")
+        };
+
+        log::info!("ROM Size: {:#X} RAM Size: {:#X} MBC Info: {:#X}", rom_size, ram_size, mbc_info);
+        log::info!("MBC Type: {}", mbc_type_to_string(mbc_info));
+
+        // Initialize the MBC
+        self.mbc.init(rom_size, mbc_info, ram_size);
+
+        // Missing data of truncated ROMs is read as 0xFF
+        // The ROM has to consist of whole pages for the page table
+        let total_rom_size = get_rom_size(rom_size).max(data.len()).next_multiple_of(2 * ROM_BANK_SIZE);
+        self.rom = data.to_vec();
+        self.rom.resize(total_rom_size, 0xFF);
+        self.cartridge_ram = vec![0; get_ram_size(ram_size)];
+
+        log::info!("Total ROM banks: {} Total Size: {}", total_rom_size / ROM_BANK_SIZE, total_rom_size);
+
+        self.update_pages();
+    }
+}
+
+impl MemoryOperations for MMU {    
+    fn read_byte(&self, address: u16) -> u8 {
+        let offset = get_page_offset(address);
+
+        match self.pages.get_read_page(address) {
+            Page::Rom(base) => self.rom[base + offset],
+            Page::CartridgeRam(base) => self.cartridge_ram[base + offset],
+            Page::Vram(base) => self.VRAM.read_physical(base + offset),
+            Page::Wram(base) => self.WRAM.read_physical(base + offset),
+            Page::Handler => self.read_handler(address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        let offset = get_page_offset(address);
+
+        match self.pages.get_write_page(address) {
+            Page::CartridgeRam(base) => self.cartridge_ram[base + offset] = value,
+            Page::Vram(base) => self.VRAM.write_physical(base + offset, value),
+            Page::Wram(base) => self.WRAM.write_physical(base + offset, value),
+            // ROM is never mapped for writes
+            Page::Rom(_) | Page::Handler => self.write_handler(address, value),
+        }
+    }
+}
+
+/// The ROM isn't part of the state, snapshots can only be loaded with the same cartridge
+impl SaveState for MMU {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.bank_00.is_boot_rom_enabled());
+        self.mbc.save_state(state);
+        state.write_bytes(&self.cartridge_ram);
+        self.VRAM.save_state(state);
+        self.WRAM.save_state(state);
+        self.OAM.save_state(state);
+        self.IO.save_state(state);
+        self.HRAM.save_state(state);
+        state.write(&self.interrupt_enable);
+        state.write(&self.ppu_mode);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.bank_00.set_boot_rom_enabled(state.read()?);
+        self.mbc.load_state(state)?;
+        state.read_bytes(&mut self.cartridge_ram)?;
+        self.VRAM.load_state(state)?;
+        self.WRAM.load_state(state)?;
+        self.OAM.load_state(state)?;
+        self.IO.load_state(state)?;
+        self.HRAM.load_state(state)?;
+        self.interrupt_enable = state.read()?;
+        self.ppu_mode = state.read()?;
+
+        // The banks and the PPU mode decide the mapping
+        self.update_pages();
+        Ok(())
+    }
+}
+
+#[test]
+pub fn ppu_access_blocking_test() {
+    let mut mmu = MMU::new_from_mbc_info(0x00).unwrap();
+    mmu.write_byte(0x8000, 0x12);
+    mmu.write_byte(0xFE00, 0x34);
+
+    // OAM Scan only blocks OAM
+    mmu.set_ppu_mode(PPU_MODE_OAM_SCAN);
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), PPU_BLOCKED_VALUE);
+    mmu.write_byte(0xFE00, 0x56);
+
+    // Drawing blocks both
+    mmu.set_ppu_mode(PPU_MODE_DRAWING);
+    assert_eq!(mmu.read_byte(0x8000), PPU_BLOCKED_VALUE);
+    mmu.write_byte(0x8000, 0x78);
+
+    // Debugging tools can still access everything
+    mmu.set_ppu_access_blocking(false);
+    assert_eq!(mmu.read_byte(0x8000), 0x12);
+    assert_eq!(mmu.read_byte(0xFE00), 0x34);
+}
+
+#[test]
+pub fn page_table_mapping_test() {
+    // 64 KiB MBC1 cartridge with 8 KiB RAM, every ROM bank starts with its number
+    let mut rom = vec![0; 4 * ROM_BANK_SIZE];
+    for bank in 0..4 {
+        rom[bank * ROM_BANK_SIZE] = bank as u8;
+    }
+    rom[MBC_INFO_ADDRESS] = 0x03;
+    rom[MBC_ROM_SIZE_ADDRESS] = 0x01;
+    rom[MBC_RAM_SIZE_ADDRESS] = 0x02;
+    let mut mmu = MMU::new_from_vec(rom).unwrap();
+
+    // The boot ROM is mapped over the first page only
+    assert_eq!(mmu.read_byte(0x0000), 0x31);
+    mmu.set_bootrom_enabled(false);
+    assert_eq!(mmu.read_byte(0x0000), 0x00);
+
+    // Switching the bank updates the pages
+    assert_eq!(mmu.read_byte(0x4000), 0x01);
+    mmu.write_byte(0x2000, 0x03);
+    assert_eq!(mmu.read_byte(0x4000), 0x03);
+    mmu.write_byte(0x2000, 0x00);
+    assert_eq!(mmu.read_byte(0x4000), 0x01);
+
+    // RAM is only mapped while it is enabled
+    mmu.write_byte(0xA000, 0x12);
+    assert_eq!(mmu.read_byte(0xA000), 0xFF);
+    mmu.write_byte(0x0000, 0x0A);
+    mmu.write_byte(0xA000, 0x12);
+    assert_eq!(mmu.read_byte(0xA000), 0x12);
+    mmu.write_byte(0x0000, 0x00);
+    assert_eq!(mmu.read_byte(0xA000), 0xFF);
+
+    // Echo RAM shares the pages of the Working RAM
+    mmu.write_byte(0xE123, 0x34);
+    assert_eq!(mmu.read_byte(0xC123), 0x34);
+}
+
+#[test]
+pub fn rom_ram_cartridge_test() {
+    // Without RAM the open bus is read
+    let mut mmu = MMU::new_from_vec(vec![0; 2 * ROM_BANK_SIZE]).unwrap();
+    mmu.write_byte(0xA000, 0x12);
+    assert_eq!(mmu.read_byte(0xA000), 0xFF);
+    assert!(!mmu.has_battery());
+
+    // ROM+RAM+BATTERY with 8 KiB, the RAM doesn't have to be enabled
+    let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+    rom[MBC_INFO_ADDRESS] = 0x09;
+    rom[MBC_RAM_SIZE_ADDRESS] = 0x02;
+    let mut mmu = MMU::new_from_vec(rom).unwrap();
+    mmu.write_byte(0xBFFF, 0x12);
+    assert_eq!(mmu.read_byte(0xBFFF), 0x12);
+    assert!(mmu.has_battery());
+
+    let mut save = mmu.get_cartridge_ram().to_vec();
+    assert_eq!(save.len(), RAM_BANK_SIZE);
+    save[0] = 0x34;
+    mmu.load_cartridge_ram(&save).unwrap();
+    assert_eq!(mmu.read_byte(0xA000), 0x34);
+    assert!(mmu.load_cartridge_ram(&save[..0x100]).is_err());
+}
+
+#[test]
+pub fn cartridge_header_test() {
+    // The MMM01 menu has its own header at the start of the last 32 KiB, the first one belongs to a game
+    let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+    rom[MBC_INFO_ADDRESS] = 0x01;
+    let menu = 6 * ROM_BANK_SIZE;
+    rom[menu + MBC_INFO_ADDRESS] = 0x0B;
+    rom[menu + MBC_ROM_SIZE_ADDRESS] = 0x02;
+    rom[menu + 0x0150] = 0x12;
+    rom[menu + TITLE_ADDRESS.start..][..8].copy_from_slice(b"MENU 2\0X");
+    sign_header(&mut rom, menu);
+    let mmu = MMU::new_from_vec(rom).unwrap();
+    assert_eq!(mmu.read_byte(0x0150), 0x12);
+    assert_eq!(mmu.get_title(), "MENU 2");
+
+    // Ordinary ROMs often have game data at the place of the menu's cartridge type
+    let mut rom = vec![0; 8 * ROM_BANK_SIZE];
+    rom[MBC_INFO_ADDRESS] = 0x01;
+    rom[MBC_ROM_SIZE_ADDRESS] = 0x02;
+    rom[TITLE_ADDRESS.start..][..4].copy_from_slice(b"GAME");
+    sign_header(&mut rom, 0);
+    rom[6 * ROM_BANK_SIZE + MBC_INFO_ADDRESS] = 0x0C;
+    let mmu = MMU::new_from_vec(rom).unwrap();
+    assert_eq!(mmu.get_title(), "GAME");
+    assert_eq!(mmu.mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+
+    // Reads from the scrambled page go through the MBC for every address
+    let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+    rom[0x0155] = 0x34;
+    let mut mmu = MMU::new_with_mbc(Box::new(Sachen::default()));
+    mmu.fill_from_slice(&rom);
+    assert_eq!(mmu.read_byte(0x0147), 0x34);
+    assert_eq!(mmu.read_byte(0x0155), 0x00);
+}
+
+#[test]
+pub fn read_only_cartridge_ram_test() {
+    // HuC3 with 32 KiB RAM, which is read only in mode 0x0
+    let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+    rom[MBC_INFO_ADDRESS] = 0xFE;
+    rom[MBC_RAM_SIZE_ADDRESS] = 0x03;
+    let mut mmu = MMU::new_from_vec(rom).unwrap();
+
+    mmu.write_byte(0x0000, 0x0A);
+    assert!(mmu.is_ram_mapped(0xA000));
+    mmu.write_byte(0xA000, 0x12);
+    mmu.write_byte(0x0000, 0x00);
+    assert!(!mmu.is_ram_mapped(0xA000));
+    mmu.write_byte(0xA000, 0x34);
+    assert_eq!(mmu.read_byte(0xA000), 0x12);
+}
+
+/// Measures the time of typical CPU memory accesses through the page table and through the range match
+/// it replaced, the best of several rounds is reported
+/// Run with: cargo test --release memory_access_benchmark -- --ignored --nocapture
+#[test]
+#[ignore]
+pub fn memory_access_benchmark() {
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    const ROUNDS: usize = 10;
+    const ITERATIONS: usize = 100;
+
+    // 64 KiB MBC1 cartridge
+    let mut rom = vec![0; 0x10000];
+    rom[MBC_INFO_ADDRESS] = 0x01;
+    rom[MBC_ROM_SIZE_ADDRESS] = 0x01;
+    let mut mmu = MMU::new_from_vec(rom).unwrap();
+    mmu.set_bootrom_enabled(false);
+
+    // ROM, VRAM, WRAM, Echo RAM & HRAM, the cartridge has no RAM
+    let sequential: Vec<u16> = (0x0000..0xA000).chain(0xC000..0xFE00).chain(0xFF80..0xFFFF).collect();
+    // The same addresses in a pseudo random order
+    let mut shuffled = sequential.clone();
+    let mut seed: u32 = 0x1234_5678;
+    for i in (1..shuffled.len()).rev() {
+        seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12345);
+        shuffled.swap(i, (seed >> 8) as usize % (i + 1));
+    }
+
+    /// Best time of all rounds for accessing every address `ITERATIONS` times
+    fn time(mmu: &mut MMU, addresses: &[u16], access: fn(&mut MMU, u16)) -> Duration {
+        (0..ROUNDS)
+            .map(|_| {
+                let start = Instant::now();
+                for _ in 0..ITERATIONS {
+                    for &address in addresses {
+                        access(mmu, black_box(address));
+                    }
+                }
+                start.elapsed()
+            })
+            .min()
+            .unwrap()
+    }
+
+    // The page table against the range match that dispatched every access before it, which is still
+    // used for the addresses the page table doesn't map
+    let page_table_read: fn(&mut MMU, u16) = |mmu, address| {
+        black_box(mmu.read_byte(address));
+    };
+    let page_table_write: fn(&mut MMU, u16) = |mmu, address| mmu.write_byte(address, black_box(address as u8));
+    let range_match_read: fn(&mut MMU, u16) = |mmu, address| {
+        black_box(mmu.read_handler(address));
+    };
+    let range_match_write: fn(&mut MMU, u16) = |mmu, address| mmu.write_handler(address, black_box(address as u8));
+
+    for (name, addresses) in [("Sequential", &sequential), ("Shuffled", &shuffled)] {
+        let writable: Vec<u16> = addresses.iter().copied().filter(|&address| address >= 0x8000).collect();
+
+        for (access, addresses, page_table, range_match) in [
+            ("read", addresses, page_table_read, range_match_read),
+            ("write", &writable, page_table_write, range_match_write),
+        ] {
+            let accesses = (ITERATIONS * addresses.len()) as f64;
+            let page_table = time(&mut mmu, addresses, page_table).as_nanos() as f64 / accesses;
+            let range_match = time(&mut mmu, addresses, range_match).as_nanos() as f64 / accesses;
+            println!(
+                "{} {}: {:.2} ns/access, range match {:.2} ns/access, {:.2}x speedup",
+                name,
+                access,
+                page_table,
+                range_match,
+                range_match / page_table
+            );
+        }
+    }
+}
@@ -1,54 +1,62 @@
-use crate::{mmu::MemoryOperations};
-
-use super::NonMbcOperations;
+use crate::mmu::MemoryOperations;
 
 const BOOTROM_SIZE: usize = 0x100;
+/// The CGB boot ROM is split, 0x100 to 0x1FF still maps the cartridge header
+const CGB_BOOTROM_SECOND_PART: usize = 0x200;
 
+/// The boot ROM, it is mapped over the start of ROM bank 00 until it is disabled
 pub struct Bank00 {
-    rom: [u8; 0x4000],
-    boot_rom: [u8; BOOTROM_SIZE],
-    pub boot_rom_enabled: bool,
+    boot_rom: Vec<u8>,
+    boot_rom_enabled: bool,
 }
 
 impl Default for Bank00 {
     fn default() -> Self {
         let rom_file = include_bytes!("../../bin/DMG_ROM.bin");
 
-        let mut boot_rom = [0; BOOTROM_SIZE];
-        for (i, byte) in rom_file.iter().enumerate() {
-            boot_rom[i] = *byte;
-        }
-
         Self {
-            rom: [0; 0x4000],
-            boot_rom,
+            boot_rom: rom_file[..BOOTROM_SIZE].to_vec(),
             boot_rom_enabled: true,
         }
     }
 }
 
 impl MemoryOperations for Bank00 {
+    /// Only valid for addresses the boot ROM is mapped to, see `is_boot_rom_mapped`
     fn read_byte(&self, address: u16) -> u8 {
-        if self.boot_rom_enabled && address < 0x100 {
-            self.boot_rom[address as usize]
-        } else {
-            self.rom[address as usize]
-        }
+        self.boot_rom[address as usize]
     }
 
     fn write_byte(&mut self, _address: u16, _value: u8) {
-        // Do nothing as this is a ROM only cartridge
+        // Do nothing as this is a ROM
     }
 }
 
-impl NonMbcOperations for Bank00 {
-    fn fill_from_slice(&mut self, data: &[u8]) {
-        self.rom.copy_from_slice(data);
+impl Bank00 {
+    pub fn is_boot_rom_enabled(&self) -> bool {
+        self.boot_rom_enabled
     }
-}
 
-impl Bank00 {
-    pub fn disable_boot_rom(&mut self) {
-        self.boot_rom_enabled = false;
+    /// The MMU has to update its page table afterwards, see `MMU::set_bootrom_enabled`
+    pub(super) fn set_boot_rom_enabled(&mut self, enabled: bool) {
+        self.boot_rom_enabled = enabled;
+    }
+
+    /// Replace the embedded DMG boot ROM, e.g. with the one of another model
+    pub fn load_boot_rom(&mut self, boot_rom: Vec<u8>) {
+        self.boot_rom = boot_rom;
+    }
+
+    /// CRC32 of the boot ROM, e.g. to check a movie has been recorded with the same one
+    pub fn get_checksum(&self) -> u32 {
+        crc32fast::hash(&self.boot_rom)
+    }
+
+    pub fn is_boot_rom_mapped(&self, address: u16) -> bool {
+        let address = address as usize;
+
+        self.boot_rom_enabled
+            && (address < BOOTROM_SIZE
+                || (CGB_BOOTROM_SECOND_PART..self.boot_rom.len()).contains(&address))
     }
 }
\ No newline at end of file
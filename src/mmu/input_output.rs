@@ -1,8 +1,109 @@
+use std::ops::RangeInclusive;
+
+use num_enum::TryFromPrimitive;
+
 use super::{MemoryOperations, NonMbcOperations};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Transfer enable & internal clock, only the internal clock works without a link partner
+const SERIAL_TRANSFER_START_MASK: u8 = 0b1000_0001;
+/// Bit 7 of LCDC turns the LCD on or off
+const LCD_ENABLE_MASK: u8 = 0b1000_0000;
+/// Bit 7 of NR52 turns the APU on or off
+const APU_ENABLE_MASK: u8 = 0b1000_0000;
+/// The wave pattern RAM isn't a register, it can always be read and written
+const WAVE_RAM: RangeInclusive<u16> = 0xFF30..=0xFF3F;
+/// Value read from addresses without a register
+const UNMAPPED_VALUE: u8 = 0xFF;
+
+/// The IO registers of the DMG
+/// See: https://gbdev.io/pandocs/Hardware_Reg_List.html
+#[derive(Debug, Clone, Copy, PartialEq, TryFromPrimitive)]
+#[repr(u16)]
+pub enum IoRegister {
+    P1 = 0xFF00,
+    Sb = 0xFF01,
+    Sc = 0xFF02,
+    Div = 0xFF04,
+    Tima = 0xFF05,
+    Tma = 0xFF06,
+    Tac = 0xFF07,
+    If = 0xFF0F,
+    Nr10 = 0xFF10,
+    Nr11 = 0xFF11,
+    Nr12 = 0xFF12,
+    Nr13 = 0xFF13,
+    Nr14 = 0xFF14,
+    Nr21 = 0xFF16,
+    Nr22 = 0xFF17,
+    Nr23 = 0xFF18,
+    Nr24 = 0xFF19,
+    Nr30 = 0xFF1A,
+    Nr31 = 0xFF1B,
+    Nr32 = 0xFF1C,
+    Nr33 = 0xFF1D,
+    Nr34 = 0xFF1E,
+    Nr41 = 0xFF20,
+    Nr42 = 0xFF21,
+    Nr43 = 0xFF22,
+    Nr44 = 0xFF23,
+    Nr50 = 0xFF24,
+    Nr51 = 0xFF25,
+    Nr52 = 0xFF26,
+    Lcdc = 0xFF40,
+    Stat = 0xFF41,
+    Scy = 0xFF42,
+    Scx = 0xFF43,
+    Ly = 0xFF44,
+    Lyc = 0xFF45,
+    Dma = 0xFF46,
+    Bgp = 0xFF47,
+    Obp0 = 0xFF48,
+    Obp1 = 0xFF49,
+    Wy = 0xFF4A,
+    Wx = 0xFF4B,
+}
 
-const DIV_REGISTER: u16 = 0xFF04;
-const JOYPAD_REGISTER: u16 = 0xFF00;
-const OAM_DMA_REGISTER: u16 = 0xFF46;
+impl IoRegister {
+    /// Unused and write-only bits, they always read as 1
+    /// See: https://gbdev.io/pandocs/Audio_Registers.html
+    fn get_read_mask(self) -> u8 {
+        match self {
+            IoRegister::P1 => 0b1100_0000,
+            IoRegister::Sc => 0b0111_1110,
+            IoRegister::Tac => 0b1111_1000,
+            IoRegister::If => 0b1110_0000,
+            IoRegister::Nr10 => 0b1000_0000,
+            IoRegister::Nr11 | IoRegister::Nr21 => 0b0011_1111,
+            IoRegister::Nr13 | IoRegister::Nr23 | IoRegister::Nr31 | IoRegister::Nr33 | IoRegister::Nr41 => 0xFF,
+            IoRegister::Nr14 | IoRegister::Nr24 | IoRegister::Nr34 | IoRegister::Nr44 => 0b1011_1111,
+            IoRegister::Nr30 => 0b0111_1111,
+            IoRegister::Nr32 => 0b1001_1111,
+            IoRegister::Nr52 => 0b0111_0000,
+            IoRegister::Stat => 0b1000_0000,
+            _ => 0x00,
+        }
+    }
+
+    /// Bits the CPU can write, the others are read-only or unused
+    fn get_write_mask(self) -> u8 {
+        match self {
+            // The lower nibble holds the state of the buttons
+            IoRegister::P1 => 0b0011_0000,
+            IoRegister::Ly => 0x00,
+            // The mode and LYC=LY bits are set by the PPU
+            IoRegister::Stat => 0b0111_1000,
+            // The lower nibble holds the state of the channels
+            IoRegister::Nr52 => APU_ENABLE_MASK,
+            _ => 0xFF,
+        }
+    }
+
+    /// The APU registers are cleared and can't be written while it is off
+    fn is_apu_register(self) -> bool {
+        (IoRegister::Nr10 as u16..=IoRegister::Nr51 as u16).contains(&(self as u16))
+    }
+}
 
 pub struct InputOutput {
     memory: Vec<u8>,
@@ -10,6 +111,11 @@ pub struct InputOutput {
     pub action_buttons: u8,
     pub direction_buttons: u8,
     pub dma_requested: bool,
+    pub div_reset_requested: bool,
+    pub timer_control_changed: bool,
+    pub serial_transfer_requested: bool,
+    pub lcd_toggled: bool,
+    pub stat_update_requested: bool,
 }
 
 impl InputOutput {
@@ -20,16 +126,33 @@ impl InputOutput {
             action_buttons: 0xF,
             direction_buttons: 0xF,
             dma_requested: false,
+            div_reset_requested: false,
+            timer_control_changed: false,
+            serial_transfer_requested: false,
+            lcd_toggled: false,
+            stat_update_requested: false,
         }
     }
 
     pub fn write_div_register(&mut self, value: u8) {
-        let addr = self.calc_physical_address(DIV_REGISTER);
+        let addr = self.calc_physical_address(IoRegister::Div as u16);
+        self.memory[addr] = value;
+    }
+
+    /// Write a register without any side effects, e.g. to set up the post boot state
+    pub fn set_register(&mut self, address: u16, value: u8) {
+        let addr = self.calc_physical_address(address);
+        self.memory[addr] = value;
+    }
+
+    /// Write STAT including its read-only bits, used by the PPU
+    pub fn write_stat_register(&mut self, value: u8) {
+        let addr = self.calc_physical_address(IoRegister::Stat as u16);
         self.memory[addr] = value;
     }
 
     pub fn write_controller_byte(&mut self, value: u8) {
-        let addr = self.calc_physical_address(JOYPAD_REGISTER);
+        let addr = self.calc_physical_address(IoRegister::P1 as u16);
         self.memory[addr] = value;
     }
 
@@ -37,6 +160,18 @@ impl InputOutput {
         (address - self.offset) as usize
     }
 
+    fn is_apu_enabled(&self) -> bool {
+        self.memory[self.calc_physical_address(IoRegister::Nr52 as u16)] & APU_ENABLE_MASK != 0
+    }
+
+    /// Turning the APU off clears all of its registers
+    /// See: https://gbdev.io/pandocs/Audio_Registers.html#ff26--nr52-audio-master-control
+    fn power_off_apu(&mut self) {
+        let start = self.calc_physical_address(IoRegister::Nr10 as u16);
+        let end = self.calc_physical_address(IoRegister::Nr51 as u16);
+        self.memory[start..=end].fill(0);
+    }
+
     pub fn is_dma_requested(&self) -> bool {
         self.dma_requested
     }
@@ -44,19 +179,89 @@ impl InputOutput {
     pub fn reset_dma_request(&mut self) {
         self.dma_requested = false;
     }
+
+    pub fn is_div_reset_requested(&self) -> bool {
+        self.div_reset_requested
+    }
+
+    pub fn reset_div_reset_request(&mut self) {
+        self.div_reset_requested = false;
+    }
+
+    pub fn is_timer_control_changed(&self) -> bool {
+        self.timer_control_changed
+    }
+
+    pub fn reset_timer_control_changed(&mut self) {
+        self.timer_control_changed = false;
+    }
+
+    pub fn is_serial_transfer_requested(&self) -> bool {
+        self.serial_transfer_requested
+    }
+
+    pub fn reset_serial_transfer_request(&mut self) {
+        self.serial_transfer_requested = false;
+    }
+
+    pub fn is_lcd_toggled(&self) -> bool {
+        self.lcd_toggled
+    }
+
+    pub fn reset_lcd_toggled(&mut self) {
+        self.lcd_toggled = false;
+    }
+
+    pub fn is_stat_update_requested(&self) -> bool {
+        self.stat_update_requested
+    }
+
+    pub fn reset_stat_update_request(&mut self) {
+        self.stat_update_requested = false;
+    }
 }
 
 impl MemoryOperations for InputOutput {
     fn read_byte(&self, address: u16) -> u8 {
-        let address = self.calc_physical_address(address);
-        self.memory[address as usize]
+        let value = self.memory[self.calc_physical_address(address)];
+
+        if WAVE_RAM.contains(&address) {
+            return value;
+        }
+
+        match IoRegister::try_from(address) {
+            Ok(register) => value | register.get_read_mask(),
+            Err(_) => UNMAPPED_VALUE,
+        }
     }
 
     fn write_byte(&mut self, address: u16, value: u8) {
         let physical_address = self.calc_physical_address(address);
-        match address {
-            DIV_REGISTER => self.memory[physical_address as usize] = 0,
-            JOYPAD_REGISTER => {
+
+        if WAVE_RAM.contains(&address) {
+            self.memory[physical_address] = value;
+            return;
+        }
+
+        // Writes to addresses without a register have no effect
+        let Ok(register) = IoRegister::try_from(address) else {
+            return;
+        };
+
+        if register.is_apu_register() && !self.is_apu_enabled() {
+            return;
+        }
+
+        let write_mask = register.get_write_mask();
+        let value = (value & write_mask) | (self.memory[physical_address] & !write_mask);
+
+        match register {
+            IoRegister::Div => {
+                // The internal counter of the timer is reset by the CPU
+                self.div_reset_requested = true;
+                self.memory[physical_address] = 0
+            },
+            IoRegister::P1 => {
                 let mut buttons: u8 = 0xF;
 
                 let value  = value & 0x30;
@@ -68,13 +273,47 @@ impl MemoryOperations for InputOutput {
                 }else if  value == 0x20 { //bit 4 = direction buttons
                     buttons = value | self.direction_buttons;
                 }
-                self.memory[physical_address as usize] =  buttons;
+                self.memory[physical_address] =  buttons;
             }
-            OAM_DMA_REGISTER => {
+            IoRegister::Stat => {
+                // Enabling a source can raise the STAT interrupt line
+                self.stat_update_requested = true;
+                self.memory[physical_address] = value
+            },
+            IoRegister::Lyc => {
+                self.stat_update_requested = true;
+                self.memory[physical_address] = value
+            },
+            IoRegister::Lcdc => {
+                if (self.memory[physical_address] ^ value) & LCD_ENABLE_MASK != 0 {
+                    self.lcd_toggled = true;
+                }
+                self.memory[physical_address] = value
+            },
+            IoRegister::Tac => {
+                self.timer_control_changed = true;
+                self.memory[physical_address] = value
+            },
+            IoRegister::Sc => {
+                if value & SERIAL_TRANSFER_START_MASK == SERIAL_TRANSFER_START_MASK {
+                    self.serial_transfer_requested = true;
+                }
+                self.memory[physical_address] = value
+            },
+            IoRegister::Dma => {
                 self.dma_requested = true;
-                self.memory[physical_address as usize] = value
+                self.memory[physical_address] = value
             },
-            _ => self.memory[physical_address as usize] = value,
+            IoRegister::Nr52 => {
+                if value & APU_ENABLE_MASK == 0 {
+                    // The channels are turned off as well
+                    self.power_off_apu();
+                    self.memory[physical_address] = 0
+                } else {
+                    self.memory[physical_address] = value
+                }
+            },
+            _ => self.memory[physical_address] = value,
         }
     }
 }
@@ -83,4 +322,63 @@ impl NonMbcOperations for InputOutput {
     fn fill_from_slice(&mut self, data: &[u8]) {
         self.memory.copy_from_slice(data);
     }
-}
\ No newline at end of file
+}
+
+/// The buttons are restored as well, they are updated again with the next poll of the inputs
+impl SaveState for InputOutput {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.memory);
+        state.write(&self.action_buttons);
+        state.write(&self.direction_buttons);
+        state.write(&self.dma_requested);
+        state.write(&self.div_reset_requested);
+        state.write(&self.timer_control_changed);
+        state.write(&self.serial_transfer_requested);
+        state.write(&self.lcd_toggled);
+        state.write(&self.stat_update_requested);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.memory)?;
+        self.action_buttons = state.read()?;
+        self.direction_buttons = state.read()?;
+        self.dma_requested = state.read()?;
+        self.div_reset_requested = state.read()?;
+        self.timer_control_changed = state.read()?;
+        self.serial_transfer_requested = state.read()?;
+        self.lcd_toggled = state.read()?;
+        self.stat_update_requested = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn io_register_test() {
+    let mut io = InputOutput::new(0x0080, 0xFF00);
+
+    // Unused bits read as 1, unmapped addresses as 0xFF
+    io.write_byte(IoRegister::If as u16, 0x00);
+    assert_eq!(io.read_byte(IoRegister::If as u16), 0b1110_0000);
+    io.write_byte(0xFF03, 0x12);
+    assert_eq!(io.read_byte(0xFF03), 0xFF);
+
+    // LY can't be written by the CPU
+    io.set_register(IoRegister::Ly as u16, 0x42);
+    io.write_byte(IoRegister::Ly as u16, 0x00);
+    assert_eq!(io.read_byte(IoRegister::Ly as u16), 0x42);
+
+    // The APU registers can only be written while it is on
+    io.write_byte(IoRegister::Nr50 as u16, 0x77);
+    assert_eq!(io.read_byte(IoRegister::Nr50 as u16), 0x00);
+    io.write_byte(IoRegister::Nr52 as u16, 0x80);
+    io.write_byte(IoRegister::Nr50 as u16, 0x77);
+    io.write_byte(0xFF30, 0xAB);
+    assert_eq!(io.read_byte(IoRegister::Nr50 as u16), 0x77);
+    assert_eq!(io.read_byte(IoRegister::Nr52 as u16), 0xF0);
+
+    // Turning it off clears them, but not the wave RAM
+    io.write_byte(IoRegister::Nr52 as u16, 0x00);
+    assert_eq!(io.read_byte(IoRegister::Nr50 as u16), 0x00);
+    assert_eq!(io.read_byte(IoRegister::Nr52 as u16), 0x70);
+    assert_eq!(io.read_byte(0xFF30), 0xAB);
+}
@@ -1,3 +1,15 @@
 pub mod no_mbc;
 pub mod mbc1;
-pub mod mbc5;
\ No newline at end of file
+pub mod mbc2;
+pub mod mbc3;
+pub mod mbc5;
+pub mod mbc7;
+pub mod pocket_camera;
+pub mod huc1;
+pub mod huc3;
+pub mod infrared;
+pub mod mbc6;
+pub mod mmm01;
+pub mod tama5;
+pub mod sachen;
+pub mod wisdom_tree;
@@ -0,0 +1,144 @@
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::infrared::{read_ir_register, Infrared, InfraredPort, IR_REGISTER_LIGHT};
+
+/// Writing this to 0x0000 to 0x1FFF maps the IR register instead of the RAM
+const IR_MODE: u8 = 0x0E;
+
+/// Hudson's HuC1, similar to MBC1 but with an IR transceiver instead of a RAM enable
+/// See: https://gbdev.io/pandocs/HuC1.html
+pub struct Huc1 {
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    rom_banks: usize,
+    ram_banks: usize,
+    /// Whether 0xA000 to 0xBFFF is the IR register instead of the RAM
+    ir_mode: bool,
+    infrared: Box<dyn Infrared>,
+}
+
+impl Default for Huc1 {
+    fn default() -> Self {
+        Self {
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            rom_banks: 2,
+            ram_banks: 0,
+            ir_mode: false,
+            infrared: Box::new(InfraredPort::default()),
+        }
+    }
+}
+
+impl MemoryOperations for Huc1 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0xA000..=0xBFFF if self.ir_mode => read_ir_register(self.infrared.as_ref()),
+            // There is no RAM, otherwise it would be mapped directly
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ir_mode = value & 0x0F == IR_MODE,
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x5FFF => self.switch_ram_bank(value),
+            0x6000..=0x7FFF => {}
+            0xA000..=0xBFFF if self.ir_mode => self.infrared.set_led(value & IR_REGISTER_LIGHT != 0),
+            0xA000..=0xBFFF => {}
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Huc1 {
+    fn init(&mut self, rom_size: u8, _cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                let bank = self.rom_bank_number as usize % self.rom_banks;
+                bank * ROM_BANK_SIZE + (address as usize - 0x4000)
+            }
+        }
+    }
+
+    /// The RAM is always accessible unless the IR register is mapped
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if self.ir_mode || self.ram_banks == 0 {
+            return None;
+        }
+
+        let bank = self.ram_bank_number as usize % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.infrared = infrared;
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_number = bank & 0x3F;
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.ram_bank_number = bank & 0x03;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        self.ir_mode = !enable;
+    }
+}
+
+impl SaveState for Huc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank_number);
+        state.write(&self.ram_bank_number);
+        state.write(&self.ir_mode);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank_number = state.read()?;
+        self.ram_bank_number = state.read()?;
+        self.ir_mode = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn huc1_test() {
+    let (port, mut remote) = InfraredPort::pair();
+    let mut mbc = Huc1::default();
+    mbc.init(0x04, 0xFF, 0x03);
+    mbc.set_infrared(Box::new(port));
+
+    mbc.write_byte(0x2000, 0x25);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x05 * ROM_BANK_SIZE);
+    mbc.write_byte(0x4000, 0x02);
+    assert_eq!(mbc.get_ram_address(0xA010), Some(2 * RAM_BANK_SIZE + 0x10));
+
+    // The IR register replaces the RAM
+    mbc.write_byte(0x0000, 0x0E);
+    assert_eq!(mbc.get_ram_address(0xA010), None);
+    assert_eq!(mbc.read_byte(0xA000), 0xC0);
+    remote.set_led(true);
+    assert_eq!(mbc.read_byte(0xB000), 0xC1);
+    mbc.write_byte(0xA000, 0x01);
+    assert!(remote.is_receiving());
+
+    mbc.write_byte(0x0000, 0x00);
+    assert_eq!(mbc.get_ram_address(0xA000), Some(2 * RAM_BANK_SIZE));
+}
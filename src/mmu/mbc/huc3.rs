@@ -0,0 +1,318 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+use super::infrared::{read_ir_register, Infrared, InfraredPort, IR_REGISTER_LIGHT};
+
+/// What 0xA000 to 0xBFFF is mapped to, selected by writing to 0x0000 to 0x1FFF
+/// See: https://gbdev.io/pandocs/HuC3.html#0000-1fff---ramrtcir-select-write-only
+const MODE_RAM_READ_ONLY: u8 = 0x0;
+const MODE_RAM: u8 = 0xA;
+const MODE_RTC_COMMAND: u8 = 0xB;
+const MODE_RTC_RESPONSE: u8 = 0xC;
+const MODE_RTC_SEMAPHORE: u8 = 0xD;
+const MODE_IR: u8 = 0xE;
+
+/// RTC commands in bits 4-6, the argument is in bits 0-3
+/// See: https://gbdev.io/pandocs/HuC3.html#rtc
+const COMMAND_READ: u8 = 0x1;
+const COMMAND_WRITE: u8 = 0x3;
+const COMMAND_ADDRESS_LOW: u8 = 0x4;
+const COMMAND_ADDRESS_HIGH: u8 = 0x5;
+const COMMAND_EXTENDED: u8 = 0x6;
+
+/// Arguments of the extended command
+const EXTENDED_LATCH_TIME: u8 = 0x0;
+const EXTENDED_SET_TIME: u8 = 0x1;
+const EXTENDED_STATUS: u8 = 0x2;
+const EXTENDED_PLAY_TONE: u8 = 0xE;
+
+/// The RTC memory consists of 256 nibbles
+const RTC_MEMORY_SIZE: usize = 0x100;
+/// Minute of the day & day counter, 3 nibbles each starting with the lowest one
+const RTC_MINUTES_ADDRESS: usize = 0x00;
+const RTC_DAYS_ADDRESS: usize = 0x03;
+/// The tone played by the tone generator
+const RTC_TONE_ADDRESS: usize = 0x26;
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+/// The day counter has 12 bits
+const DAYS_WRAP: i64 = 0x1000;
+
+/// Hudson's HuC3 with a clock, a tone generator and an IR transceiver
+/// See: https://gbdev.io/pandocs/HuC3.html
+pub struct Huc3 {
+    rom_bank_number: u8,
+    ram_bank_number: u8,
+    rom_banks: usize,
+    ram_banks: usize,
+    /// What 0xA000 to 0xBFFF is mapped to
+    mode: u8,
+    /// The command in the upper & the result in the lower nibble, read in RTC response mode
+    rtc_response: u8,
+    rtc_address: u8,
+    rtc_memory: [u8; RTC_MEMORY_SIZE],
+    /// Difference between the RTC and the host clock in minutes
+    rtc_offset: i64,
+    /// Replaces the host clock while set, see `set_clock_minutes`
+    clock_minutes: Option<i64>,
+    infrared: Box<dyn Infrared>,
+}
+
+impl Default for Huc3 {
+    fn default() -> Self {
+        Self {
+            rom_bank_number: 1,
+            ram_bank_number: 0,
+            rom_banks: 2,
+            ram_banks: 0,
+            mode: MODE_RAM_READ_ONLY,
+            rtc_response: 0,
+            rtc_address: 0,
+            rtc_memory: [0; RTC_MEMORY_SIZE],
+            rtc_offset: 0,
+            clock_minutes: None,
+            infrared: Box::new(InfraredPort::default()),
+        }
+    }
+}
+
+impl Huc3 {
+    /// Minutes since the unix epoch according to the host clock, unless the clock is pinned
+    fn get_host_minutes(&self) -> i64 {
+        self.clock_minutes.unwrap_or_else(|| {
+            let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs());
+            seconds as i64 / 60
+        })
+    }
+
+    /// Execute an RTC command written in RTC command mode
+    fn execute_command(&mut self, value: u8, host_minutes: i64) {
+        let command = (value >> 4) & 0x07;
+        let argument = value & 0x0F;
+
+        match command {
+            COMMAND_READ => {
+                let value = self.rtc_memory[self.rtc_address as usize];
+                self.rtc_response = (command << 4) | value;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            COMMAND_WRITE => {
+                self.rtc_memory[self.rtc_address as usize] = argument;
+                self.rtc_address = self.rtc_address.wrapping_add(1);
+            }
+            COMMAND_ADDRESS_LOW => self.rtc_address = (self.rtc_address & 0xF0) | argument,
+            COMMAND_ADDRESS_HIGH => self.rtc_address = (self.rtc_address & 0x0F) | (argument << 4),
+            COMMAND_EXTENDED => match argument {
+                EXTENDED_LATCH_TIME => self.latch_time(host_minutes),
+                EXTENDED_SET_TIME => self.set_time(host_minutes),
+                // The clock is always ready
+                EXTENDED_STATUS => self.rtc_response = (command << 4) | 0x1,
+                // There is no audio output for the tone generator
+                EXTENDED_PLAY_TONE => {
+                    log::info!("HuC3 tone generator plays tone {}", self.rtc_memory[RTC_TONE_ADDRESS]);
+                }
+                _ => log::warn!("Unknown HuC3 extended command: {:#03X}", argument),
+            },
+            _ => log::warn!("Unknown HuC3 RTC command: {:#04X}", value),
+        }
+    }
+
+    /// Copy the current time into the RTC memory
+    fn latch_time(&mut self, host_minutes: i64) {
+        let minutes = host_minutes + self.rtc_offset;
+        let days = minutes.div_euclid(MINUTES_PER_DAY).rem_euclid(DAYS_WRAP);
+        let minute_of_day = minutes.rem_euclid(MINUTES_PER_DAY);
+
+        self.write_rtc_value(RTC_MINUTES_ADDRESS, minute_of_day as u16);
+        self.write_rtc_value(RTC_DAYS_ADDRESS, days as u16);
+    }
+
+    /// Set the clock to the time in the RTC memory
+    fn set_time(&mut self, host_minutes: i64) {
+        let minute_of_day = self.read_rtc_value(RTC_MINUTES_ADDRESS) as i64;
+        let days = self.read_rtc_value(RTC_DAYS_ADDRESS) as i64;
+
+        self.rtc_offset = days * MINUTES_PER_DAY + minute_of_day - host_minutes;
+    }
+
+    /// Read a 12-bit value from 3 nibbles of RTC memory
+    fn read_rtc_value(&self, address: usize) -> u16 {
+        (0..3).fold(0, |value, nibble| value | ((self.rtc_memory[address + nibble] as u16 & 0x0F) << (nibble * 4)))
+    }
+
+    fn write_rtc_value(&mut self, address: usize, value: u16) {
+        for nibble in 0..3 {
+            self.rtc_memory[address + nibble] = ((value >> (nibble * 4)) & 0x0F) as u8;
+        }
+    }
+}
+
+impl MemoryOperations for Huc3 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0xA000..=0xBFFF => match self.mode {
+                MODE_RTC_RESPONSE => 0x80 | self.rtc_response,
+                // Commands are executed immediately, so the RTC is always ready
+                MODE_RTC_SEMAPHORE => 0x01,
+                MODE_IR => read_ir_register(self.infrared.as_ref()),
+                // There is no RAM, otherwise it would be mapped directly
+                _ => 0xFF,
+            },
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.mode = value & 0x0F,
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x5FFF => self.switch_ram_bank(value),
+            0x6000..=0x7FFF => {}
+            0xA000..=0xBFFF => match self.mode {
+                MODE_RTC_COMMAND => self.execute_command(value, self.get_host_minutes()),
+                MODE_IR => self.infrared.set_led(value & IR_REGISTER_LIGHT != 0),
+                _ => {}
+            },
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Huc3 {
+    fn init(&mut self, rom_size: u8, _cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                let bank = self.rom_bank_number as usize % self.rom_banks;
+                bank * ROM_BANK_SIZE + (address as usize - 0x4000)
+            }
+        }
+    }
+
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if !matches!(self.mode, MODE_RAM_READ_ONLY | MODE_RAM) || self.ram_banks == 0 {
+            return None;
+        }
+
+        let bank = self.ram_bank_number as usize % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
+    }
+
+    fn is_ram_writable(&self) -> bool {
+        self.mode == MODE_RAM
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn set_clock_minutes(&mut self, minutes: Option<i64>) {
+        self.clock_minutes = minutes;
+    }
+
+    fn set_infrared(&mut self, infrared: Box<dyn Infrared>) {
+        self.infrared = infrared;
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_number = bank & 0x7F;
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.ram_bank_number = bank & 0x03;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        self.mode = if enable { MODE_RAM } else { MODE_RAM_READ_ONLY };
+    }
+}
+
+impl SaveState for Huc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank_number);
+        state.write(&self.ram_bank_number);
+        state.write(&self.mode);
+        state.write(&self.rtc_response);
+        state.write(&self.rtc_address);
+        state.write(&self.rtc_memory);
+        state.write(&self.rtc_offset);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank_number = state.read()?;
+        self.ram_bank_number = state.read()?;
+        self.mode = state.read()?;
+        self.rtc_response = state.read()?;
+        self.rtc_address = state.read()?;
+        self.rtc_memory = state.read()?;
+        self.rtc_offset = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn huc3_rtc_test() {
+    let mut mbc = Huc3::default();
+    mbc.init(0x05, 0xFE, 0x03);
+
+    // Set the clock to day 0x123, 10:30 by writing the RTC memory from address 0
+    let minutes: u16 = 10 * 60 + 30;
+    mbc.execute_command(0x40, 0);
+    mbc.execute_command(0x50, 0);
+    for value in [minutes, 0x123] {
+        for nibble in 0..3 {
+            mbc.execute_command(0x30 | ((value >> (nibble * 4)) & 0x0F) as u8, 0);
+        }
+    }
+    mbc.execute_command(0x61, 1000);
+
+    // Two days and one minute later
+    mbc.execute_command(0x60, 1000 + 2 * MINUTES_PER_DAY + 1);
+    mbc.execute_command(0x40, 0);
+    let mut read = || {
+        (0..3).fold(0, |value, nibble| {
+            mbc.execute_command(0x10, 0);
+            value | ((mbc.rtc_response as u16 & 0x0F) << (nibble * 4))
+        })
+    };
+    assert_eq!(read(), minutes + 1);
+    assert_eq!(read(), 0x125);
+
+    // Responses are read in mode 0xC
+    mbc.execute_command(0x62, 0);
+    mbc.write_byte(0x0000, MODE_RTC_RESPONSE);
+    assert_eq!(mbc.read_byte(0xA000), 0xE1);
+    mbc.write_byte(0x0000, MODE_RTC_SEMAPHORE);
+    assert_eq!(mbc.read_byte(0xA000), 0x01);
+}
+
+#[test]
+pub fn huc3_mode_test() {
+    let (port, mut remote) = InfraredPort::pair();
+    let mut mbc = Huc3::default();
+    mbc.init(0x05, 0xFE, 0x03);
+    mbc.set_infrared(Box::new(port));
+
+    // The RAM is read only in mode 0
+    mbc.write_byte(0x4000, 0x01);
+    assert_eq!(mbc.get_ram_address(0xA000), Some(RAM_BANK_SIZE));
+    assert!(!mbc.is_ram_writable());
+    mbc.write_byte(0x0000, MODE_RAM);
+    assert!(mbc.is_ram_writable());
+
+    mbc.write_byte(0x0000, MODE_IR);
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+    remote.set_led(true);
+    assert_eq!(mbc.read_byte(0xA000), 0xC1);
+    mbc.write_byte(0xA000, 0x01);
+    assert!(remote.is_receiving());
+}
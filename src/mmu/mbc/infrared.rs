@@ -0,0 +1,78 @@
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+
+/// Value of the IR register of HuC1 & HuC3 cartridges, the upper bits are always set
+pub const IR_REGISTER_UNUSED: u8 = 0b1100_0000;
+/// Bit 0 of the IR register is the LED when written and the receiver when read
+pub const IR_REGISTER_LIGHT: u8 = 0b0000_0001;
+
+/// An infrared LED & receiver, e.g. of a HuC1 or HuC3 cartridge
+pub trait Infrared {
+    /// Turn the own LED on or off
+    fn set_led(&mut self, on: bool);
+
+    /// Whether light from another device is received
+    fn is_receiving(&self) -> bool;
+}
+
+/// An IR transceiver that can be connected to another one, e.g. of another emulator instance
+/// Without a connection it never receives any light
+#[derive(Default)]
+pub struct InfraredPort {
+    led: Arc<AtomicBool>,
+    remote_led: Option<Arc<AtomicBool>>,
+}
+
+impl InfraredPort {
+    /// Create two ports facing each other, each one receives the light of the other's LED
+    pub fn pair() -> (Self, Self) {
+        let first = Arc::new(AtomicBool::new(false));
+        let second = Arc::new(AtomicBool::new(false));
+
+        (
+            InfraredPort { led: first.clone(), remote_led: Some(second.clone()) },
+            InfraredPort { led: second, remote_led: Some(first) },
+        )
+    }
+}
+
+impl Infrared for InfraredPort {
+    fn set_led(&mut self, on: bool) {
+        self.led.store(on, Ordering::Relaxed);
+    }
+
+    fn is_receiving(&self) -> bool {
+        self.remote_led.as_ref().is_some_and(|led| led.load(Ordering::Relaxed))
+    }
+}
+
+/// Read the IR register of a cartridge
+pub fn read_ir_register(infrared: &dyn Infrared) -> u8 {
+    if infrared.is_receiving() {
+        IR_REGISTER_UNUSED | IR_REGISTER_LIGHT
+    } else {
+        IR_REGISTER_UNUSED
+    }
+}
+
+#[test]
+pub fn infrared_port_test() {
+    let (mut first, mut second) = InfraredPort::pair();
+    assert!(!first.is_receiving() && !second.is_receiving());
+
+    first.set_led(true);
+    assert!(second.is_receiving());
+    assert!(!first.is_receiving());
+    assert_eq!(read_ir_register(&second), 0xC1);
+
+    first.set_led(false);
+    second.set_led(true);
+    assert!(first.is_receiving() && !second.is_receiving());
+
+    // An unconnected port is always dark
+    let mut port = InfraredPort::default();
+    port.set_led(true);
+    assert_eq!(read_ir_register(&port), 0xC0);
+}
@@ -1,64 +1,93 @@
-use crate::mmu::{MemoryBankControllerOperations, MemoryOperations};
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
+/// Cartridge type of MBC1 carts with a battery buffering the RAM
+const MBC1_RAM_BATTERY: u8 = 0x03;
+
+/// The logo in the header of every game, MBC1M multicarts have one in each game's bank 0
+const LOGO_START: usize = 0x0104;
+const LOGO_END: usize = 0x0134;
+/// Size of MBC1M multicarts, the second game starts at bank 0x10
+const MULTICART_ROM_SIZE: usize = 0x10_0000;
+const MULTICART_SECOND_GAME_BANK: usize = 0x10;
+
+/// See: https://gbdev.io/pandocs/MBC1.html
 pub struct Mbc1 {
-    rom: Vec<[u8; 0x4000]>,
-    ram: Vec<[u8; 0x2000]>,
-    rom_bank_number: u8,
-    ram_bank_number: u8,
+    /// BANK1, the lower 5 bits of the ROM bank number, can't be 0
+    bank1: u8,
+    /// BANK2, the upper 2 bits of the ROM bank number or the RAM bank number
+    bank2: u8,
     ram_enabled: bool,
-    cartridge_type: u8,
-    rom_size: u8,
-    ram_size: u8,
+    /// MODE, whether BANK2 also applies to 0x0000 to 0x3FFF and the RAM
     advanced_banking_mode: bool,
+    /// Amount of ROM banks, always a power of 2
+    rom_banks: usize,
+    /// Amount of RAM banks, 0 if there is no RAM
+    ram_banks: usize,
+    has_battery: bool,
+    /// MBC1M multicarts only wire up 4 bits of BANK1, so BANK2 selects one of four games
+    /// See: https://gbdev.io/pandocs/MBC1.html#mbc1m-1-mib-multi-game-compilation-carts
+    multicart: bool,
 }
 
 impl Default for Mbc1 {
     fn default() -> Self {
-        let rom = [0; 0x4000];
-        let ram = [0; 0x2000];
-
         Self {
-            rom: vec![rom],
-            ram: vec![ram],
-            rom_bank_number: 1,
-            ram_bank_number: 0,
+            bank1: 1,
+            bank2: 0,
             ram_enabled: false,
-            cartridge_type: 0,
-            rom_size: 0,
-            ram_size: 0,
             advanced_banking_mode: false,
+            rom_banks: 2,
+            ram_banks: 0,
+            has_battery: false,
+            multicart: false,
         }
     }
 }
 
-impl MemoryOperations for Mbc1 {
-    fn read_byte(&self, address: u16) -> u8 {
-        match address {
-            0x0000..=0x3FFF => {
-                let current_rambank = match self.rom.get(0) {
-                    Some(bank) => bank,
-                    None => panic!("ROM bank 0 not found"),
-                };
+impl Mbc1 {
+    pub fn new_multicart() -> Self {
+        Self {
+            multicart: true,
+            ..Self::default()
+        }
+    }
 
-                current_rambank[address as usize]
-            }
-            0x4000..=0x7FFF => {
-                let current_rambank = match self.rom.get(self.rom_bank_number as usize) {
-                    Some(bank) => bank,
-                    None => panic!("ROM bank {} not found", self.rom_bank_number),
-                };
+    /// MBC1M carts use the header of a regular 1 MiB MBC1 cart,
+    /// but the first bank of the second game contains a logo as well
+    pub fn is_multicart(rom: &[u8]) -> bool {
+        let second_game = MULTICART_SECOND_GAME_BANK * ROM_BANK_SIZE;
 
-                current_rambank[self.calc_physical_rom_address(address)]
-            }
-            0xA000..=0xBFFF => {
-                let current_rambank = match self.ram.get(self.ram_bank_number as usize - 1) {
-                    Some(bank) => bank,
-                    None => panic!("RAM bank {} not found for addr {:#06X} type {} ram banks existing: {}", self.ram_bank_number, address, self.cartridge_type,
-                        self.ram.len()),
-                };
+        rom.len() == MULTICART_ROM_SIZE
+            && rom[LOGO_START..LOGO_END] == rom[second_game + LOGO_START..second_game + LOGO_END]
+    }
 
-                current_rambank[self.calc_physical_ram_address(address)]
+    /// Amount of bits BANK2 is shifted by in the bank number
+    fn get_bank2_shift(&self) -> u8 {
+        if self.multicart { 4 } else { 5 }
+    }
+
+    /// Get the ROM bank mapped to 0x0000 to 0x3FFF (`high` false) or 0x4000 to 0x7FFF (`high` true)
+    fn get_rom_bank(&self, high: bool) -> usize {
+        let bank = match (high, self.advanced_banking_mode) {
+            (false, false) => 0,
+            (false, true) => self.bank2 << self.get_bank2_shift(),
+            (true, _) => {
+                let bank1_mask = (1 << self.get_bank2_shift()) - 1;
+                (self.bank2 << self.get_bank2_shift()) | (self.bank1 & bank1_mask)
             }
+        };
+
+        // Unused upper bits of the bank number aren't connected
+        bank as usize & (self.rom_banks - 1)
+    }
+}
+
+impl MemoryOperations for Mbc1 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            // The RAM is disabled or missing, otherwise it would be mapped directly
+            0xA000..=0xBFFF => 0xFF,
             _ => panic!("Invalid address: {:#06X}", address),
         }
     }
@@ -67,45 +96,25 @@ impl MemoryOperations for Mbc1 {
         match address {
             // https://gbdev.io/pandocs/MBC1.html#00001fff--ram-enable-write-only
             0x0000..=0x1FFF => {
-                self.ram_enabled = value & 0x0F == 0x0A; // Check if the lower 4 bits are 0x0A
+                self.enable_ram(value & 0x0F == 0x0A); // Check if the lower 4 bits are 0x0A
             }
             // https://gbdev.io/pandocs/MBC1.html#20003fff--rom-bank-number-write-only
             0x2000..=0x3FFF => {
-                let bank = value & 0b0001_1111; // Discard the upper 3 bits
-                let bank = if bank == 0 { 1 } else { bank }; // Bank 0 is not accessible
-                self.switch_rom_bank(bank);
+                // Bank 0 can't be selected, even if the upper bits aren't connected
+                let bank = value & 0b0001_1111;
+                self.bank1 = if bank == 0 { 1 } else { bank };
             }
             // https://gbdev.io/pandocs/MBC1.html#40005fff--ram-bank-number--or--upper-bits-of-rom-bank-number-write-only
             0x4000..=0x5FFF => {
-                let bank = value & 0b0000_0011; // Discard the upper 5 bits (Only 2 bits are used)
-                if self.ram_size >= 3 {
-                    // If the RAM size is 32KB
-                    self.switch_ram_bank(bank);
-                } else if self.rom_size >= 5 {
-                    // If the ROM size is 1MB or more
-                    let rom_bank_number = self.rom_bank_number & 0b0001_1111; // Discard the upper 3 bits
-                    self.switch_rom_bank((bank << 5) | rom_bank_number); // Combine the upper 2 bits with the lower 5 bits
-                }
-            }
-            0xA000..=0xBFFF => {
-                log::debug!("Writing to RAM address: {:#06X}", address);
-                let address = self.calc_physical_ram_address(address);
-
-                if self.ram_enabled {
-                    let current_rambank = match self.ram.get_mut(self.ram_bank_number as usize) {
-                        Some(bank) => bank,
-                        None => panic!("RAM bank {} not found", self.rom_bank_number),
-                    };
-                    current_rambank[address] = value;
-                }
+                self.switch_ram_bank(value & 0b0000_0011);
             }
+            // https://gbdev.io/pandocs/MBC1.html#60007fff--banking-mode-select-write-only
             0x6000..=0x7FFF => {
-                let bit = value & 0x01;
-                log::debug!("Writing to ROM/RAM mode select register: {:#06X}", bit);
-                if bit == 0 {
-                    self.ram_bank_number = 0;
-                }
-                self.advanced_banking_mode = bit == 1;
+                log::debug!("Writing to ROM/RAM mode select register: {:#06X}", value & 0x01);
+                self.advanced_banking_mode = value & 0x01 == 1;
+            }
+            0xA000..=0xBFFF => {
+                // The RAM is disabled or missing, otherwise it would be mapped directly
             }
             _ => panic!("Invalid address: {:#06X}", address),
         }
@@ -114,64 +123,120 @@ impl MemoryOperations for Mbc1 {
 
 impl MemoryBankControllerOperations for Mbc1 {
     fn init(&mut self, rom_size: u8, cartridge_type: u8, ram_size: u8) {
-        self.rom_size = rom_size;
-        self.cartridge_type = cartridge_type;
-        self.ram_size = ram_size;
-
-        // Create empty ROM and RAM banks
-        for _ in 0..2_usize.pow(rom_size as u32 + 1) {
-            self.rom.push([0; 0x4000]);
-        }
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+        self.has_battery = cartridge_type == MBC1_RAM_BATTERY;
+    }
 
-        for _ in 0..2_usize.pow(ram_size as u32) {
-            self.ram.push([0; 0x2000]);
-        }
+    fn get_rom_address(&self, address: u16) -> usize {
+        let bank = self.get_rom_bank(address >= 0x4000);
+        bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
     }
 
-    fn fill_rom_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x4000]) {
-        // Make sure all banks before the current bank are filled
-        for _ in self.rom.len()..bank as usize {
-            self.rom.push([0; 0x4000]);
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return None;
         }
 
-        // Check whether the bank already exists
-        if let Some(rom) = self.rom.get_mut(bank as usize) {
-            rom.copy_from_slice(data);
-        } else {
-            self.rom.push(*data);
-        }
+        // Without advanced banking mode only RAM bank 0 is accessible
+        let bank = if self.advanced_banking_mode { self.bank2 as usize } else { 0 };
+        let bank = bank % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
     }
 
-    fn fill_ram_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x2000]) {
-        // Make sure all banks before the current bank are filled
-        for _ in self.ram.len()..bank as usize {
-            self.ram.push([0; 0x2000]);
-        }
-
-        // Check whether the bank already exists
-        if let Some(ram) = self.ram.get_mut(bank as usize) {
-            ram.copy_from_slice(data);
-        } else {
-            self.ram.push(*data);
-        }
+    fn has_battery(&self) -> bool {
+        self.has_battery
     }
 
     fn switch_rom_bank(&mut self, bank: u8) {
         log::info!("Switching ROM bank to {}", bank);
-        self.rom_bank_number = bank;
+        let bank1 = bank & 0b0001_1111;
+        self.bank1 = if bank1 == 0 { 1 } else { bank1 };
+        self.bank2 = (bank >> 5) & 0b11;
     }
 
     fn switch_ram_bank(&mut self, bank: u8) {
         log::info!("Switching RAM bank to {}", bank);
-        self.ram_bank_number = bank;
+        self.bank2 = bank & 0b11;
     }
 
     fn enable_ram(&mut self, enable: bool) {
-        log::info!("RAM enabled: {}", enable);
+        log::debug!("RAM enabled: {}", enable);
         self.ram_enabled = enable;
     }
+}
+
+impl SaveState for Mbc1 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.bank1);
+        state.write(&self.bank2);
+        state.write(&self.ram_enabled);
+        state.write(&self.advanced_banking_mode);
+    }
 
-    fn is_advanced_banking_mode(&self) -> bool {
-        self.advanced_banking_mode
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.bank1 = state.read()?;
+        self.bank2 = state.read()?;
+        self.ram_enabled = state.read()?;
+        self.advanced_banking_mode = state.read()?;
+        Ok(())
     }
 }
+
+#[test]
+pub fn mbc1_banking_test() {
+    // 1 MiB ROM & 32 KiB RAM
+    let mut mbc = Mbc1::default();
+    mbc.init(0x05, 0x03, 0x03);
+
+    // Bank 0 is translated to 1, but only based on the lower 5 bits
+    mbc.write_byte(0x2000, 0x00);
+    assert_eq!(mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x20);
+    assert_eq!(mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+
+    // BANK2 selects the upper bits, 0x0000 to 0x3FFF only uses them in mode 1
+    mbc.write_byte(0x2000, 0x02);
+    mbc.write_byte(0x4000, 0x01);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x22 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x0000), 0);
+    mbc.write_byte(0x6000, 0x01);
+    assert_eq!(mbc.get_rom_address(0x0000), 0x20 * ROM_BANK_SIZE);
+
+    // The RAM is only banked in mode 1
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+    mbc.write_byte(0x0000, 0x0A);
+    assert_eq!(mbc.get_ram_address(0xA123), Some(RAM_BANK_SIZE + 0x123));
+    mbc.write_byte(0x6000, 0x00);
+    assert_eq!(mbc.get_ram_address(0xA123), Some(0x123));
+
+    // Bank numbers are masked to the ROM size of 256 KiB
+    let mut mbc = Mbc1::default();
+    mbc.init(0x03, 0x01, 0x00);
+    mbc.write_byte(0x2000, 0x12);
+    mbc.write_byte(0x4000, 0x03);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x02 * ROM_BANK_SIZE);
+    mbc.write_byte(0x0000, 0x0A);
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+}
+
+#[test]
+pub fn mbc1_multicart_test() {
+    let mut rom = vec![0; MULTICART_ROM_SIZE];
+    rom[LOGO_START..LOGO_END].fill(0xCE);
+    assert!(!Mbc1::is_multicart(&rom));
+    let second_game = MULTICART_SECOND_GAME_BANK * ROM_BANK_SIZE;
+    rom[second_game + LOGO_START..second_game + LOGO_END].fill(0xCE);
+    assert!(Mbc1::is_multicart(&rom));
+
+    // BANK2 is shifted by 4 and the 5th bit of BANK1 isn't connected
+    let mut mbc = Mbc1::new_multicart();
+    mbc.init(0x05, 0x01, 0x00);
+    mbc.write_byte(0x2000, 0x12);
+    mbc.write_byte(0x4000, 0x01);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x12 * ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x10);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x10 * ROM_BANK_SIZE);
+    mbc.write_byte(0x6000, 0x01);
+    assert_eq!(mbc.get_rom_address(0x0000), 0x10 * ROM_BANK_SIZE);
+}
@@ -0,0 +1,138 @@
+use crate::mmu::{get_rom_size, MemoryBankControllerOperations, MemoryOperations, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Cartridge type of MBC2 carts with a battery buffering the RAM
+const MBC2_BATTERY: u8 = 0x06;
+/// The built-in RAM has 512 half-bytes, mirrored across 0xA000 to 0xBFFF
+const RAM_SIZE: usize = 0x200;
+/// Address bit 8 selects whether 0x0000 to 0x3FFF controls the RAM or the ROM bank
+const ROM_BANK_SELECT_BIT: u16 = 0x0100;
+
+/// See: https://gbdev.io/pandocs/MBC2.html
+pub struct Mbc2 {
+    /// The 4 bit ROM bank number mapped to 0x4000 to 0x7FFF, can't be 0
+    rom_bank: u8,
+    ram_enabled: bool,
+    /// Amount of ROM banks, always a power of 2
+    rom_banks: usize,
+    has_battery: bool,
+    /// Only the lower 4 bits of each byte are stored
+    ram: [u8; RAM_SIZE],
+}
+
+impl Default for Mbc2 {
+    fn default() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_enabled: false,
+            rom_banks: 2,
+            has_battery: false,
+            ram: [0; RAM_SIZE],
+        }
+    }
+}
+
+impl MemoryOperations for Mbc2 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            // The upper 4 bits aren't connected and read as 1
+            0xA000..=0xBFFF if self.ram_enabled => 0xF0 | self.ram[address as usize % RAM_SIZE],
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            // https://gbdev.io/pandocs/MBC2.html#00003fff--ram-enable-rom-bank-number-write-only
+            0x0000..=0x3FFF if address & ROM_BANK_SELECT_BIT == 0 => self.enable_ram(value & 0x0F == 0x0A),
+            0x0000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x7FFF => {}
+            0xA000..=0xBFFF if self.ram_enabled => self.ram[address as usize % RAM_SIZE] = value & 0x0F,
+            0xA000..=0xBFFF => {}
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mbc2 {
+    fn init(&mut self, rom_size: u8, cartridge_type: u8, _ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.has_battery = cartridge_type == MBC2_BATTERY;
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        let bank = if address >= 0x4000 { self.rom_bank as usize } else { 0 };
+        // Unused upper bits of the bank number aren't connected
+        (bank & (self.rom_banks - 1)) * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        // The RAM is built into the MBC, the header reports no cartridge RAM
+        None
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        Some(&self.ram)
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram)
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        let bank = bank & 0x0F;
+        self.rom_bank = if bank == 0 { 1 } else { bank };
+    }
+
+    fn switch_ram_bank(&mut self, _bank: u8) {
+        // There are no RAM banks
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM enabled: {}", enable);
+        self.ram_enabled = enable;
+    }
+}
+
+impl SaveState for Mbc2 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank);
+        state.write(&self.ram_enabled);
+        state.write(&self.ram);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank = state.read()?;
+        self.ram_enabled = state.read()?;
+        self.ram = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn mbc2_test() {
+    // 256 KiB ROM with battery
+    let mut mbc = Mbc2::default();
+    mbc.init(0x03, 0x06, 0x00);
+    assert!(mbc.has_battery());
+    assert_eq!(mbc.read_byte(0xA000), 0xFF);
+
+    // Address bit 8 decides which register is written, bank 0 selects bank 1
+    mbc.write_byte(0x2100, 0x0F);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x0F * ROM_BANK_SIZE);
+    mbc.write_byte(0x0100, 0x00);
+    assert_eq!(mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x0A);
+    assert_eq!(mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+
+    // Half-bytes mirrored every 512 bytes, the RAM has been enabled by the last write
+    mbc.write_byte(0xA001, 0x12);
+    assert_eq!(mbc.read_byte(0xA201), 0xF2);
+    assert_eq!(mbc.get_internal_ram().unwrap()[1], 0x02);
+}
@@ -0,0 +1,330 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Cartridge types of MBC3 carts with a battery buffering the RAM & clock
+const MBC3_BATTERY_TYPES: [u8; 3] = [0x0F, 0x10, 0x13];
+/// Cartridge types with a real time clock
+const MBC3_TIMER_TYPES: [u8; 2] = [0x0F, 0x10];
+
+/// Values written to 0x4000 to 0x5FFF selecting an RTC register instead of a RAM bank
+/// See: https://gbdev.io/pandocs/MBC3.html#the-clock-counter-registers
+const RTC_SECONDS: u8 = 0x08;
+const RTC_MINUTES: u8 = 0x09;
+const RTC_HOURS: u8 = 0x0A;
+const RTC_DAY_LOW: u8 = 0x0B;
+const RTC_DAY_HIGH: u8 = 0x0C;
+const RTC_REGISTER_COUNT: usize = 5;
+
+/// Bits of the upper day register, bit 0 is the 9th bit of the day counter
+const DAY_HIGH_HALT: u8 = 0b0100_0000;
+const DAY_HIGH_CARRY: u8 = 0b1000_0000;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+/// The day counter has 9 bits, the carry is set when it overflows
+const DAYS_WRAP: i64 = 0x200;
+
+/// See: https://gbdev.io/pandocs/MBC3.html
+pub struct Mbc3 {
+    /// The 7 bit ROM bank number mapped to 0x4000 to 0x7FFF, can't be 0
+    rom_bank: u8,
+    /// A RAM bank from 0x00 to 0x07 or an RTC register from 0x08 to 0x0C
+    ram_bank: u8,
+    /// Enables both the RAM and the RTC registers
+    ram_enabled: bool,
+    /// Amount of ROM banks, always a power of 2
+    rom_banks: usize,
+    /// Amount of RAM banks, 0 if there is no RAM
+    ram_banks: usize,
+    has_battery: bool,
+    has_timer: bool,
+    /// Difference between the clock counter and the host clock in seconds
+    rtc_offset: i64,
+    /// The clock counter while it is halted
+    rtc_halted: Option<i64>,
+    /// Set when the day counter overflows until the game clears it
+    rtc_carry: bool,
+    /// The registers read by the game, only updated by latching the clock
+    rtc_latched: [u8; RTC_REGISTER_COUNT],
+    /// The last value written to 0x6000 to 0x7FFF, writing 0x00 and then 0x01 latches the clock
+    latch_value: u8,
+    /// Replaces the host clock while set, see `set_clock_minutes`
+    clock_minutes: Option<i64>,
+}
+
+impl Default for Mbc3 {
+    fn default() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rom_banks: 2,
+            ram_banks: 0,
+            has_battery: false,
+            has_timer: false,
+            rtc_offset: 0,
+            rtc_halted: None,
+            rtc_carry: false,
+            rtc_latched: [0; RTC_REGISTER_COUNT],
+            latch_value: 0xFF,
+            clock_minutes: None,
+        }
+    }
+}
+
+impl Mbc3 {
+    /// Seconds since the unix epoch according to the host clock, unless the clock is pinned
+    fn get_host_seconds(&self) -> i64 {
+        match self.clock_minutes {
+            Some(minutes) => minutes * 60,
+            None => SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs() as i64),
+        }
+    }
+
+    /// The clock counter in seconds, including days beyond the 9 bit day counter
+    fn get_rtc_seconds(&self, host_seconds: i64) -> i64 {
+        self.rtc_halted.unwrap_or(host_seconds + self.rtc_offset)
+    }
+
+    fn set_rtc_seconds(&mut self, seconds: i64, host_seconds: i64) {
+        match self.rtc_halted {
+            Some(_) => self.rtc_halted = Some(seconds),
+            None => self.rtc_offset = seconds - host_seconds,
+        }
+    }
+
+    /// Copy the current time into the registers read by the game
+    fn latch_time(&mut self, host_seconds: i64) {
+        let mut seconds = self.get_rtc_seconds(host_seconds);
+        if seconds.div_euclid(SECONDS_PER_DAY) >= DAYS_WRAP {
+            self.rtc_carry = true;
+            seconds = seconds.rem_euclid(DAYS_WRAP * SECONDS_PER_DAY);
+            self.set_rtc_seconds(seconds, host_seconds);
+        }
+
+        let days = seconds.div_euclid(SECONDS_PER_DAY);
+        let mut day_high = (days >> 8) as u8 & 0x01;
+        if self.rtc_halted.is_some() {
+            day_high |= DAY_HIGH_HALT;
+        }
+        if self.rtc_carry {
+            day_high |= DAY_HIGH_CARRY;
+        }
+
+        self.rtc_latched = [
+            seconds.rem_euclid(60) as u8,
+            seconds.div_euclid(60).rem_euclid(60) as u8,
+            seconds.div_euclid(60 * 60).rem_euclid(24) as u8,
+            days as u8,
+            day_high,
+        ];
+    }
+
+    /// Set one of the clock counter registers, the others keep counting from their current value
+    fn write_rtc_register(&mut self, register: u8, value: u8, host_seconds: i64) {
+        let seconds = self.get_rtc_seconds(host_seconds).rem_euclid(DAYS_WRAP * SECONDS_PER_DAY);
+        let (days, time_of_day) = (seconds.div_euclid(SECONDS_PER_DAY), seconds.rem_euclid(SECONDS_PER_DAY));
+        let (hours, minutes, seconds) = (time_of_day / 3600, time_of_day / 60 % 60, time_of_day % 60);
+        let value = value as i64;
+
+        let (days, hours, minutes, seconds) = match register {
+            RTC_SECONDS => (days, hours, minutes, value % 60),
+            RTC_MINUTES => (days, hours, value % 60, seconds),
+            RTC_HOURS => (days, value % 24, minutes, seconds),
+            RTC_DAY_LOW => ((days & 0x100) | value, hours, minutes, seconds),
+            _ => {
+                let halt = value as u8 & DAY_HIGH_HALT != 0;
+                self.rtc_carry = value as u8 & DAY_HIGH_CARRY != 0;
+                match (halt, self.rtc_halted) {
+                    (true, None) => self.rtc_halted = Some(self.get_rtc_seconds(host_seconds)),
+                    (false, Some(halted)) => {
+                        self.rtc_offset = halted - host_seconds;
+                        self.rtc_halted = None;
+                    }
+                    _ => {}
+                }
+                ((days & 0xFF) | ((value & 0x01) << 8), hours, minutes, seconds)
+            }
+        };
+
+        let total = ((days * 24 + hours) * 60 + minutes) * 60 + seconds;
+        self.set_rtc_seconds(total, host_seconds);
+    }
+
+    fn is_rtc_selected(&self) -> bool {
+        self.has_timer && (RTC_SECONDS..=RTC_DAY_HIGH).contains(&self.ram_bank)
+    }
+}
+
+impl MemoryOperations for Mbc3 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0xA000..=0xBFFF if self.ram_enabled && self.is_rtc_selected() => {
+                self.rtc_latched[(self.ram_bank - RTC_SECONDS) as usize]
+            }
+            // The RAM is disabled or missing, otherwise it would be mapped directly
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            // https://gbdev.io/pandocs/MBC3.html#0000-1fff---ram-and-timer-enable-write-only
+            0x0000..=0x1FFF => self.enable_ram(value & 0x0F == 0x0A),
+            // https://gbdev.io/pandocs/MBC3.html#2000-3fff---rom-bank-number-write-only
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            // https://gbdev.io/pandocs/MBC3.html#4000-5fff---ram-bank-number---or---rtc-register-select-write-only
+            0x4000..=0x5FFF => self.switch_ram_bank(value),
+            // https://gbdev.io/pandocs/MBC3.html#6000-7fff---latch-clock-data-write-only
+            0x6000..=0x7FFF => {
+                if self.latch_value == 0x00 && value == 0x01 && self.has_timer {
+                    self.latch_time(self.get_host_seconds());
+                }
+                self.latch_value = value;
+            }
+            0xA000..=0xBFFF if self.ram_enabled && self.is_rtc_selected() => {
+                self.write_rtc_register(self.ram_bank, value, self.get_host_seconds());
+            }
+            0xA000..=0xBFFF => {
+                // The RAM is disabled or missing, otherwise it would be mapped directly
+            }
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mbc3 {
+    fn init(&mut self, rom_size: u8, cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+        self.has_battery = MBC3_BATTERY_TYPES.contains(&cartridge_type);
+        self.has_timer = MBC3_TIMER_TYPES.contains(&cartridge_type);
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        let bank = if address >= 0x4000 { self.rom_bank as usize } else { 0 };
+        // Unused upper bits of the bank number aren't connected
+        (bank & (self.rom_banks - 1)) * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+    }
+
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram_banks == 0 || self.ram_bank >= RTC_SECONDS {
+            return None;
+        }
+
+        let bank = self.ram_bank as usize % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn set_clock_minutes(&mut self, minutes: Option<i64>) {
+        self.clock_minutes = minutes;
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        let bank = bank & 0x7F;
+        self.rom_bank = if bank == 0 { 1 } else { bank };
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.ram_bank = bank & 0x0F;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM enabled: {}", enable);
+        self.ram_enabled = enable;
+    }
+}
+
+impl SaveState for Mbc3 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank);
+        state.write(&self.ram_bank);
+        state.write(&self.ram_enabled);
+        state.write(&self.rtc_offset);
+        state.write(&self.rtc_halted);
+        state.write(&self.rtc_carry);
+        state.write(&self.rtc_latched);
+        state.write(&self.latch_value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank = state.read()?;
+        self.ram_bank = state.read()?;
+        self.ram_enabled = state.read()?;
+        self.rtc_offset = state.read()?;
+        self.rtc_halted = state.read()?;
+        self.rtc_carry = state.read()?;
+        self.rtc_latched = state.read()?;
+        self.latch_value = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn mbc3_banking_test() {
+    // 2 MiB ROM & 32 KiB RAM without a clock
+    let mut mbc = Mbc3::default();
+    mbc.init(0x06, 0x13, 0x03);
+    assert!(mbc.has_battery());
+
+    // Bank 0 selects bank 1, all 7 bits are used
+    mbc.write_byte(0x2000, 0x00);
+    assert_eq!(mbc.get_rom_address(0x4000), ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x7F);
+    assert_eq!(mbc.get_rom_address(0x7FFF), 0x80 * ROM_BANK_SIZE - 1);
+
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+    mbc.write_byte(0x0000, 0x0A);
+    mbc.write_byte(0x4000, 0x02);
+    assert_eq!(mbc.get_ram_address(0xA000), Some(2 * RAM_BANK_SIZE));
+
+    // Without a clock its registers read as open bus
+    mbc.write_byte(0x4000, RTC_SECONDS);
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+    assert_eq!(mbc.read_byte(0xA000), 0xFF);
+}
+
+#[test]
+pub fn mbc3_rtc_test() {
+    let mut mbc = Mbc3::default();
+    mbc.init(0x05, 0x10, 0x03);
+    mbc.write_byte(0x0000, 0x0A);
+    let latch = |mbc: &mut Mbc3, host_seconds: i64| {
+        mbc.latch_time(host_seconds);
+        mbc.rtc_latched
+    };
+
+    // Set the clock to day 0x1FF, 23:59:58 at host second 1000
+    let registers = [(RTC_SECONDS, 58), (RTC_MINUTES, 59), (RTC_HOURS, 23), (RTC_DAY_LOW, 0xFF), (RTC_DAY_HIGH, 0x01)];
+    for (register, value) in registers {
+        mbc.write_rtc_register(register, value, 1000);
+    }
+    assert_eq!(latch(&mut mbc, 1001), [59, 59, 23, 0xFF, 0x01]);
+
+    // The day counter overflows into the carry
+    assert_eq!(latch(&mut mbc, 1002), [0, 0, 0, 0x00, DAY_HIGH_CARRY]);
+
+    // The clock doesn't count while halted and the carry stays until it is cleared
+    mbc.write_rtc_register(RTC_DAY_HIGH, DAY_HIGH_HALT | DAY_HIGH_CARRY, 1010);
+    assert_eq!(latch(&mut mbc, 2000), [8, 0, 0, 0x00, DAY_HIGH_HALT | DAY_HIGH_CARRY]);
+    mbc.write_rtc_register(RTC_DAY_HIGH, 0x00, 2000);
+    assert_eq!(latch(&mut mbc, 2005), [13, 0, 0, 0x00, 0x00]);
+
+    // Latching is done by writing 0x00 and then 0x01, the registers are read in RAM bank 0x08 to 0x0C
+    // 400 seconds later by a pinned clock
+    mbc.set_clock_minutes(Some(40));
+    mbc.write_byte(0x6000, 0x00);
+    mbc.write_byte(0x6000, 0x01);
+    mbc.write_byte(0x4000, RTC_SECONDS);
+    assert_eq!(mbc.read_byte(0xA000), 48);
+    mbc.write_byte(0x4000, RTC_MINUTES);
+    assert_eq!(mbc.read_byte(0xA000), 6);
+}
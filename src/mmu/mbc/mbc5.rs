@@ -0,0 +1,146 @@
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Cartridge types of MBC5 carts with a battery buffering the RAM
+const MBC5_BATTERY_TYPES: [u8; 2] = [0x1B, 0x1E];
+/// Cartridge types with a rumble motor, which is switched by bit 3 of the RAM bank number
+const MBC5_RUMBLE_TYPES: std::ops::RangeInclusive<u8> = 0x1C..=0x1E;
+const RUMBLE_BIT: u8 = 0b0000_1000;
+
+/// Unlike MBC1, bank 0 can be mapped to 0x4000 to 0x7FFF and there is no banking mode
+/// See: https://gbdev.io/pandocs/MBC5.html
+pub struct Mbc5 {
+    /// The 9 bit ROM bank number mapped to 0x4000 to 0x7FFF
+    rom_bank: u16,
+    ram_bank: u8,
+    ram_enabled: bool,
+    /// Amount of ROM banks, always a power of 2
+    rom_banks: usize,
+    /// Amount of RAM banks, 0 if there is no RAM
+    ram_banks: usize,
+    has_battery: bool,
+    has_rumble: bool,
+}
+
+impl Default for Mbc5 {
+    fn default() -> Self {
+        Self {
+            rom_bank: 1,
+            ram_bank: 0,
+            ram_enabled: false,
+            rom_banks: 2,
+            ram_banks: 0,
+            has_battery: false,
+            has_rumble: false,
+        }
+    }
+}
+
+impl MemoryOperations for Mbc5 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            // The RAM is disabled or missing, otherwise it would be mapped directly
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            // https://gbdev.io/pandocs/MBC5.html#0000-1fff---ram-enable-write-only
+            0x0000..=0x1FFF => self.enable_ram(value == 0x0A),
+            // https://gbdev.io/pandocs/MBC5.html#2000-2fff---8-least-significant-bits-of-rom-bank-number-write-only
+            0x2000..=0x2FFF => self.rom_bank = (self.rom_bank & 0x100) | value as u16,
+            // https://gbdev.io/pandocs/MBC5.html#3000-3fff---9th-bit-of-rom-bank-number-write-only
+            0x3000..=0x3FFF => self.rom_bank = (self.rom_bank & 0xFF) | ((value as u16 & 0x01) << 8),
+            // https://gbdev.io/pandocs/MBC5.html#4000-5fff---ram-bank-number-write-only
+            0x4000..=0x5FFF => self.switch_ram_bank(value),
+            0x6000..=0x7FFF => {}
+            0xA000..=0xBFFF => {
+                // The RAM is disabled or missing, otherwise it would be mapped directly
+            }
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mbc5 {
+    fn init(&mut self, rom_size: u8, cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+        self.has_battery = MBC5_BATTERY_TYPES.contains(&cartridge_type);
+        self.has_rumble = MBC5_RUMBLE_TYPES.contains(&cartridge_type);
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        let bank = if address >= 0x4000 { self.rom_bank as usize } else { 0 };
+        // Unused upper bits of the bank number aren't connected
+        (bank & (self.rom_banks - 1)) * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+    }
+
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return None;
+        }
+
+        let bank = self.ram_bank as usize % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::info!("Switching ROM bank to {}", bank);
+        self.rom_bank = bank as u16;
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        // The rumble motor takes the place of the upper bit, it isn't emulated
+        let mask = if self.has_rumble { 0x0F & !RUMBLE_BIT } else { 0x0F };
+        self.ram_bank = bank & mask;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM enabled: {}", enable);
+        self.ram_enabled = enable;
+    }
+}
+
+impl SaveState for Mbc5 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank);
+        state.write(&self.ram_bank);
+        state.write(&self.ram_enabled);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank = state.read()?;
+        self.ram_bank = state.read()?;
+        self.ram_enabled = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn mbc5_banking_test() {
+    // 8 MiB ROM & 128 KiB RAM with rumble
+    let mut mbc = Mbc5::default();
+    mbc.init(0x08, 0x1E, 0x04);
+    assert!(mbc.has_battery());
+
+    // Bank 0 can be mapped to the switchable area, the 9th bit is written separately
+    mbc.write_byte(0x2000, 0x00);
+    assert_eq!(mbc.get_rom_address(0x4000), 0);
+    mbc.write_byte(0x2000, 0x23);
+    mbc.write_byte(0x3000, 0x01);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x123 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x0000), 0);
+
+    // The rumble bit doesn't select a RAM bank
+    assert_eq!(mbc.get_ram_address(0xA000), None);
+    mbc.write_byte(0x0000, 0x0A);
+    mbc.write_byte(0x4000, 0x0F);
+    assert_eq!(mbc.get_ram_address(0xA000), Some(7 * RAM_BANK_SIZE));
+}
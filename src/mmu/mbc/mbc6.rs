@@ -0,0 +1,348 @@
+use crate::mmu::{MemoryBankControllerOperations, MemoryOperations};
+use crate::save_state::{SaveState, StateReader, StateValue, StateWriter};
+
+/// MBC6 switches the ROM & flash in 8 KiB and the RAM in 4 KiB halves
+const HALF_ROM_BANK_SIZE: usize = 0x2000;
+const HALF_RAM_BANK_SIZE: usize = 0x1000;
+/// 32 KiB RAM followed by 1 MiB flash, saved together
+const RAM_SIZE: usize = 0x8000;
+const FLASH_SIZE: usize = 0x10_0000;
+/// The flash is erased in sectors of 128 KiB
+const FLASH_SECTOR_SIZE: usize = 0x2_0000;
+
+/// Writing this to the bank select registers maps the flash instead of the ROM
+const SELECT_FLASH: u8 = 0x08;
+
+/// Command addresses & values of the Macronix MX29F008 flash, the address is masked to 15 bits
+/// See: https://gbdev.io/pandocs/MBC6.html#flash-commands
+const FLASH_UNLOCK_ADDRESS_1: usize = 0x5555;
+const FLASH_UNLOCK_ADDRESS_2: usize = 0x2AAA;
+const FLASH_UNLOCK_1: u8 = 0xAA;
+const FLASH_UNLOCK_2: u8 = 0x55;
+const FLASH_COMMAND_ID: u8 = 0x90;
+const FLASH_COMMAND_RESET: u8 = 0xF0;
+const FLASH_COMMAND_PROGRAM: u8 = 0xA0;
+const FLASH_COMMAND_ERASE: u8 = 0x80;
+const FLASH_ERASE_SECTOR: u8 = 0x30;
+const FLASH_ERASE_CHIP: u8 = 0x10;
+/// Manufacturer & device ID read in ID mode
+const FLASH_MANUFACTURER_ID: u8 = 0xC2;
+const FLASH_DEVICE_ID: u8 = 0x81;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum FlashState {
+    Ready,
+    /// Received the first and second unlock byte
+    Unlocked1,
+    Unlocked2,
+    /// The next write programs a byte
+    Program,
+    /// Erasing needs a second unlock sequence
+    EraseUnlocked0,
+    EraseUnlocked1,
+    EraseUnlocked2,
+}
+
+/// MBC6 of Net de Get, with two independently switchable halves and flash memory
+/// See: https://gbdev.io/pandocs/MBC6.html
+pub struct Mbc6 {
+    ram_enabled: bool,
+    /// RAM banks mapped to 0xA000 to 0xAFFF (A) and 0xB000 to 0xBFFF (B)
+    ram_bank_a: u8,
+    ram_bank_b: u8,
+    flash_enabled: bool,
+    flash_write_enabled: bool,
+    /// ROM or flash banks mapped to 0x4000 to 0x5FFF (A) and 0x6000 to 0x7FFF (B)
+    rom_bank_a: u8,
+    rom_bank_b: u8,
+    flash_selected_a: bool,
+    flash_selected_b: bool,
+    flash_state: FlashState,
+    /// The flash returns its IDs instead of the data
+    flash_id_mode: bool,
+    /// The RAM followed by the flash
+    storage: Vec<u8>,
+}
+
+impl Default for Mbc6 {
+    fn default() -> Self {
+        let mut storage = vec![0; RAM_SIZE + FLASH_SIZE];
+        storage[RAM_SIZE..].fill(0xFF);
+
+        Self {
+            ram_enabled: false,
+            ram_bank_a: 0,
+            ram_bank_b: 0,
+            flash_enabled: false,
+            flash_write_enabled: false,
+            rom_bank_a: 0,
+            rom_bank_b: 0,
+            flash_selected_a: false,
+            flash_selected_b: false,
+            flash_state: FlashState::Ready,
+            flash_id_mode: false,
+            storage,
+        }
+    }
+}
+
+impl Mbc6 {
+    /// Bank number & whether the flash is selected for the half containing the address
+    fn get_half_bank(&self, address: u16) -> (usize, bool) {
+        match address {
+            0x4000..=0x5FFF => (self.rom_bank_a as usize, self.flash_selected_a),
+            _ => (self.rom_bank_b as usize, self.flash_selected_b),
+        }
+    }
+
+    fn get_flash_address(&self, address: u16) -> usize {
+        let (bank, _) = self.get_half_bank(address);
+        (bank * HALF_ROM_BANK_SIZE + (address as usize % HALF_ROM_BANK_SIZE)) % FLASH_SIZE
+    }
+
+    fn get_ram_index(&self, address: u16) -> usize {
+        let bank = if address < 0xB000 { self.ram_bank_a } else { self.ram_bank_b };
+        (bank as usize * HALF_RAM_BANK_SIZE + (address as usize % HALF_RAM_BANK_SIZE)) % RAM_SIZE
+    }
+
+    fn write_flash(&mut self, address: u16, value: u8) {
+        let flash_address = self.get_flash_address(address);
+        let command_address = flash_address & 0x7FFF;
+
+        self.flash_state = match (self.flash_state, command_address, value) {
+            (_, _, FLASH_COMMAND_RESET) => {
+                self.flash_id_mode = false;
+                FlashState::Ready
+            }
+            (FlashState::Ready, FLASH_UNLOCK_ADDRESS_1, FLASH_UNLOCK_1) => FlashState::Unlocked1,
+            (FlashState::Unlocked1, FLASH_UNLOCK_ADDRESS_2, FLASH_UNLOCK_2) => FlashState::Unlocked2,
+            (FlashState::Unlocked2, FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_ID) => {
+                self.flash_id_mode = true;
+                FlashState::Ready
+            }
+            (FlashState::Unlocked2, FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_PROGRAM) => FlashState::Program,
+            (FlashState::Unlocked2, FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_ERASE) => FlashState::EraseUnlocked0,
+            (FlashState::Program, _, _) => {
+                // Programming can only clear bits
+                self.storage[RAM_SIZE + flash_address] &= value;
+                FlashState::Ready
+            }
+            (FlashState::EraseUnlocked0, FLASH_UNLOCK_ADDRESS_1, FLASH_UNLOCK_1) => FlashState::EraseUnlocked1,
+            (FlashState::EraseUnlocked1, FLASH_UNLOCK_ADDRESS_2, FLASH_UNLOCK_2) => FlashState::EraseUnlocked2,
+            (FlashState::EraseUnlocked2, _, FLASH_ERASE_SECTOR) => {
+                let sector = RAM_SIZE + flash_address / FLASH_SECTOR_SIZE * FLASH_SECTOR_SIZE;
+                self.storage[sector..sector + FLASH_SECTOR_SIZE].fill(0xFF);
+                FlashState::Ready
+            }
+            (FlashState::EraseUnlocked2, FLASH_UNLOCK_ADDRESS_1, FLASH_ERASE_CHIP) => {
+                self.storage[RAM_SIZE..].fill(0xFF);
+                FlashState::Ready
+            }
+            _ => FlashState::Ready,
+        };
+    }
+}
+
+impl MemoryOperations for Mbc6 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0x4000..=0x7FFF if !self.flash_enabled => 0xFF,
+            0x4000..=0x7FFF if self.flash_id_mode => {
+                if address & 0x01 == 0 { FLASH_MANUFACTURER_ID } else { FLASH_DEVICE_ID }
+            }
+            0x4000..=0x7FFF => self.storage[RAM_SIZE + self.get_flash_address(address)],
+            0xA000..=0xBFFF if self.ram_enabled => self.storage[self.get_ram_index(address)],
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x03FF => self.enable_ram(value & 0x0F == 0x0A),
+            0x0400..=0x07FF => self.ram_bank_a = value & 0x07,
+            0x0800..=0x0BFF => self.ram_bank_b = value & 0x07,
+            0x0C00..=0x0FFF => self.flash_enabled = value & 0x01 != 0,
+            0x1000..=0x1FFF => self.flash_write_enabled = value & 0x01 != 0,
+            0x2000..=0x27FF => self.rom_bank_a = value & 0x7F,
+            0x2800..=0x2FFF => self.flash_selected_a = value == SELECT_FLASH,
+            0x3000..=0x37FF => self.rom_bank_b = value & 0x7F,
+            0x3800..=0x3FFF => self.flash_selected_b = value == SELECT_FLASH,
+            0x4000..=0x7FFF if self.get_half_bank(address).1 && self.flash_enabled && self.flash_write_enabled => {
+                self.write_flash(address, value);
+            }
+            0x4000..=0x7FFF => {}
+            0xA000..=0xBFFF if self.ram_enabled => {
+                let index = self.get_ram_index(address);
+                self.storage[index] = value;
+            }
+            0xA000..=0xBFFF => {}
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mbc6 {
+    fn init(&mut self, _rom_size: u8, _cartridge_type: u8, _ram_size: u8) {
+        // The RAM & flash always have the same size
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                let (bank, _) = self.get_half_bank(address);
+                bank * HALF_ROM_BANK_SIZE + (address as usize % HALF_ROM_BANK_SIZE)
+            }
+        }
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        // The RAM is saved together with the flash, so the MBC manages it
+        None
+    }
+
+    fn is_rom_mapped(&self, address: u16) -> bool {
+        address < 0x4000 || !self.get_half_bank(address).1
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        Some(&self.storage)
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.storage)
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_a = bank & 0x7F;
+        self.rom_bank_b = bank & 0x7F;
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.ram_bank_a = bank & 0x07;
+        self.ram_bank_b = bank & 0x07;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM enabled: {}", enable);
+        self.ram_enabled = enable;
+    }
+}
+
+impl StateValue for FlashState {
+    fn write_to(&self, state: &mut StateWriter) {
+        state.write(&(*self as u8));
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        match state.read::<u8>()? {
+            0 => Ok(FlashState::Ready),
+            1 => Ok(FlashState::Unlocked1),
+            2 => Ok(FlashState::Unlocked2),
+            3 => Ok(FlashState::Program),
+            4 => Ok(FlashState::EraseUnlocked0),
+            5 => Ok(FlashState::EraseUnlocked1),
+            6 => Ok(FlashState::EraseUnlocked2),
+            value => Err(format!("Invalid flash state {}", value)),
+        }
+    }
+}
+
+impl SaveState for Mbc6 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.ram_enabled);
+        state.write(&self.ram_bank_a);
+        state.write(&self.ram_bank_b);
+        state.write(&self.flash_enabled);
+        state.write(&self.flash_write_enabled);
+        state.write(&self.rom_bank_a);
+        state.write(&self.rom_bank_b);
+        state.write(&self.flash_selected_a);
+        state.write(&self.flash_selected_b);
+        state.write(&self.flash_state);
+        state.write(&self.flash_id_mode);
+        state.write_bytes(&self.storage);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.ram_enabled = state.read()?;
+        self.ram_bank_a = state.read()?;
+        self.ram_bank_b = state.read()?;
+        self.flash_enabled = state.read()?;
+        self.flash_write_enabled = state.read()?;
+        self.rom_bank_a = state.read()?;
+        self.rom_bank_b = state.read()?;
+        self.flash_selected_a = state.read()?;
+        self.flash_selected_b = state.read()?;
+        self.flash_state = state.read()?;
+        self.flash_id_mode = state.read()?;
+        state.read_bytes(&mut self.storage)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Mbc6 {
+    /// Send a command to the flash via bank A, selecting the bank the address is in
+    fn send_flash_command(&mut self, flash_address: usize, value: u8) {
+        self.write_byte(0x2000, (flash_address / HALF_ROM_BANK_SIZE) as u8);
+        self.write_byte(0x4000 + (flash_address % HALF_ROM_BANK_SIZE) as u16, value);
+    }
+
+    fn send_flash_unlock(&mut self) {
+        self.send_flash_command(FLASH_UNLOCK_ADDRESS_1, FLASH_UNLOCK_1);
+        self.send_flash_command(FLASH_UNLOCK_ADDRESS_2, FLASH_UNLOCK_2);
+    }
+}
+
+#[test]
+pub fn mbc6_test() {
+    let mut mbc = Mbc6::default();
+    mbc.init(0x05, 0x20, 0x03);
+
+    // Both halves are switched separately
+    mbc.write_byte(0x2000, 0x05);
+    mbc.write_byte(0x3000, 0x0A);
+    assert_eq!(mbc.get_rom_address(0x4000), 5 * HALF_ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x6001), 10 * HALF_ROM_BANK_SIZE + 1);
+    mbc.write_byte(0x0000, 0x0A);
+    mbc.write_byte(0x0400, 0x01);
+    mbc.write_byte(0x0800, 0x02);
+    mbc.write_byte(0xB000, 0x12);
+    assert_eq!(mbc.read_byte(0xB000), 0x12);
+    assert_eq!(mbc.get_internal_ram().unwrap()[2 * HALF_RAM_BANK_SIZE], 0x12);
+    assert_eq!(mbc.read_byte(0xA000), 0x00);
+
+    // Map the flash to bank A and program a byte
+    mbc.write_byte(0x0C00, 0x01);
+    mbc.write_byte(0x1000, 0x01);
+    mbc.write_byte(0x2800, SELECT_FLASH);
+    assert!(!mbc.is_rom_mapped(0x4000));
+    assert!(mbc.is_rom_mapped(0x6000));
+
+    mbc.send_flash_unlock();
+    mbc.send_flash_command(FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_PROGRAM);
+    mbc.send_flash_command(0x4_0123, 0x5A);
+    assert_eq!(mbc.read_byte(0x4123), 0x5A);
+
+    mbc.send_flash_unlock();
+    mbc.send_flash_command(FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_ID);
+    assert_eq!(mbc.read_byte(0x4000), FLASH_MANUFACTURER_ID);
+    assert_eq!(mbc.read_byte(0x4001), FLASH_DEVICE_ID);
+    mbc.send_flash_command(0x0000, FLASH_COMMAND_RESET);
+
+    // Erasing the sector sets it to 0xFF again
+    mbc.send_flash_unlock();
+    mbc.send_flash_command(FLASH_UNLOCK_ADDRESS_1, FLASH_COMMAND_ERASE);
+    mbc.send_flash_unlock();
+    mbc.send_flash_command(0x4_0000, FLASH_ERASE_SECTOR);
+    mbc.send_flash_command(0x4_0123, 0x00);
+    assert_eq!(mbc.read_byte(0x4123), 0xFF);
+}
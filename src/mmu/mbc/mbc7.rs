@@ -0,0 +1,495 @@
+use crate::mmu::{get_rom_size, MemoryBankControllerOperations, MemoryOperations, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateValue, StateWriter};
+
+/// Value of both accelerometer axes while the cartridge is level
+const ACCELEROMETER_CENTER: f32 = 0x81D0 as f32;
+/// Change of an axis per g of acceleration
+const ACCELEROMETER_GRAVITY: f32 = 0x70 as f32;
+/// Value of both axes after erasing the latch
+const ACCELEROMETER_ERASED: u16 = 0x8000;
+
+/// The 93LC56 stores 128 16-bit words
+const EEPROM_SIZE: usize = 0x100;
+const EEPROM_WORDS: u8 = 0x80;
+/// Bits of the command after the start bit, 2 opcode & 8 address bits
+const EEPROM_COMMAND_BITS: u8 = 10;
+const EEPROM_WORD_BITS: u8 = 16;
+
+/// Bits of the EEPROM register at Ax8x
+const EEPROM_CS: u8 = 0b1000_0000;
+const EEPROM_CLK: u8 = 0b0100_0000;
+const EEPROM_DI: u8 = 0b0000_0010;
+const EEPROM_DO: u8 = 0b0000_0001;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum EepromState {
+    /// Waiting for the start bit
+    Idle,
+    /// Shifting in the opcode & address
+    Command { value: u16, bits: u8 },
+    /// Shifting out a word, continues with the next word afterwards
+    Reading { word: u8, bits: u8 },
+    /// Shifting in the data of WRITE (Some word) or WRAL (None)
+    Writing { word: Option<u8>, value: u16, bits: u8 },
+    /// The command is done, nothing happens until CS is lowered
+    Done,
+}
+
+/// The 93LC56 serial EEPROM, bit-banged by the game via the Ax8x register
+/// See: https://gbdev.io/pandocs/MBC7.html#ax8x---eeprom
+struct Eeprom {
+    data: Vec<u8>,
+    state: EepromState,
+    /// Writes & erases are ignored until EWEN is sent
+    write_enabled: bool,
+    chip_select: bool,
+    clock: bool,
+    data_in: bool,
+    data_out: bool,
+}
+
+impl Default for Eeprom {
+    fn default() -> Self {
+        Self {
+            data: vec![0xFF; EEPROM_SIZE],
+            state: EepromState::Idle,
+            write_enabled: false,
+            chip_select: false,
+            clock: false,
+            data_in: false,
+            data_out: true,
+        }
+    }
+}
+
+impl Eeprom {
+    fn read_register(&self) -> u8 {
+        let mut value = 0;
+        if self.chip_select {
+            value |= EEPROM_CS;
+        }
+        if self.clock {
+            value |= EEPROM_CLK;
+        }
+        if self.data_in {
+            value |= EEPROM_DI;
+        }
+        if self.data_out {
+            value |= EEPROM_DO;
+        }
+        value
+    }
+
+    fn write_register(&mut self, value: u8) {
+        let chip_select = value & EEPROM_CS != 0;
+        let clock = value & EEPROM_CLK != 0;
+        self.data_in = value & EEPROM_DI != 0;
+
+        if !chip_select {
+            // Lowering CS aborts the command, DO shows that the chip is ready
+            self.state = EepromState::Idle;
+            self.data_out = true;
+        } else if clock && !self.clock {
+            // Bits are shifted on the rising edge of the clock
+            self.clock_bit();
+        }
+
+        self.chip_select = chip_select;
+        self.clock = clock;
+    }
+
+    fn clock_bit(&mut self) {
+        let bit = self.data_in as u16;
+
+        self.state = match self.state {
+            EepromState::Idle if self.data_in => EepromState::Command { value: 0, bits: 0 },
+            EepromState::Idle => EepromState::Idle,
+            EepromState::Command { value, bits } => {
+                let value = (value << 1) | bit;
+                if bits + 1 == EEPROM_COMMAND_BITS {
+                    self.execute(value)
+                } else {
+                    EepromState::Command { value, bits: bits + 1 }
+                }
+            }
+            EepromState::Reading { word, bits } => {
+                self.data_out = self.read_word(word) & (0x8000 >> bits) != 0;
+                if bits + 1 == EEPROM_WORD_BITS {
+                    EepromState::Reading { word: (word + 1) % EEPROM_WORDS, bits: 0 }
+                } else {
+                    EepromState::Reading { word, bits: bits + 1 }
+                }
+            }
+            EepromState::Writing { word, value, bits } => {
+                let value = (value << 1) | bit;
+                if bits + 1 == EEPROM_WORD_BITS {
+                    match word {
+                        Some(word) => self.write_word(word, value),
+                        None => (0..EEPROM_WORDS).for_each(|word| self.write_word(word, value)),
+                    }
+                    self.data_out = true;
+                    EepromState::Done
+                } else {
+                    EepromState::Writing { word, value, bits: bits + 1 }
+                }
+            }
+            EepromState::Done => EepromState::Done,
+        };
+    }
+
+    /// Execute a complete command, the address is 8 bits, but the top bit is ignored
+    /// See: https://gbdev.io/pandocs/MBC7.html#eeprom-commands
+    fn execute(&mut self, command: u16) -> EepromState {
+        let opcode = command >> 8;
+        let address = command as u8;
+        let word = address % EEPROM_WORDS;
+
+        match opcode {
+            // READ, a dummy 0 is output before the data
+            0b10 => {
+                self.data_out = false;
+                EepromState::Reading { word, bits: 0 }
+            }
+            // WRITE
+            0b01 => EepromState::Writing { word: Some(word), value: 0, bits: 0 },
+            // ERASE
+            0b11 => {
+                self.write_word(word, 0xFFFF);
+                EepromState::Done
+            }
+            _ => match address >> 6 {
+                // EWDS
+                0b00 => {
+                    self.write_enabled = false;
+                    EepromState::Done
+                }
+                // WRAL
+                0b01 => EepromState::Writing { word: None, value: 0, bits: 0 },
+                // ERAL
+                0b10 => {
+                    (0..EEPROM_WORDS).for_each(|word| self.write_word(word, 0xFFFF));
+                    EepromState::Done
+                }
+                // EWEN
+                _ => {
+                    self.write_enabled = true;
+                    EepromState::Done
+                }
+            },
+        }
+    }
+
+    fn read_word(&self, word: u8) -> u16 {
+        let index = word as usize * 2;
+        u16::from_le_bytes([self.data[index], self.data[index + 1]])
+    }
+
+    fn write_word(&mut self, word: u8, value: u16) {
+        if !self.write_enabled {
+            return;
+        }
+
+        let index = word as usize * 2;
+        self.data[index..index + 2].copy_from_slice(&value.to_le_bytes());
+    }
+}
+
+/// MBC7 with a two-axis accelerometer and a serial EEPROM instead of RAM
+/// See: https://gbdev.io/pandocs/MBC7.html
+pub struct Mbc7 {
+    rom_bank_number: u8,
+    rom_banks: usize,
+    /// Both RAM enable registers have to be set to access 0xA000 to 0xAFFF
+    ram_enabled_1: bool,
+    ram_enabled_2: bool,
+    /// Current tilt in g, see `set_tilt`
+    tilt: (f32, f32),
+    /// The accelerometer values the game reads
+    latched_x: u16,
+    latched_y: u16,
+    /// A new value can only be latched after the latch has been erased
+    latch_erased: bool,
+    eeprom: Eeprom,
+}
+
+impl Default for Mbc7 {
+    fn default() -> Self {
+        Self {
+            rom_bank_number: 1,
+            rom_banks: 2,
+            ram_enabled_1: false,
+            ram_enabled_2: false,
+            tilt: (0.0, 0.0),
+            latched_x: ACCELEROMETER_ERASED,
+            latched_y: ACCELEROMETER_ERASED,
+            latch_erased: false,
+            eeprom: Eeprom::default(),
+        }
+    }
+}
+
+impl Mbc7 {
+    fn is_ram_enabled(&self) -> bool {
+        self.ram_enabled_1 && self.ram_enabled_2
+    }
+
+    fn get_accelerometer_value(tilt: f32) -> u16 {
+        (ACCELEROMETER_CENTER + tilt.clamp(-1.0, 1.0) * ACCELEROMETER_GRAVITY) as u16
+    }
+}
+
+impl MemoryOperations for Mbc7 {
+    /// Registers are selected by bits 4-7 of the address
+    /// See: https://gbdev.io/pandocs/MBC7.html#a000-afff---ram-registers
+    fn read_byte(&self, address: u16) -> u8 {
+        if !self.is_ram_enabled() || address >= 0xB000 {
+            return 0xFF;
+        }
+
+        match (address >> 4) & 0x0F {
+            0x2 => self.latched_x as u8,
+            0x3 => (self.latched_x >> 8) as u8,
+            0x4 => self.latched_y as u8,
+            0x5 => (self.latched_y >> 8) as u8,
+            0x6 => 0x00,
+            0x8 => self.eeprom.read_register(),
+            _ => 0xFF,
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.ram_enabled_1 = value == 0x0A,
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x5FFF => self.ram_enabled_2 = value == 0x40,
+            0xA000..=0xAFFF if self.is_ram_enabled() => match (address >> 4) & 0x0F {
+                0x0 if value == 0x55 => {
+                    self.latched_x = ACCELEROMETER_ERASED;
+                    self.latched_y = ACCELEROMETER_ERASED;
+                    self.latch_erased = true;
+                }
+                0x1 if value == 0xAA && self.latch_erased => {
+                    self.latched_x = Self::get_accelerometer_value(self.tilt.0);
+                    self.latched_y = Self::get_accelerometer_value(self.tilt.1);
+                    self.latch_erased = false;
+                }
+                0x8 => self.eeprom.write_register(value),
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mbc7 {
+    fn init(&mut self, rom_size: u8, _cartridge_type: u8, _ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                let bank = self.rom_bank_number as usize % self.rom_banks;
+                bank * ROM_BANK_SIZE + (address as usize - 0x4000)
+            }
+        }
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        // There is no RAM, only registers
+        None
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        Some(&self.eeprom.data)
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.eeprom.data)
+    }
+
+    fn set_tilt(&mut self, x: f32, y: f32) {
+        self.tilt = (x, y);
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_number = bank & 0x7F;
+    }
+
+    fn switch_ram_bank(&mut self, _bank: u8) {
+        // There are no RAM banks
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        self.ram_enabled_1 = enable;
+        self.ram_enabled_2 = enable;
+    }
+}
+
+impl StateValue for EepromState {
+    fn write_to(&self, state: &mut StateWriter) {
+        match *self {
+            EepromState::Idle => state.write(&0u8),
+            EepromState::Command { value, bits } => {
+                state.write(&1u8);
+                state.write(&value);
+                state.write(&bits);
+            }
+            EepromState::Reading { word, bits } => {
+                state.write(&2u8);
+                state.write(&word);
+                state.write(&bits);
+            }
+            EepromState::Writing { word, value, bits } => {
+                state.write(&3u8);
+                state.write(&word);
+                state.write(&value);
+                state.write(&bits);
+            }
+            EepromState::Done => state.write(&4u8),
+        }
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        match state.read::<u8>()? {
+            0 => Ok(EepromState::Idle),
+            1 => Ok(EepromState::Command { value: state.read()?, bits: state.read()? }),
+            2 => Ok(EepromState::Reading { word: state.read()?, bits: state.read()? }),
+            3 => Ok(EepromState::Writing { word: state.read()?, value: state.read()?, bits: state.read()? }),
+            4 => Ok(EepromState::Done),
+            value => Err(format!("Invalid EEPROM state {}", value)),
+        }
+    }
+}
+
+impl SaveState for Eeprom {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.data);
+        state.write(&self.state);
+        state.write(&self.write_enabled);
+        state.write(&self.chip_select);
+        state.write(&self.clock);
+        state.write(&self.data_in);
+        state.write(&self.data_out);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.data)?;
+        self.state = state.read()?;
+        self.write_enabled = state.read()?;
+        self.chip_select = state.read()?;
+        self.clock = state.read()?;
+        self.data_in = state.read()?;
+        self.data_out = state.read()?;
+        Ok(())
+    }
+}
+
+/// The tilt is an input of the frontend, only the values latched by the game are part of the state
+impl SaveState for Mbc7 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank_number);
+        state.write(&self.ram_enabled_1);
+        state.write(&self.ram_enabled_2);
+        state.write(&self.latched_x);
+        state.write(&self.latched_y);
+        state.write(&self.latch_erased);
+        self.eeprom.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank_number = state.read()?;
+        self.ram_enabled_1 = state.read()?;
+        self.ram_enabled_2 = state.read()?;
+        self.latched_x = state.read()?;
+        self.latched_y = state.read()?;
+        self.latch_erased = state.read()?;
+        self.eeprom.load_state(state)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Mbc7 {
+    /// Start bit, opcode & address of an EEPROM command
+    fn get_eeprom_command(opcode: u32, address: u32) -> u32 {
+        (1 << EEPROM_COMMAND_BITS) | (opcode << 8) | address
+    }
+
+    /// Send bits to the EEPROM, each with a falling and a rising clock edge
+    fn send_eeprom_bits(&mut self, value: u32, bits: u8) {
+        for bit in (0..bits).rev() {
+            let data_in = if value & (1 << bit) != 0 { EEPROM_DI } else { 0 };
+            self.write_byte(0xA080, EEPROM_CS | data_in);
+            self.write_byte(0xA080, EEPROM_CS | EEPROM_CLK | data_in);
+        }
+    }
+
+    /// Clock out a word from the EEPROM
+    fn receive_eeprom_word(&mut self) -> u16 {
+        let mut value = 0;
+        for _ in 0..EEPROM_WORD_BITS {
+            self.write_byte(0xA080, EEPROM_CS);
+            self.write_byte(0xA080, EEPROM_CS | EEPROM_CLK);
+            value = (value << 1) | (self.read_byte(0xA080) & EEPROM_DO) as u16;
+        }
+        value
+    }
+}
+
+#[test]
+pub fn mbc7_accelerometer_test() {
+    let mut mbc = Mbc7::default();
+    mbc.write_byte(0x0000, 0x0A);
+    assert_eq!(mbc.read_byte(0xA020), 0xFF);
+    mbc.write_byte(0x4000, 0x40);
+
+    // Latching only works after erasing
+    mbc.set_tilt(1.0, -0.5);
+    mbc.write_byte(0xA010, 0xAA);
+    assert_eq!(mbc.read_byte(0xA030), 0x80);
+    mbc.write_byte(0xA000, 0x55);
+    mbc.write_byte(0xA010, 0xAA);
+    assert_eq!(mbc.read_byte(0xA020) as u16 | (mbc.read_byte(0xA030) as u16) << 8, 0x81D0 + 0x70);
+    assert_eq!(mbc.read_byte(0xA040) as u16 | (mbc.read_byte(0xA050) as u16) << 8, 0x81D0 - 0x38);
+
+    // The latched value is kept while tilting
+    mbc.set_tilt(0.0, 0.0);
+    assert_eq!(mbc.read_byte(0xA020), 0x40);
+}
+
+#[test]
+pub fn mbc7_eeprom_test() {
+    let mut mbc = Mbc7::default();
+    mbc.enable_ram(true);
+
+    // Writes are ignored until EWEN
+    mbc.send_eeprom_bits((Mbc7::get_eeprom_command(0b01, 0x03) << 16) | 0x1234, 27);
+    mbc.write_byte(0xA080, 0x00);
+    assert_eq!(mbc.eeprom.read_word(0x03), 0xFFFF);
+
+    // EWEN, WRITE 0x1234 to word 3
+    mbc.send_eeprom_bits(Mbc7::get_eeprom_command(0b00, 0xC0), 11);
+    mbc.write_byte(0xA080, 0x00);
+    mbc.send_eeprom_bits((Mbc7::get_eeprom_command(0b01, 0x03) << 16) | 0x1234, 27);
+    mbc.write_byte(0xA080, 0x00);
+    assert_eq!(mbc.read_byte(0xA080) & EEPROM_DO, EEPROM_DO);
+
+    // READ word 3 after the dummy bit, then continue with word 4
+    mbc.send_eeprom_bits(Mbc7::get_eeprom_command(0b10, 0x03), 11);
+    assert_eq!(mbc.read_byte(0xA080) & EEPROM_DO, 0);
+    assert_eq!(mbc.receive_eeprom_word(), 0x1234);
+    assert_eq!(mbc.receive_eeprom_word(), 0xFFFF);
+    mbc.write_byte(0xA080, 0x00);
+
+    // ERAL
+    mbc.send_eeprom_bits(Mbc7::get_eeprom_command(0b00, 0x80), 11);
+    mbc.write_byte(0xA080, 0x00);
+    assert_eq!(mbc.eeprom.read_word(0x03), 0xFFFF);
+}
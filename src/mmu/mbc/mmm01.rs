@@ -0,0 +1,286 @@
+use crate::mmu::{get_ram_size, get_rom_size, is_header_valid, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Cartridge types of the MMM01, the one with a battery buffering the RAM is the last
+const MMM01_TYPES: std::ops::RangeInclusive<u8> = 0x0B..=0x0D;
+const MMM01_RAM_BATTERY: u8 = 0x0D;
+const CARTRIDGE_TYPE_ADDRESS: usize = 0x0147;
+
+/// Until the mapping is enabled the last 32 KiB of the ROM are mapped, containing the menu
+const MENU_SIZE: usize = 2 * ROM_BANK_SIZE;
+const MENU_ROM_BANK_0: usize = 0x1FE;
+const MENU_ROM_BANK_1: usize = 0x1FF;
+
+/// Bits of the register at 0x0000 to 0x1FFF
+const MAPPING_ENABLE: u8 = 0b0100_0000;
+/// Bits of the register at 0x4000 to 0x5FFF
+const MBC1_MODE_WRITE_PROTECT: u8 = 0b0100_0000;
+/// Bits of the register at 0x6000 to 0x7FFF
+const MULTIPLEX_ENABLE: u8 = 0b0100_0000;
+
+/// Multi-game cartridges, a menu selects the banks of a game, which then behaves like an MBC1 cartridge
+/// Bits written while the mapping is disabled select the game and can't be changed by the game afterwards
+/// See: https://gbdev.io/pandocs/MMM01.html
+pub struct Mmm01 {
+    /// Set by the menu once it selected a game, the configuration can't be changed afterwards
+    mapping_enabled: bool,
+    ram_enabled: bool,
+    /// ROM bank bits 0-4, 5-6 and 7-8
+    rom_bank_low: u8,
+    rom_bank_mid: u8,
+    rom_bank_high: u8,
+    /// Bits 1-4 of the ROM bank low the game can't change
+    rom_bank_mask: u8,
+    /// RAM bank bits 0-1 and 2-3
+    ram_bank_low: u8,
+    ram_bank_high: u8,
+    /// Bits of the RAM bank low the game can't change
+    ram_bank_mask: u8,
+    mbc1_mode: bool,
+    mbc1_mode_write_protected: bool,
+    /// Swaps the ROM bank mid and RAM bank low, so games can use MBC1's advanced banking mode
+    multiplex: bool,
+    rom_banks: usize,
+    ram_banks: usize,
+    has_battery: bool,
+}
+
+impl Default for Mmm01 {
+    fn default() -> Self {
+        Self {
+            mapping_enabled: false,
+            ram_enabled: false,
+            rom_bank_low: 0,
+            rom_bank_mid: 0,
+            rom_bank_high: 0,
+            rom_bank_mask: 0,
+            ram_bank_low: 0,
+            ram_bank_high: 0,
+            ram_bank_mask: 0,
+            mbc1_mode: false,
+            mbc1_mode_write_protected: false,
+            multiplex: false,
+            rom_banks: 2,
+            ram_banks: 0,
+            has_battery: false,
+        }
+    }
+}
+
+impl Mmm01 {
+    /// The menu has its own header at the end of the ROM, get the start of the menu's 32 KiB if it says MMM01
+    /// Ordinary ROMs have game data there, so the menu's header has to be valid while the one at the start isn't
+    pub fn get_menu_offset(rom: &[u8]) -> Option<usize> {
+        let menu_offset = rom.len().checked_sub(MENU_SIZE).filter(|&offset| offset > 0)?;
+        if !is_header_valid(rom, menu_offset) || is_header_valid(rom, 0) {
+            return None;
+        }
+
+        match rom.get(menu_offset + CARTRIDGE_TYPE_ADDRESS) {
+            Some(cartridge_type) if MMM01_TYPES.contains(cartridge_type) => Some(menu_offset),
+            _ => None,
+        }
+    }
+
+    /// ROM bank bits 5-6, in multiplex mode they are swapped with the RAM bank low
+    fn get_rom_bank_mid(&self) -> u8 {
+        if self.multiplex { self.ram_bank_low } else { self.rom_bank_mid }
+    }
+
+    fn get_rom_bank(&self, high: bool) -> usize {
+        if !self.mapping_enabled {
+            let bank = if high { MENU_ROM_BANK_1 } else { MENU_ROM_BANK_0 };
+            return bank & (self.rom_banks - 1);
+        }
+
+        let fixed_bits = self.rom_bank_mask << 1;
+        let upper_bits = ((self.get_rom_bank_mid() as usize) << 5) | ((self.rom_bank_high as usize) << 7);
+
+        let bank = if high {
+            // Like on MBC1, bank 0 of the game is translated to 1
+            let game_bank = self.rom_bank_low & !fixed_bits & 0x1F;
+            let low = if game_bank == 0 { self.rom_bank_low | 0x01 } else { self.rom_bank_low };
+            low as usize | upper_bits
+        } else if self.multiplex && !self.mbc1_mode {
+            (self.rom_bank_low & fixed_bits) as usize | ((self.rom_bank_high as usize) << 7)
+        } else {
+            (self.rom_bank_low & fixed_bits) as usize | upper_bits
+        };
+
+        bank & (self.rom_banks - 1)
+    }
+
+    fn get_ram_bank(&self) -> usize {
+        let low = match (self.multiplex, self.mbc1_mode) {
+            (true, _) => self.rom_bank_mid,
+            (false, true) => self.ram_bank_low,
+            // Without MBC1's advanced banking mode only the bits fixed by the menu are used
+            (false, false) => self.ram_bank_low & self.ram_bank_mask,
+        };
+
+        (low | (self.ram_bank_high << 2)) as usize
+    }
+}
+
+impl MemoryOperations for Mmm01 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            // The RAM is disabled or missing, otherwise it would be mapped directly
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        let unlocked = !self.mapping_enabled;
+
+        match address {
+            0x0000..=0x1FFF => {
+                self.enable_ram(value & 0x0F == 0x0A);
+                if unlocked {
+                    self.ram_bank_mask = (value >> 4) & 0b11;
+                    self.mapping_enabled = value & MAPPING_ENABLE != 0;
+                }
+            }
+            0x2000..=0x3FFF => {
+                if unlocked {
+                    self.rom_bank_mid = (value >> 5) & 0b11;
+                }
+                let fixed_bits = self.rom_bank_mask << 1;
+                self.rom_bank_low = ((self.rom_bank_low & fixed_bits) | (value & !fixed_bits)) & 0x1F;
+            }
+            0x4000..=0x5FFF => {
+                self.ram_bank_low = ((self.ram_bank_low & self.ram_bank_mask) | (value & !self.ram_bank_mask)) & 0b11;
+                if unlocked {
+                    self.ram_bank_high = (value >> 2) & 0b11;
+                    self.rom_bank_high = (value >> 4) & 0b11;
+                    self.mbc1_mode_write_protected = value & MBC1_MODE_WRITE_PROTECT != 0;
+                }
+            }
+            0x6000..=0x7FFF => {
+                if !self.mbc1_mode_write_protected {
+                    self.mbc1_mode = value & 0x01 != 0;
+                }
+                if unlocked {
+                    self.rom_bank_mask = (value >> 2) & 0x0F;
+                    self.multiplex = value & MULTIPLEX_ENABLE != 0;
+                }
+            }
+            0xA000..=0xBFFF => {
+                // The RAM is disabled or missing, otherwise it would be mapped directly
+            }
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Mmm01 {
+    /// The MMU passes the sizes of the menu's header
+    fn init(&mut self, rom_size: u8, cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram_banks = get_ram_size(ram_size) / RAM_BANK_SIZE;
+        self.has_battery = cartridge_type == MMM01_RAM_BATTERY;
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        let bank = self.get_rom_bank(address >= 0x4000);
+        bank * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+    }
+
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if !self.ram_enabled || self.ram_banks == 0 {
+            return None;
+        }
+
+        let bank = self.get_ram_bank() % self.ram_banks;
+        Some(bank * RAM_BANK_SIZE + (address as usize - 0xA000))
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.write_byte(0x2000, bank);
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.write_byte(0x4000, bank);
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM enabled: {}", enable);
+        self.ram_enabled = enable;
+    }
+}
+
+impl SaveState for Mmm01 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.mapping_enabled);
+        state.write(&self.ram_enabled);
+        state.write(&self.rom_bank_low);
+        state.write(&self.rom_bank_mid);
+        state.write(&self.rom_bank_high);
+        state.write(&self.rom_bank_mask);
+        state.write(&self.ram_bank_low);
+        state.write(&self.ram_bank_high);
+        state.write(&self.ram_bank_mask);
+        state.write(&self.mbc1_mode);
+        state.write(&self.mbc1_mode_write_protected);
+        state.write(&self.multiplex);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.mapping_enabled = state.read()?;
+        self.ram_enabled = state.read()?;
+        self.rom_bank_low = state.read()?;
+        self.rom_bank_mid = state.read()?;
+        self.rom_bank_high = state.read()?;
+        self.rom_bank_mask = state.read()?;
+        self.ram_bank_low = state.read()?;
+        self.ram_bank_high = state.read()?;
+        self.ram_bank_mask = state.read()?;
+        self.mbc1_mode = state.read()?;
+        self.mbc1_mode_write_protected = state.read()?;
+        self.multiplex = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn mmm01_test() {
+    // 512 KiB with the menu's header at the end
+    let mut rom = vec![0; 32 * ROM_BANK_SIZE];
+    rom[CARTRIDGE_TYPE_ADDRESS] = 0x01;
+    assert_eq!(Mmm01::get_menu_offset(&rom), None);
+    rom[30 * ROM_BANK_SIZE + CARTRIDGE_TYPE_ADDRESS] = 0x0D;
+    assert_eq!(Mmm01::get_menu_offset(&rom), None);
+    crate::mmu::sign_header(&mut rom, 30 * ROM_BANK_SIZE);
+    assert_eq!(Mmm01::get_menu_offset(&rom), Some(30 * ROM_BANK_SIZE));
+
+    let mut mbc = Mmm01::default();
+    mbc.init(0x04, 0x0D, 0x03);
+    assert_eq!(mbc.get_rom_address(0x0000), 30 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x4000), 31 * ROM_BANK_SIZE);
+
+    // The menu selects the game at bank 4 with 4 banks, only ROM bank bits 0 & 1 are left to the game
+    mbc.write_byte(0x2000, 0x04);
+    mbc.write_byte(0x6000, 0b0000_1110 << 2);
+    mbc.write_byte(0x0000, MAPPING_ENABLE);
+    assert_eq!(mbc.get_rom_address(0x0000), 4 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x4000), 5 * ROM_BANK_SIZE);
+
+    mbc.write_byte(0x2000, 0x03);
+    assert_eq!(mbc.get_rom_address(0x4000), 7 * ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x1C);
+    assert_eq!(mbc.get_rom_address(0x4000), 5 * ROM_BANK_SIZE);
+
+    // The game can't change the upper bits anymore, but can use the RAM of its bank
+    mbc.write_byte(0x2000, 0x60);
+    mbc.write_byte(0x6000, 0x00);
+    mbc.write_byte(0x0000, 0x0A);
+    assert_eq!(mbc.get_rom_address(0x4000), 5 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_ram_address(0xA000), Some(0));
+}
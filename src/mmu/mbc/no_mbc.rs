@@ -1,46 +1,49 @@
 use crate::mmu::{MemoryBankControllerOperations, MemoryOperations};
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
-pub struct NoMbc {
-    rom: [u8; 0x4000],
-    ram: [u8; 0x2000],
-}
+/// Cartridge types with a battery buffering the RAM
+const ROM_RAM_BATTERY: u8 = 0x09;
 
-impl Default for NoMbc {
-    fn default() -> Self {
-        Self {
-            rom: [0; 0x4000],
-            ram: [0; 0x2000],
-        }
-    }
+/// ROM only cartridges, optionally with up to 8 KiB of RAM that is always accessible
+/// See: https://gbdev.io/pandocs/nombc.html
+#[derive(Default)]
+pub struct NoMbc {
+    has_ram: bool,
+    has_battery: bool,
 }
 
 impl MemoryOperations for NoMbc {
-    fn read_byte(&self, address: u16) -> u8 {
-        let physical_address = self.calc_physical_rom_address(address);
-
-        if self.rom.len() <= physical_address {
-            panic!("ROM bank not found");
-        }
-
-        self.rom[physical_address]
+    fn read_byte(&self, _address: u16) -> u8 {
+        // Open bus, the ROM and RAM are mapped directly
+        0xFF
     }
 
     fn write_byte(&mut self, _address: u16, _value: u8) {
-        // Do nothing as this is a ROM only cartridge
+        // Do nothing as there are no registers
     }
 }
 
 impl MemoryBankControllerOperations for NoMbc { 
     fn init(&mut self, _rom_size: u8, cartridge_type: u8, ram_size: u8) {
-        assert_eq!(cartridge_type, 0);
+        assert!(matches!(cartridge_type, 0x00 | 0x08 | 0x09), "Not a ROM only cartridge: {:#04X}", cartridge_type);
+        self.has_ram = cartridge_type != 0x00 && ram_size != 0x00;
+        self.has_battery = cartridge_type == ROM_RAM_BATTERY;
     }
-    
-    fn fill_rom_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x4000]) {
-        self.rom.copy_from_slice(data);
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        address as usize
     }
-    
-    fn fill_ram_bank_from_slice(&mut self, bank: u8, data: &[u8; 0x2000]) {
-        self.ram.copy_from_slice(data);
+
+    fn get_ram_address(&self, address: u16) -> Option<usize> {
+        if self.has_ram {
+            Some(address as usize - 0xA000)
+        } else {
+            None
+        }
+    }
+
+    fn has_battery(&self) -> bool {
+        self.has_battery
     }
     
     fn switch_rom_bank(&mut self, _bank: u8) {
@@ -52,11 +55,17 @@ impl MemoryBankControllerOperations for NoMbc {
     }
     
     fn enable_ram(&mut self, enable: bool) {
-        assert!(!enable, "RAM can not be enabled in ROM only cartridge");
+        assert!(!enable || self.has_ram, "RAM can not be enabled in ROM only cartridge");
     }
-    
-    fn is_advanced_banking_mode(&self) -> bool {
-        false
+}
+
+impl SaveState for NoMbc {
+    fn save_state(&self, _state: &mut StateWriter) {
+        // There are no registers
+    }
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
     }
 }
 
@@ -90,6 +99,15 @@ mod tests {
         no_mbc.enable_ram(false); // Should pass
     }
 
+    #[test]
+    fn test_rom_ram_battery() {
+        let mut no_mbc = NoMbc::default();
+        no_mbc.init(0, 0x09, 0x02);
+        assert!(no_mbc.has_battery());
+        assert_eq!(no_mbc.get_ram_address(0xA123), Some(0x123));
+        no_mbc.enable_ram(true); // Should pass
+    }
+
     #[test]
     #[should_panic(expected = "RAM can not be enabled in ROM only cartridge")]
     fn test_ram_enable_should_panic() {
@@ -0,0 +1,368 @@
+use crate::mmu::{get_ram_size, get_rom_size, MemoryBankControllerOperations, MemoryOperations, RAM_BANK_SIZE, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+use image_source::{ImageSource, TestPattern, SENSOR_HEIGHT, SENSOR_WIDTH};
+
+pub mod image_source;
+
+/// Setting bit 4 of the RAM bank number maps the camera registers to 0xA000 to 0xBFFF
+const REGISTER_BANK_FLAG: u8 = 0x10;
+/// The registers are mirrored every 0x80 bytes
+const REGISTER_MIRROR_SIZE: u16 = 0x80;
+const REGISTER_COUNT: usize = 0x36;
+
+/// Registers of the M64282FP sensor, selected via the lower bits of the address
+/// See: https://gbdev.io/pandocs/Gameboy_Camera.html#camera-registers
+const CONTROL_REGISTER: usize = 0x00;
+const PARAMETER_REGISTER: usize = 0x01;
+const EXPOSURE_HIGH_REGISTER: usize = 0x02;
+const EXPOSURE_LOW_REGISTER: usize = 0x03;
+const EDGE_REGISTER: usize = 0x04;
+/// 4x4 matrix of 3 thresholds each, used to dither the image to 4 shades
+const DITHER_MATRIX_START: usize = 0x06;
+
+/// Bit 0 of A000 starts a capture and stays set while the capture is in progress
+const CONTROL_CAPTURE: u8 = 0b0000_0001;
+/// Only bits 0-2 of A000 are writable
+const CONTROL_MASK: u8 = 0b0000_0111;
+/// Bits of A001
+const PARAMETER_GAIN_MASK: u8 = 0b0001_1111;
+const PARAMETER_EDGE_SHIFT: u8 = 5;
+/// Bits of A004
+const EDGE_INVERT: u8 = 0b0000_1000;
+const EDGE_RATIO_SHIFT: u8 = 4;
+/// Edge enhancement ratio selected by bits 4-6 of A004
+const EDGE_RATIOS: [f32; 8] = [0.5, 0.75, 1.0, 1.25, 2.0, 3.0, 4.0, 5.0];
+
+/// Exposure time that keeps the brightness of the source image as is
+const EXPOSURE_NEUTRAL: f32 = 0x1000 as f32;
+/// Every step of the gain increases the brightness by this fraction
+const GAIN_STEP: f32 = 1.0 / 16.0;
+
+/// The captured image is stored as 16x14 tiles in RAM bank 0, starting at 0xA100
+const IMAGE_ADDRESS: usize = 0x0100;
+const TILES_PER_ROW: usize = SENSOR_WIDTH / 8;
+const TILE_SIZE: usize = 16;
+
+/// The Game Boy Camera (Pocket Camera) mapper with its image sensor
+/// Instead of a real sensor, the images come from an `ImageSource`
+/// See: https://gbdev.io/pandocs/Gameboy_Camera.html
+pub struct PocketCamera {
+    rom_bank_number: u8,
+    rom_banks: usize,
+    /// Selects one of the 16 RAM banks or the camera registers
+    ram_bank_number: u8,
+    /// Reads are always possible, but writes have to be enabled
+    ram_write_enabled: bool,
+    /// The camera uses its RAM for the captured image, so it isn't mapped directly
+    ram: Vec<u8>,
+    registers: [u8; REGISTER_COUNT],
+    image_source: Box<dyn ImageSource>,
+}
+
+impl Default for PocketCamera {
+    fn default() -> Self {
+        Self {
+            rom_bank_number: 1,
+            rom_banks: 2,
+            ram_bank_number: 0,
+            ram_write_enabled: false,
+            ram: vec![0; RAM_BANK_SIZE],
+            registers: [0; REGISTER_COUNT],
+            image_source: Box::new(TestPattern::gradient()),
+        }
+    }
+}
+
+impl PocketCamera {
+    fn is_register_bank(&self) -> bool {
+        self.ram_bank_number & REGISTER_BANK_FLAG != 0
+    }
+
+    fn get_ram_index(&self, address: u16) -> usize {
+        let bank = (self.ram_bank_number & 0x0F) as usize;
+        (bank * RAM_BANK_SIZE + (address as usize - 0xA000)) % self.ram.len()
+    }
+
+    /// Capture an image and store it in RAM
+    /// A real capture takes a few frames, here it is done as soon as it is started
+    fn capture(&mut self) {
+        let frame = self.image_source.capture();
+        let exposed = self.expose(&frame);
+
+        let pixel = |x: usize, y: usize, dx: isize, dy: isize| {
+            let x = x.saturating_add_signed(dx).min(SENSOR_WIDTH - 1);
+            let y = y.saturating_add_signed(dy).min(SENSOR_HEIGHT - 1);
+            exposed[y * SENSOR_WIDTH + x]
+        };
+
+        let edge_mode = (self.registers[PARAMETER_REGISTER] >> PARAMETER_EDGE_SHIFT) & 0b11;
+        let edge_ratio = EDGE_RATIOS[(self.registers[EDGE_REGISTER] >> EDGE_RATIO_SHIFT) as usize & 0b111];
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let center = pixel(x, y, 0, 0);
+                let horizontal = 2.0 * center - pixel(x, y, -1, 0) - pixel(x, y, 1, 0);
+                let vertical = 2.0 * center - pixel(x, y, 0, -1) - pixel(x, y, 0, 1);
+
+                // Edge enhancement adds the difference to the neighbouring pixels
+                let edge = match edge_mode {
+                    0b01 => horizontal,
+                    0b10 => vertical,
+                    0b11 => horizontal + vertical,
+                    _ => 0.0,
+                };
+
+                let value = (center + edge * edge_ratio).clamp(0.0, 255.0) as u8;
+                let shade = self.dither(x, y, value);
+                self.write_pixel(x, y, shade);
+            }
+        }
+    }
+
+    /// Apply the exposure time, gain & inversion to the source image
+    /// The analog parts of the sensor are approximated by scaling the brightness linearly
+    fn expose(&self, frame: &[u8]) -> Vec<f32> {
+        let exposure = u16::from_be_bytes([
+            self.registers[EXPOSURE_HIGH_REGISTER],
+            self.registers[EXPOSURE_LOW_REGISTER],
+        ]);
+        let gain = self.registers[PARAMETER_REGISTER] & PARAMETER_GAIN_MASK;
+        let scale = exposure as f32 / EXPOSURE_NEUTRAL * (1.0 + gain as f32 * GAIN_STEP);
+        let invert = self.registers[EDGE_REGISTER] & EDGE_INVERT != 0;
+
+        frame
+            .iter()
+            .map(|&pixel| {
+                let value = (pixel as f32 * scale).min(255.0);
+                if invert { 255.0 - value } else { value }
+            })
+            .collect()
+    }
+
+    /// Compare the value against the thresholds of the matrix entry for the pixel
+    /// Values below the first threshold are black, values above the last one white
+    fn dither(&self, x: usize, y: usize, value: u8) -> u8 {
+        let entry = DITHER_MATRIX_START + ((y % 4) * 4 + x % 4) * 3;
+        let thresholds = &self.registers[entry..entry + 3];
+
+        match thresholds.iter().position(|&threshold| value < threshold) {
+            Some(index) => 3 - index as u8,
+            None => 0,
+        }
+    }
+
+    /// Store the shade of a pixel in the 2bpp tile data of RAM bank 0
+    fn write_pixel(&mut self, x: usize, y: usize, shade: u8) {
+        let tile = (y / 8) * TILES_PER_ROW + x / 8;
+        let index = IMAGE_ADDRESS + tile * TILE_SIZE + (y % 8) * 2;
+        let bit = 0x80 >> (x % 8);
+
+        for (plane, byte) in self.ram[index..index + 2].iter_mut().enumerate() {
+            if (shade >> plane) & 1 != 0 {
+                *byte |= bit;
+            } else {
+                *byte &= !bit;
+            }
+        }
+    }
+}
+
+impl MemoryOperations for PocketCamera {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            // Only A000 can be read, a capture is never in progress
+            0xA000..=0xBFFF if self.is_register_bank() => match address % REGISTER_MIRROR_SIZE {
+                0x00 => self.registers[CONTROL_REGISTER] & CONTROL_MASK,
+                _ => 0x00,
+            },
+            0xA000..=0xBFFF => self.ram[self.get_ram_index(address)],
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF => self.enable_ram(value & 0x0F == 0x0A),
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x5FFF => self.switch_ram_bank(value),
+            0x6000..=0x7FFF => {}
+            0xA000..=0xBFFF if self.is_register_bank() => {
+                let register = (address % REGISTER_MIRROR_SIZE) as usize;
+                match register {
+                    CONTROL_REGISTER => {
+                        self.registers[CONTROL_REGISTER] = value & CONTROL_MASK;
+                        if value & CONTROL_CAPTURE != 0 {
+                            self.capture();
+                            self.registers[CONTROL_REGISTER] &= !CONTROL_CAPTURE;
+                        }
+                    }
+                    register if register < REGISTER_COUNT => self.registers[register] = value,
+                    _ => {}
+                }
+            }
+            0xA000..=0xBFFF if self.ram_write_enabled => {
+                let index = self.get_ram_index(address);
+                self.ram[index] = value;
+            }
+            0xA000..=0xBFFF => {}
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for PocketCamera {
+    fn init(&mut self, rom_size: u8, _cartridge_type: u8, ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+        self.ram = vec![0; get_ram_size(ram_size).max(RAM_BANK_SIZE)];
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => {
+                let bank = self.rom_bank_number as usize % self.rom_banks;
+                bank * ROM_BANK_SIZE + (address as usize - 0x4000)
+            }
+        }
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        // The RAM is managed by the camera itself
+        None
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        Some(&self.ram)
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram)
+    }
+
+    fn set_image_source(&mut self, source: Box<dyn ImageSource>) {
+        self.image_source = source;
+    }
+
+    /// Unlike most MBCs, bank 0 can be mapped to 0x4000 to 0x7FFF
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_number = bank & 0x3F;
+    }
+
+    fn switch_ram_bank(&mut self, bank: u8) {
+        log::debug!("Switching RAM bank to {}", bank);
+        self.ram_bank_number = bank & 0x1F;
+    }
+
+    fn enable_ram(&mut self, enable: bool) {
+        log::debug!("RAM writes enabled: {}", enable);
+        self.ram_write_enabled = enable;
+    }
+}
+
+impl SaveState for PocketCamera {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.rom_bank_number);
+        state.write(&self.ram_bank_number);
+        state.write(&self.ram_write_enabled);
+        state.write_bytes(&self.ram);
+        state.write(&self.registers);
+        self.image_source.save_state(state);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.rom_bank_number = state.read()?;
+        self.ram_bank_number = state.read()?;
+        self.ram_write_enabled = state.read()?;
+        state.read_bytes(&mut self.ram)?;
+        self.registers = state.read()?;
+        self.image_source.load_state(state)
+    }
+}
+
+/// Always returns the same pixel value
+#[cfg(test)]
+struct FlatImage(u8);
+
+#[cfg(test)]
+impl SaveState for FlatImage {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl ImageSource for FlatImage {
+    fn capture(&mut self) -> Vec<u8> {
+        vec![self.0; SENSOR_WIDTH * SENSOR_HEIGHT]
+    }
+}
+
+#[test]
+pub fn pocket_camera_ram_test() {
+    let mut camera = PocketCamera::default();
+    camera.init(0x05, 0xFC, 0x04);
+
+    // Reads are always possible, writes only after enabling them
+    camera.write_byte(0x4000, 0x03);
+    camera.write_byte(0xA000, 0x12);
+    assert_eq!(camera.read_byte(0xA000), 0x00);
+    camera.write_byte(0x0000, 0x0A);
+    camera.write_byte(0xA000, 0x12);
+    assert_eq!(camera.read_byte(0xA000), 0x12);
+    assert_eq!(camera.get_internal_ram().unwrap()[3 * RAM_BANK_SIZE], 0x12);
+
+    // Bit 4 maps the registers, only A000 is readable
+    camera.write_byte(0x4000, 0x10);
+    camera.write_byte(0xA001, 0xFF);
+    assert_eq!(camera.registers[PARAMETER_REGISTER], 0xFF);
+    assert_eq!(camera.read_byte(0xA001), 0x00);
+    camera.write_byte(0xA080, 0xFE);
+    assert_eq!(camera.read_byte(0xA000), 0x06);
+
+    // ROM bank 0 can be selected
+    camera.write_byte(0x2000, 0x00);
+    assert_eq!(camera.get_rom_address(0x4000), 0);
+}
+
+#[test]
+pub fn pocket_camera_capture_test() {
+    let mut camera = PocketCamera::default();
+    camera.init(0x05, 0xFC, 0x04);
+    camera.set_image_source(Box::new(FlatImage(0x50)));
+
+    camera.write_byte(0x4000, 0x10);
+    camera.write_byte(0xA002, 0x10);
+    camera.write_byte(0xA003, 0x00);
+
+    // Thresholds between black, dark grey, light grey & white
+    // The second row of the matrix is darker, so the flat image is dithered
+    for entry in 0..16 {
+        let thresholds: [u8; 3] = if entry / 4 == 1 { [0x90, 0xA0, 0xB0] } else { [0x40, 0x60, 0xA0] };
+        for (i, &threshold) in thresholds.iter().enumerate() {
+            camera.write_byte(0xA006 + entry * 3 + i as u16, threshold);
+        }
+    }
+
+    camera.write_byte(0xA000, CONTROL_CAPTURE);
+    assert_eq!(camera.read_byte(0xA000) & CONTROL_CAPTURE, 0);
+
+    // 0x50 is dark grey with the thresholds of the first row, but black with those of the second one
+    let ram = camera.get_internal_ram().unwrap();
+    assert_eq!(ram[IMAGE_ADDRESS..IMAGE_ADDRESS + 4], [0x00, 0xFF, 0xFF, 0xFF]);
+    // The last tile is dithered the same way
+    let last_tile = IMAGE_ADDRESS + (SENSOR_WIDTH / 8) * (SENSOR_HEIGHT / 8) * TILE_SIZE - TILE_SIZE;
+    assert_eq!(ram[last_tile..last_tile + 4], [0x00, 0xFF, 0xFF, 0xFF]);
+
+    // Inverting the image makes the first row white
+    camera.write_byte(0xA004, EDGE_INVERT);
+    camera.write_byte(0xA000, CONTROL_CAPTURE);
+    let ram = camera.get_internal_ram().unwrap();
+    assert_eq!(ram[IMAGE_ADDRESS..IMAGE_ADDRESS + 2], [0x00, 0x00]);
+}
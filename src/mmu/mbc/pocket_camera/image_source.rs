@@ -0,0 +1,281 @@
+use std::path::Path;
+
+use macroquad::texture::Image;
+
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Size of the image the camera sensor captures
+/// The real sensor has a few more rows, but only 128x112 pixels end up in the RAM
+pub const SENSOR_WIDTH: usize = 128;
+pub const SENSOR_HEIGHT: usize = 112;
+
+/// Provides the images the camera sensor sees, e.g. a file or a test pattern
+/// The state holds the position within the images, so loading it captures the same images again
+pub trait ImageSource: SaveState {
+    /// Get the next frame as `SENSOR_WIDTH` * `SENSOR_HEIGHT` grayscale pixels, 0 is black and 255 white
+    fn capture(&mut self) -> Vec<u8>;
+}
+
+/// Synthetic images that move by one pixel with every capture
+pub enum TestPattern {
+    /// Diagonal gradient from black to white
+    Gradient { offset: usize },
+    /// Black and white squares of 8x8 pixels
+    Checkerboard { offset: usize },
+}
+
+impl TestPattern {
+    pub fn gradient() -> Self {
+        TestPattern::Gradient { offset: 0 }
+    }
+
+    pub fn checkerboard() -> Self {
+        TestPattern::Checkerboard { offset: 0 }
+    }
+}
+
+impl ImageSource for TestPattern {
+    fn capture(&mut self) -> Vec<u8> {
+        let mut frame = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+
+        for y in 0..SENSOR_HEIGHT {
+            for x in 0..SENSOR_WIDTH {
+                let pixel = match *self {
+                    TestPattern::Gradient { offset } => {
+                        let position = (x + y + offset) % (SENSOR_WIDTH + SENSOR_HEIGHT);
+                        (position * 0xFF / (SENSOR_WIDTH + SENSOR_HEIGHT - 1)) as u8
+                    }
+                    TestPattern::Checkerboard { offset } => {
+                        if ((x + offset) / 8 + y / 8) % 2 == 0 { 0xFF } else { 0x00 }
+                    }
+                };
+                frame.push(pixel);
+            }
+        }
+
+        match self {
+            TestPattern::Gradient { offset } | TestPattern::Checkerboard { offset } => *offset += 1,
+        }
+
+        frame
+    }
+}
+
+impl SaveState for TestPattern {
+    fn save_state(&self, state: &mut StateWriter) {
+        match self {
+            TestPattern::Gradient { offset } | TestPattern::Checkerboard { offset } => state.write(&(*offset as u64)),
+        }
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        let loaded = state.read::<u64>()? as usize;
+        match self {
+            TestPattern::Gradient { offset } | TestPattern::Checkerboard { offset } => *offset = loaded,
+        }
+        Ok(())
+    }
+}
+
+/// The same image for every capture
+pub struct StillImage {
+    frame: Vec<u8>,
+}
+
+impl StillImage {
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        Ok(StillImage { frame: load_frame(path)? })
+    }
+}
+
+impl ImageSource for StillImage {
+    fn capture(&mut self) -> Vec<u8> {
+        self.frame.clone()
+    }
+}
+
+impl SaveState for StillImage {
+    fn save_state(&self, _state: &mut StateWriter) {}
+
+    fn load_state(&mut self, _state: &mut StateReader) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// A new image for every capture, starts over after the last one
+pub struct FrameSequence {
+    frames: Vec<Vec<u8>>,
+    index: usize,
+}
+
+impl FrameSequence {
+    pub fn from_files(paths: &[&Path]) -> Result<Self, String> {
+        if paths.is_empty() {
+            return Err("A frame sequence needs at least one frame".to_string());
+        }
+
+        let frames = paths.iter().map(|path| load_frame(path)).collect::<Result<_, _>>()?;
+        Ok(FrameSequence { frames, index: 0 })
+    }
+}
+
+impl ImageSource for FrameSequence {
+    fn capture(&mut self) -> Vec<u8> {
+        let frame = self.frames[self.index].clone();
+        self.index = (self.index + 1) % self.frames.len();
+        frame
+    }
+}
+
+impl SaveState for FrameSequence {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&(self.index as u64));
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.index = state.read::<u64>()? as usize % self.frames.len();
+        Ok(())
+    }
+}
+
+/// Load a PGM or PNG file and scale it to the sensor size
+pub fn load_frame(path: &Path) -> Result<Vec<u8>, String> {
+    let data = std::fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+
+    let (width, height, pixels) = if data.starts_with(b"P2") || data.starts_with(b"P5") {
+        decode_pgm(&data)?
+    } else {
+        // Let macroquad figure out the format, e.g. PNG
+        let image = Image::from_file_with_format(&data, None)
+            .map_err(|e| format!("Unable to decode {}: {}", path.display(), e))?;
+        let pixels = image
+            .bytes
+            .chunks_exact(4)
+            .map(|rgba| (0.299 * rgba[0] as f32 + 0.587 * rgba[1] as f32 + 0.114 * rgba[2] as f32) as u8)
+            .collect();
+        (image.width as usize, image.height as usize, pixels)
+    };
+
+    if width == 0 || height == 0 {
+        return Err(format!("{} is empty", path.display()));
+    }
+
+    Ok(scale_to_sensor(width, height, &pixels))
+}
+
+/// Decode a binary (P5) or ASCII (P2) PGM into its width, height and 8-bit pixels
+/// See: https://netpbm.sourceforge.net/doc/pgm.html
+fn decode_pgm(data: &[u8]) -> Result<(usize, usize, Vec<u8>), String> {
+    let mut position = 0;
+
+    // The header consists of the magic number, width, height & maximum value, comments start with #
+    let next_token = |position: &mut usize| -> Result<String, String> {
+        loop {
+            while data.get(*position).is_some_and(|byte| byte.is_ascii_whitespace()) {
+                *position += 1;
+            }
+            if data.get(*position) == Some(&b'#') {
+                while data.get(*position).is_some_and(|&byte| byte != b'\n') {
+                    *position += 1;
+                }
+            } else {
+                break;
+            }
+        }
+
+        let start = *position;
+        while data.get(*position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+            *position += 1;
+        }
+
+        if start == *position {
+            return Err("Unexpected end of PGM".to_string());
+        }
+        Ok(String::from_utf8_lossy(&data[start..*position]).into_owned())
+    };
+
+    let magic = next_token(&mut position)?;
+    let number = |position: &mut usize| -> Result<usize, String> {
+        let token = next_token(position)?;
+        token.parse().map_err(|_| format!("Invalid number in PGM: {}", token))
+    };
+    let width = number(&mut position)?;
+    let height = number(&mut position)?;
+    let max_value = number(&mut position)?;
+
+    if width == 0 || height == 0 || !(1..=0xFFFF).contains(&max_value) {
+        return Err(format!("Invalid PGM header: {}x{} with maximum {}", width, height, max_value));
+    }
+
+    let samples: Vec<usize> = match magic.as_str() {
+        "P5" => {
+            // A single whitespace separates the header from the binary data
+            let start = position + 1;
+            let bytes_per_sample = if max_value < 0x100 { 1 } else { 2 };
+            let end = start + width * height * bytes_per_sample;
+            let Some(raster) = data.get(start..end) else {
+                return Err("PGM is missing pixels".to_string());
+            };
+
+            raster
+                .chunks_exact(bytes_per_sample)
+                .map(|sample| sample.iter().fold(0, |value, &byte| (value << 8) | byte as usize))
+                .collect()
+        }
+        _ => (0..width * height).map(|_| number(&mut position)).collect::<Result<_, _>>()?,
+    };
+
+    let pixels = samples
+        .into_iter()
+        .map(|sample| (sample.min(max_value) * 0xFF / max_value) as u8)
+        .collect();
+
+    Ok((width, height, pixels))
+}
+
+/// Scale a grayscale image to the sensor size using the nearest pixel
+fn scale_to_sensor(width: usize, height: usize, pixels: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(SENSOR_WIDTH * SENSOR_HEIGHT);
+
+    for y in 0..SENSOR_HEIGHT {
+        for x in 0..SENSOR_WIDTH {
+            let source_x = x * width / SENSOR_WIDTH;
+            let source_y = y * height / SENSOR_HEIGHT;
+            frame.push(pixels[source_y * width + source_x]);
+        }
+    }
+
+    frame
+}
+
+#[test]
+pub fn pgm_test() {
+    let ascii = b"P2\n# comment\n2 2\n15\n0 15\n5 10\n";
+    assert_eq!(decode_pgm(ascii), Ok((2, 2, vec![0x00, 0xFF, 0x55, 0xAA])));
+
+    let binary = [b"P5 2 1 255\n".as_slice(), &[0x12, 0x34]].concat();
+    assert_eq!(decode_pgm(&binary), Ok((2, 1, vec![0x12, 0x34])));
+    assert!(decode_pgm(b"P5 2 2 255\n\x00").is_err());
+
+    // Every source pixel covers a quarter of the sensor
+    let frame = scale_to_sensor(2, 2, &[0x00, 0xFF, 0x55, 0xAA]);
+    assert_eq!(frame[0], 0x00);
+    assert_eq!(frame[SENSOR_WIDTH - 1], 0xFF);
+    assert_eq!(frame[SENSOR_WIDTH * (SENSOR_HEIGHT - 1)], 0x55);
+    assert_eq!(frame[SENSOR_WIDTH * SENSOR_HEIGHT - 1], 0xAA);
+}
+
+#[test]
+pub fn test_pattern_state_test() {
+    let mut pattern = TestPattern::gradient();
+    pattern.capture();
+
+    let mut state = StateWriter::new();
+    pattern.save_state(&mut state);
+    let expected = pattern.capture();
+
+    // Loading the state captures the same image again
+    let state = state.into_bytes();
+    pattern.load_state(&mut StateReader::new(&state)).unwrap();
+    assert_eq!(pattern.capture(), expected);
+}
@@ -0,0 +1,163 @@
+use crate::mmu::{MemoryBankControllerOperations, MemoryOperations, NINTENDO_LOGO, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+const LOGO_START: u16 = 0x0104;
+
+/// The address lines of the header page are scrambled
+const SCRAMBLED_PAGE_START: u16 = 0x0100;
+const SCRAMBLED_PAGE_END: u16 = 0x01FF;
+
+/// The base bank & mask can only be changed while bits 4 & 5 of the base bank are set
+const BASE_BANK_UNLOCKED: u8 = 0b0011_0000;
+
+/// Unlicensed mapper of Sachen's MMC1 & MMC2 cartridges
+/// Games are selected by a base bank, the mask decides which bits of the ROM bank it overrides
+/// The cartridges show their own logo to the boot ROM for the first reads of the header, that lock isn't emulated
+/// See: https://gbdev.gg8.se/wiki/articles/Memory_Bank_Controllers#Sachen
+pub struct Sachen {
+    base_bank: u8,
+    mask: u8,
+    rom_bank_number: u8,
+}
+
+impl Default for Sachen {
+    fn default() -> Self {
+        Self {
+            base_bank: 0xFF,
+            mask: 0x00,
+            rom_bank_number: 1,
+        }
+    }
+}
+
+impl Sachen {
+    /// The Nintendo logo is only in the header once the address lines are unscrambled
+    pub fn is_sachen(rom: &[u8]) -> bool {
+        let logo_end = LOGO_START + NINTENDO_LOGO.len() as u16;
+        if rom.len() <= SCRAMBLED_PAGE_END as usize {
+            return false;
+        }
+
+        let unscrambled_logo_matches = (LOGO_START..logo_end)
+            .zip(NINTENDO_LOGO)
+            .all(|(address, byte)| rom[Self::unscramble(address) as usize] == byte);
+
+        unscrambled_logo_matches && rom[LOGO_START as usize..logo_end as usize] != NINTENDO_LOGO
+    }
+
+    /// Swap address lines A0 & A6 and A1 & A4 of addresses in the header page
+    pub fn unscramble(address: u16) -> u16 {
+        if !(SCRAMBLED_PAGE_START..=SCRAMBLED_PAGE_END).contains(&address) {
+            return address;
+        }
+
+        (address & 0xFFAC)
+            | ((address & 0x40) >> 6)
+            | ((address & 0x10) >> 3)
+            | ((address & 0x02) << 3)
+            | ((address & 0x01) << 6)
+    }
+
+    fn is_base_bank_unlocked(&self) -> bool {
+        self.base_bank & BASE_BANK_UNLOCKED == BASE_BANK_UNLOCKED
+    }
+}
+
+impl MemoryOperations for Sachen {
+    fn read_byte(&self, _address: u16) -> u8 {
+        // There is no RAM
+        0xFF
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0x0000..=0x1FFF if self.is_base_bank_unlocked() => self.base_bank = value,
+            0x2000..=0x3FFF => self.switch_rom_bank(value),
+            0x4000..=0x5FFF if self.is_base_bank_unlocked() => self.mask = value,
+            _ => {}
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Sachen {
+    fn init(&mut self, _rom_size: u8, _cartridge_type: u8, _ram_size: u8) {
+        // The header can't be trusted, the MMU mirrors the banks based on the actual ROM size
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        let address = Self::unscramble(address);
+
+        let bank = match address {
+            0x0000..=0x3FFF => self.base_bank & self.mask,
+            _ => (self.rom_bank_number & !self.mask) | (self.base_bank & self.mask),
+        };
+
+        bank as usize * ROM_BANK_SIZE + (address as usize % ROM_BANK_SIZE)
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        None
+    }
+
+    fn is_rom_page_linear(&self, address: u16) -> bool {
+        !(SCRAMBLED_PAGE_START..=SCRAMBLED_PAGE_END).contains(&address)
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.rom_bank_number = if bank == 0 { 1 } else { bank };
+    }
+
+    fn switch_ram_bank(&mut self, _bank: u8) {
+        // There is no RAM
+    }
+
+    fn enable_ram(&mut self, _enable: bool) {
+        // There is no RAM
+    }
+}
+
+impl SaveState for Sachen {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.base_bank);
+        state.write(&self.mask);
+        state.write(&self.rom_bank_number);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.base_bank = state.read()?;
+        self.mask = state.read()?;
+        self.rom_bank_number = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn sachen_test() {
+    let mut rom = vec![0; 2 * ROM_BANK_SIZE];
+    rom[LOGO_START as usize..LOGO_START as usize + NINTENDO_LOGO.len()].copy_from_slice(&NINTENDO_LOGO);
+    assert!(!Sachen::is_sachen(&rom));
+
+    let mut scrambled = vec![0; 2 * ROM_BANK_SIZE];
+    for address in SCRAMBLED_PAGE_START..=SCRAMBLED_PAGE_END {
+        scrambled[Sachen::unscramble(address) as usize] = rom[address as usize];
+    }
+    assert!(Sachen::is_sachen(&scrambled));
+    assert_eq!(Sachen::unscramble(0x0147), 0x0155);
+
+    // The menu selects the game at bank 0x20 with 8 banks, which also locks the base bank & mask
+    let mut mbc = Sachen::default();
+    mbc.write_byte(0x4000, 0xF8);
+    mbc.write_byte(0x0000, 0x20);
+    assert_eq!(mbc.get_rom_address(0x0000), 0x20 * ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x00);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x21 * ROM_BANK_SIZE);
+    mbc.write_byte(0x2000, 0x45);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x25 * ROM_BANK_SIZE);
+
+    // The game can't leave its banks
+    mbc.write_byte(0x0000, 0x00);
+    mbc.write_byte(0x4000, 0x00);
+    assert_eq!(mbc.get_rom_address(0x0000), 0x20 * ROM_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x0147), 0x20 * ROM_BANK_SIZE + 0x0155);
+}
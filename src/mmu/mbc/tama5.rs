@@ -0,0 +1,201 @@
+use crate::mmu::{get_rom_size, MemoryBankControllerOperations, MemoryOperations, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// Registers written via A000 after selecting them via A001
+/// See: https://gbdev.io/pandocs/TAMA5.html
+const REGISTER_ROM_BANK_LOW: u8 = 0x0;
+const REGISTER_ROM_BANK_HIGH: u8 = 0x1;
+const REGISTER_WRITE_LOW: u8 = 0x4;
+const REGISTER_WRITE_HIGH: u8 = 0x5;
+/// Bit 0 is bit 4 of the address, bits 1-3 are the command
+const REGISTER_COMMAND: u8 = 0x6;
+/// Writing the lower 4 bits of the address executes the command
+const REGISTER_ADDRESS_LOW: u8 = 0x7;
+const REGISTER_ACTIVE: u8 = 0xA;
+const REGISTER_READ_LOW: u8 = 0xC;
+const REGISTER_READ_HIGH: u8 = 0xD;
+const REGISTER_COUNT: usize = 0x10;
+
+const COMMAND_WRITE_RAM: u8 = 0x0;
+const COMMAND_READ_RAM: u8 = 0x1;
+
+/// The TAMA5 has 32 bytes of battery buffered RAM
+const RAM_SIZE: usize = 0x20;
+/// The upper 4 bits of registers read via A000 are always set
+const READ_UNUSED: u8 = 0xF0;
+/// Value of the active register once the TAMA5 is ready
+const ACTIVE_VALUE: u8 = 0xF1;
+
+/// Bandai's TAMA5 of Tamagotchi 3, everything is accessed through two registers at A000 & A001
+/// The TAMA6 clock chip next to it isn't emulated, its commands are ignored
+/// See: https://gbdev.io/pandocs/TAMA5.html
+pub struct Tama5 {
+    /// The register A000 reads & writes
+    selected_register: u8,
+    registers: [u8; REGISTER_COUNT],
+    rom_banks: usize,
+    ram: Vec<u8>,
+    /// The byte read by the last read command
+    read_value: u8,
+}
+
+impl Default for Tama5 {
+    fn default() -> Self {
+        Self {
+            selected_register: 0,
+            registers: [0; REGISTER_COUNT],
+            rom_banks: 2,
+            ram: vec![0; RAM_SIZE],
+            read_value: 0,
+        }
+    }
+}
+
+impl Tama5 {
+    fn get_rom_bank(&self) -> usize {
+        let bank = self.registers[REGISTER_ROM_BANK_LOW as usize]
+            | ((self.registers[REGISTER_ROM_BANK_HIGH as usize] & 0x01) << 4);
+        bank as usize % self.rom_banks
+    }
+
+    fn write_register(&mut self, value: u8) {
+        let register = self.selected_register as usize % REGISTER_COUNT;
+        self.registers[register] = value & 0x0F;
+
+        if self.selected_register == REGISTER_ADDRESS_LOW {
+            self.execute_command();
+        }
+    }
+
+    fn execute_command(&mut self) {
+        let command_register = self.registers[REGISTER_COMMAND as usize];
+        let address = (((command_register & 0x01) << 4) | self.registers[REGISTER_ADDRESS_LOW as usize]) as usize;
+        let value = (self.registers[REGISTER_WRITE_HIGH as usize] << 4) | self.registers[REGISTER_WRITE_LOW as usize];
+
+        match command_register >> 1 {
+            COMMAND_WRITE_RAM => self.ram[address] = value,
+            COMMAND_READ_RAM => self.read_value = self.ram[address],
+            command => log::debug!("Ignoring TAMA5 command {:#03X} at {:#04X}", command, address),
+        }
+    }
+}
+
+impl MemoryOperations for Tama5 {
+    fn read_byte(&self, address: u16) -> u8 {
+        match address {
+            0xA000..=0xBFFF if address & 0x01 == 0 => match self.selected_register {
+                REGISTER_ACTIVE => ACTIVE_VALUE,
+                REGISTER_READ_LOW => READ_UNUSED | (self.read_value & 0x0F),
+                REGISTER_READ_HIGH => READ_UNUSED | (self.read_value >> 4),
+                _ => READ_UNUSED,
+            },
+            0xA000..=0xBFFF => 0xFF,
+            _ => panic!("Invalid address: {:#06X}", address),
+        }
+    }
+
+    fn write_byte(&mut self, address: u16, value: u8) {
+        match address {
+            0xA000..=0xBFFF if address & 0x01 == 0 => self.write_register(value),
+            0xA000..=0xBFFF => self.selected_register = value & 0x0F,
+            // The TAMA5 ignores the usual MBC registers
+            _ => {}
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for Tama5 {
+    fn init(&mut self, rom_size: u8, _cartridge_type: u8, _ram_size: u8) {
+        self.rom_banks = (get_rom_size(rom_size) / ROM_BANK_SIZE).max(2);
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        match address {
+            0x0000..=0x3FFF => address as usize,
+            _ => self.get_rom_bank() * ROM_BANK_SIZE + (address as usize - 0x4000),
+        }
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        // The RAM is only accessible via the registers
+        None
+    }
+
+    fn has_battery(&self) -> bool {
+        true
+    }
+
+    fn get_internal_ram(&self) -> Option<&[u8]> {
+        Some(&self.ram)
+    }
+
+    fn get_internal_ram_mut(&mut self) -> Option<&mut [u8]> {
+        Some(&mut self.ram)
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.registers[REGISTER_ROM_BANK_LOW as usize] = bank & 0x0F;
+        self.registers[REGISTER_ROM_BANK_HIGH as usize] = (bank >> 4) & 0x01;
+    }
+
+    fn switch_ram_bank(&mut self, _bank: u8) {
+        // There are no RAM banks
+    }
+
+    fn enable_ram(&mut self, _enable: bool) {
+        // The RAM is always accessible via the registers
+    }
+}
+
+impl SaveState for Tama5 {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.selected_register);
+        state.write(&self.registers);
+        state.write_bytes(&self.ram);
+        state.write(&self.read_value);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.selected_register = state.read()?;
+        self.registers = state.read()?;
+        state.read_bytes(&mut self.ram)?;
+        self.read_value = state.read()?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Tama5 {
+    fn write_to_register(&mut self, register: u8, value: u8) {
+        self.write_byte(0xA001, register);
+        self.write_byte(0xA000, value);
+    }
+}
+
+#[test]
+pub fn tama5_test() {
+    let mut mbc = Tama5::default();
+    mbc.init(0x04, 0xFD, 0x00);
+
+    mbc.write_byte(0xA001, REGISTER_ACTIVE);
+    assert_eq!(mbc.read_byte(0xA000), 0xF1);
+
+    mbc.write_to_register(REGISTER_ROM_BANK_LOW, 0x03);
+    mbc.write_to_register(REGISTER_ROM_BANK_HIGH, 0x01);
+    assert_eq!(mbc.get_rom_address(0x4000), 0x13 * ROM_BANK_SIZE);
+
+    // Write 0xA5 to address 0x12 and read it back
+    mbc.write_to_register(REGISTER_WRITE_LOW, 0x05);
+    mbc.write_to_register(REGISTER_WRITE_HIGH, 0x0A);
+    mbc.write_to_register(REGISTER_COMMAND, (COMMAND_WRITE_RAM << 1) | 0x01);
+    mbc.write_to_register(REGISTER_ADDRESS_LOW, 0x02);
+    assert_eq!(mbc.get_internal_ram().unwrap()[0x12], 0xA5);
+
+    mbc.write_to_register(REGISTER_COMMAND, (COMMAND_READ_RAM << 1) | 0x01);
+    mbc.write_to_register(REGISTER_ADDRESS_LOW, 0x02);
+    mbc.write_byte(0xA001, REGISTER_READ_LOW);
+    assert_eq!(mbc.read_byte(0xA000), 0xF5);
+    mbc.write_byte(0xA001, REGISTER_READ_HIGH);
+    assert_eq!(mbc.read_byte(0xA000), 0xFA);
+}
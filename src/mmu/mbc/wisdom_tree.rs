@@ -0,0 +1,93 @@
+use crate::mmu::{MemoryBankControllerOperations, MemoryOperations, ROM_BANK_SIZE};
+use crate::save_state::{SaveState, StateReader, StateWriter};
+
+/// The unlicensed Wisdom Tree mapper switches all 32 KiB at once
+const WISDOM_TREE_BANK_SIZE: usize = 2 * ROM_BANK_SIZE;
+
+/// Wisdom Tree games claim to be ROM only, but contain the publisher's name
+const WISDOM_TREE_SIGNATURES: [&[u8]; 2] = [b"WISDOM TREE", b"WISDOM\x00TREE"];
+
+/// Unlicensed mapper of Wisdom Tree, the lower byte of the address written to selects the bank
+/// See: https://gbdev.gg8.se/wiki/articles/Memory_Bank_Controllers#Wisdom_Tree
+#[derive(Default)]
+pub struct WisdomTree {
+    bank: u8,
+}
+
+impl WisdomTree {
+    /// The header says ROM only, but the ROM is larger than 32 KiB and contains the signature
+    pub fn is_wisdom_tree(rom: &[u8]) -> bool {
+        rom.len() > WISDOM_TREE_BANK_SIZE
+            && WISDOM_TREE_SIGNATURES
+                .iter()
+                .any(|signature| rom.windows(signature.len()).any(|window| window == *signature))
+    }
+}
+
+impl MemoryOperations for WisdomTree {
+    fn read_byte(&self, _address: u16) -> u8 {
+        // There is no RAM
+        0xFF
+    }
+
+    fn write_byte(&mut self, address: u16, _value: u8) {
+        if address < 0x4000 {
+            self.switch_rom_bank(address as u8);
+        }
+    }
+}
+
+impl MemoryBankControllerOperations for WisdomTree {
+    fn init(&mut self, _rom_size: u8, _cartridge_type: u8, _ram_size: u8) {
+        // The header can't be trusted, the MMU mirrors the banks based on the actual ROM size
+    }
+
+    fn get_rom_address(&self, address: u16) -> usize {
+        self.bank as usize * WISDOM_TREE_BANK_SIZE + address as usize
+    }
+
+    fn get_ram_address(&self, _address: u16) -> Option<usize> {
+        None
+    }
+
+    fn switch_rom_bank(&mut self, bank: u8) {
+        log::debug!("Switching ROM bank to {}", bank);
+        self.bank = bank;
+    }
+
+    fn switch_ram_bank(&mut self, _bank: u8) {
+        // There is no RAM
+    }
+
+    fn enable_ram(&mut self, _enable: bool) {
+        // There is no RAM
+    }
+}
+
+impl SaveState for WisdomTree {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.bank);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.bank = state.read()?;
+        Ok(())
+    }
+}
+
+#[test]
+pub fn wisdom_tree_test() {
+    let mut rom = vec![0; 4 * WISDOM_TREE_BANK_SIZE];
+    assert!(!WisdomTree::is_wisdom_tree(&rom));
+    rom[0x1234..0x1234 + 11].copy_from_slice(b"WISDOM\x00TREE");
+    assert!(WisdomTree::is_wisdom_tree(&rom));
+    assert!(!WisdomTree::is_wisdom_tree(&rom[..WISDOM_TREE_BANK_SIZE]));
+
+    // The value is ignored, the address selects the bank for the whole ROM area
+    let mut mbc = WisdomTree::default();
+    mbc.write_byte(0x0002, 0xFF);
+    assert_eq!(mbc.get_rom_address(0x0000), 2 * WISDOM_TREE_BANK_SIZE);
+    assert_eq!(mbc.get_rom_address(0x4000), 2 * WISDOM_TREE_BANK_SIZE + ROM_BANK_SIZE);
+    mbc.write_byte(0x4001, 0x00);
+    assert_eq!(mbc.get_rom_address(0x0000), 2 * WISDOM_TREE_BANK_SIZE);
+}
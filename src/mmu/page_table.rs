@@ -0,0 +1,68 @@
+/// The address space is split into 256 pages of 256 bytes each
+pub const PAGE_SIZE: usize = 0x100;
+const PAGE_COUNT: usize = 0x100;
+
+/// Where accesses to a page go
+/// The value is the physical address of the first byte of the page within the backing storage
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Page {
+    /// Cartridge ROM
+    Rom(usize),
+    /// Cartridge RAM
+    CartridgeRam(usize),
+    /// Graphics RAM
+    Vram(usize),
+    /// Working RAM, also used for the Echo RAM
+    Wram(usize),
+    /// The access has side effects or depends on some state, e.g. IO & MBC registers or
+    /// VRAM blocked by the PPU, so it has to go through the MMU's handlers
+    Handler,
+}
+
+/// Maps every page of the address space to its backing storage,
+/// so plain reads and writes don't have to go through the memory regions
+/// Reads and writes are mapped separately, e.g. writes to ROM go to the MBC registers
+pub struct PageTable {
+    read: [Page; PAGE_COUNT],
+    write: [Page; PAGE_COUNT],
+}
+
+impl Default for PageTable {
+    fn default() -> Self {
+        Self {
+            read: [Page::Handler; PAGE_COUNT],
+            write: [Page::Handler; PAGE_COUNT],
+        }
+    }
+}
+
+impl PageTable {
+    pub fn get_read_page(&self, address: u16) -> Page {
+        self.read[address as usize / PAGE_SIZE]
+    }
+
+    pub fn get_write_page(&self, address: u16) -> Page {
+        self.write[address as usize / PAGE_SIZE]
+    }
+
+    /// Map the reads of the page containing the address
+    pub fn map_read(&mut self, address: u16, page: Page) {
+        self.read[address as usize / PAGE_SIZE] = page;
+    }
+
+    /// Map the writes of the page containing the address
+    pub fn map_write(&mut self, address: u16, page: Page) {
+        self.write[address as usize / PAGE_SIZE] = page;
+    }
+
+    /// Map reads and writes of the page containing the address
+    pub fn map(&mut self, address: u16, page: Page) {
+        self.map_read(address, page);
+        self.map_write(address, page);
+    }
+}
+
+/// Get the position of the address within its page
+pub fn get_page_offset(address: u16) -> usize {
+    address as usize % PAGE_SIZE
+}
@@ -1,4 +1,5 @@
 use super::{MemoryOperations, NonMbcOperations};
+use crate::save_state::{SaveState, StateReader, StateWriter};
 
 pub struct SimpleRegion {
     memory: Vec<u8>,
@@ -14,6 +15,18 @@ impl SimpleRegion {
             offset,
         }
     }
+
+    /// Read a byte by its position within the region, used by the page table
+    pub fn read_physical(&self, index: usize) -> u8 {
+        self.memory[index]
+    }
+
+    /// Write a byte by its position within the region, used by the page table
+    pub fn write_physical(&mut self, index: usize, value: u8) {
+        if self.writeable {
+            self.memory[index] = value;
+        }
+    }
 }
 
 impl MemoryOperations for SimpleRegion {
@@ -41,4 +54,14 @@ impl NonMbcOperations for SimpleRegion {
     fn fill_from_slice(&mut self, data: &[u8]) {
         self.memory.copy_from_slice(data);
     }
-}
\ No newline at end of file
+}
+
+impl SaveState for SimpleRegion {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write_bytes(&self.memory);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        state.read_bytes(&mut self.memory)
+    }
+}
@@ -0,0 +1,388 @@
+use std::{
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use crate::{
+    cpu::{hardware_model::HardwareModel, CPU, CPU_FREQUENCY},
+    rendering::line_rendering::DOTS_PER_FRAME,
+    save_state::{StateReader, StateWriter},
+};
+
+const MOVIE_MAGIC: [u8; 4] = *b"GBMV";
+/// Version 2 added the clock time the movie starts at, version 3 the tilt of every frame
+/// and version 4 the boot ROM
+const MOVIE_VERSION: u8 = 4;
+const START_POWER_ON: u8 = 0;
+const START_SNAPSHOT: u8 = 1;
+
+/// The state the recorded input starts from
+#[derive(Debug, Clone, PartialEq)]
+pub enum MovieStart {
+    /// A freshly created machine, the cartridge RAM must not be loaded from a save
+    PowerOn,
+    /// A snapshot taken by `CPU::save_snapshot` right after the inputs of a frame have been polled
+    Snapshot(Vec<u8>),
+}
+
+/// The input polled at the end of a frame
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct MovieFrame {
+    /// See `CPU::read_button_keys` for the bits
+    pub buttons: u8,
+    /// Tilt of cartridges with an accelerometer (MBC7) in g, see `MMU::set_tilt`
+    pub tilt: (f32, f32),
+}
+
+impl MovieFrame {
+    /// Read the input from the keyboard
+    pub fn from_keys() -> Self {
+        Self {
+            buttons: CPU::read_button_keys(),
+            tilt: CPU::read_tilt_keys(),
+        }
+    }
+
+    /// Hand the input to the CPU in place of the keyboard
+    pub fn apply(&self, cpu: &mut CPU) {
+        cpu.set_button_input(self.buttons);
+        cpu.mmu.set_tilt(self.tilt.0, self.tilt.1);
+    }
+}
+
+/// The input of every frame, replaying it from the same start reproduces the run
+/// The input is polled once per frame, including the tilt of MBC7 cartridges
+/// The RTC of cartridges with a clock follows the emulated frames instead of the host clock
+/// Cheats aren't part of the input, so they have to be suspended while a movie is active
+#[derive(Debug, Clone, PartialEq)]
+pub struct Movie {
+    /// CRC32 of the ROM the movie has been recorded with
+    rom_checksum: u32,
+    /// CRC32 of the boot ROM, only checked if the movie starts while it is still mapped
+    boot_rom_checksum: u32,
+    model: HardwareModel,
+    start: MovieStart,
+    /// Host time the recording started at in minutes since the unix epoch, the clock of the first frame
+    start_minutes: i64,
+    frames: Vec<MovieFrame>,
+}
+
+impl Movie {
+    pub fn new(cpu: &CPU, start: MovieStart) -> Self {
+        Self {
+            rom_checksum: cpu.mmu.get_rom_checksum(),
+            boot_rom_checksum: cpu.mmu.bank_00.get_checksum(),
+            model: cpu.get_hardware_model(),
+            start,
+            start_minutes: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_secs() as i64 / 60),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn get_start(&self) -> &MovieStart {
+        &self.start
+    }
+
+    pub fn record_frame(&mut self, frame: MovieFrame) {
+        self.frames.push(frame);
+    }
+
+    /// Amount of recorded frames
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    /// Check that the movie belongs to the ROM and set the model it has been recorded on
+    /// Has to be called on a freshly created CPU, before the boot ROM is set up
+    pub fn prepare_playback(&self, cpu: &mut CPU) -> Result<(), String> {
+        let rom_checksum = cpu.mmu.get_rom_checksum();
+        if rom_checksum != self.rom_checksum {
+            return Err(format!(
+                "The movie has been recorded with another ROM (CRC32: {:#010X}, loaded: {:#010X})",
+                self.rom_checksum, rom_checksum
+            ));
+        }
+
+        cpu.set_hardware_model(self.model);
+        Ok(())
+    }
+
+    /// Restore the snapshot the movie starts from, nothing to do for movies starting at power-on
+    /// Has to be called after the boot ROM is set up, a movie starting in it needs the one it has been recorded with
+    pub fn load_start_state(&self, cpu: &mut CPU) -> Result<(), String> {
+        if let MovieStart::Snapshot(snapshot) = &self.start {
+            cpu.load_snapshot(snapshot)?;
+        }
+
+        let boot_rom_checksum = cpu.mmu.bank_00.get_checksum();
+        if cpu.is_boot_rom_enabled() && boot_rom_checksum != self.boot_rom_checksum {
+            return Err(format!(
+                "The movie has been recorded with another boot ROM (CRC32: {:#010X}, loaded: {:#010X})",
+                self.boot_rom_checksum, boot_rom_checksum
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Pin the clock of the cartridge to the emulated time at the start of the frame
+    pub fn sync_clock(&self, cpu: &mut CPU, frame: usize) {
+        let elapsed_minutes = frame as u128 * DOTS_PER_FRAME as u128 / (CPU_FREQUENCY * 60);
+        cpu.mmu.set_clock_minutes(Some(self.start_minutes + elapsed_minutes as i64));
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut state = StateWriter::new();
+        state.write(&MOVIE_MAGIC);
+        state.write(&MOVIE_VERSION);
+        state.write(&self.rom_checksum);
+        state.write(&self.boot_rom_checksum);
+        state.write(&self.model);
+
+        match &self.start {
+            MovieStart::PowerOn => state.write(&START_POWER_ON),
+            MovieStart::Snapshot(snapshot) => {
+                state.write(&START_SNAPSHOT);
+                state.write_bytes(snapshot);
+            }
+        }
+
+        state.write(&self.start_minutes);
+        state.write(&(self.frames.len() as u32));
+        for frame in &self.frames {
+            state.write(&frame.buttons);
+            state.write(&frame.tilt.0);
+            state.write(&frame.tilt.1);
+        }
+        state.into_bytes()
+    }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let mut state = StateReader::new(data);
+        if state.read::<[u8; 4]>()? != MOVIE_MAGIC {
+            return Err("Not a movie file".to_string());
+        }
+
+        let version = state.read::<u8>()?;
+        if version != MOVIE_VERSION {
+            return Err(format!("Unsupported movie version {}", version));
+        }
+
+        let rom_checksum = state.read()?;
+        let boot_rom_checksum = state.read()?;
+        let model = state.read()?;
+        let start = match state.read::<u8>()? {
+            START_POWER_ON => MovieStart::PowerOn,
+            START_SNAPSHOT => MovieStart::Snapshot(read_vec(&mut state)?),
+            start => return Err(format!("Invalid movie start {}", start)),
+        };
+        let start_minutes = state.read()?;
+        let frame_count = state.read::<u32>()?;
+        let frames = (0..frame_count)
+            .map(|_| {
+                Ok(MovieFrame {
+                    buttons: state.read()?,
+                    tilt: (state.read()?, state.read()?),
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        Ok(Self {
+            rom_checksum,
+            boot_rom_checksum,
+            model,
+            start,
+            start_minutes,
+            frames,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        std::fs::write(path, self.to_bytes()).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+    }
+
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let data = std::fs::read(path).map_err(|e| format!("Unable to read {}: {}", path.display(), e))?;
+        Self::from_bytes(&data)
+    }
+}
+
+/// Read bytes written by `StateWriter::write_bytes` whose length isn't known in advance
+fn read_vec(state: &mut StateReader) -> Result<Vec<u8>, String> {
+    let length = state.read::<u32>()? as usize;
+    (0..length).map(|_| state.read::<u8>()).collect()
+}
+
+/// Decides where the joypad input of each frame comes from
+#[derive(Default)]
+pub enum MovieState {
+    /// The keyboard is used as usual
+    #[default]
+    Idle,
+    /// The keyboard is used and every frame's input is appended to the movie
+    Recording(Movie),
+    /// The movie replaces the keyboard until its last frame
+    Playing { movie: Movie, frame: usize },
+}
+
+impl MovieState {
+    pub fn is_active(&self) -> bool {
+        !matches!(self, MovieState::Idle)
+    }
+
+    /// Drive the cartridge's clock by the frames of the movie, back to the host clock while idle
+    /// Has to be called whenever a movie starts or stops, `poll_inputs` calls it for every frame
+    pub fn sync_clock(&self, cpu: &mut CPU) {
+        match self {
+            MovieState::Idle => cpu.mmu.set_clock_minutes(None),
+            MovieState::Recording(movie) => movie.sync_clock(cpu, movie.len()),
+            MovieState::Playing { movie, frame } => movie.sync_clock(cpu, *frame),
+        }
+    }
+
+    /// Poll the inputs of a frame, has to be called exactly once per emulated frame
+    pub fn poll_inputs(&mut self, cpu: &mut CPU) {
+        match self {
+            MovieState::Idle => cpu.poll_inputs(),
+            MovieState::Recording(movie) => {
+                let input = MovieFrame::from_keys();
+                input.apply(cpu);
+                movie.record_frame(input);
+            }
+            MovieState::Playing { movie, frame } => match movie.frames.get(*frame) {
+                Some(input) => {
+                    input.apply(cpu);
+                    *frame += 1;
+                }
+                None => {
+                    log::info!("🎞️ Movie finished after {} frames", frame);
+                    *self = MovieState::Idle;
+                    cpu.poll_inputs();
+                }
+            },
+        }
+
+        self.sync_clock(cpu);
+    }
+}
+
+#[cfg(test)]
+fn run_frame(cpu: &mut CPU, movie_state: &mut MovieState) {
+    while !cpu.get_ppu_mut().take_frame_ready() {
+        cpu.step().unwrap();
+    }
+    movie_state.poll_inputs(cpu);
+}
+
+#[test]
+pub fn movie_playback_test() {
+    use crate::mmu::MemoryOperations;
+
+    let mut rom = vec![0; 0x8000];
+    rom[0x0200] = 0x42;
+    let mut cpu = CPU::new(rom.clone());
+    cpu.skip_boot_rom();
+    // Select the direction buttons
+    cpu.mmu.write_byte(0xFF00, 0x20);
+
+    let mut movie = Movie::new(&cpu, MovieStart::Snapshot(cpu.save_snapshot()));
+    for buttons in [0x01, 0x05, 0x00, 0x08] {
+        movie.record_frame(MovieFrame { buttons, ..Default::default() });
+    }
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+    assert_eq!(movie.len(), 4);
+
+    // Playing the movie on another machine ends in the same state
+    let mut original = MovieState::Playing { movie: movie.clone(), frame: 0 };
+    run_frame(&mut cpu, &mut original);
+    run_frame(&mut cpu, &mut original);
+    // Right & Up are held, pressed buttons read as 0
+    assert_eq!(cpu.mmu.read_byte(0xFF00) & 0x0F, 0x0A);
+    run_frame(&mut cpu, &mut original);
+
+    let mut replay_cpu = CPU::new(rom);
+    movie.prepare_playback(&mut replay_cpu).unwrap();
+    movie.load_start_state(&mut replay_cpu).unwrap();
+    let mut replay = MovieState::Playing { movie: movie.clone(), frame: 0 };
+    for _ in 0..3 {
+        run_frame(&mut replay_cpu, &mut replay);
+    }
+    assert_eq!(replay_cpu.save_snapshot(), cpu.save_snapshot());
+
+    // Movies of other ROMs are rejected
+    assert!(movie.prepare_playback(&mut CPU::new(vec![0; 0x8000])).is_err());
+
+    // Movies starting in the boot ROM need the same boot ROM
+    let mut rom = vec![0; 0x8000];
+    rom[0x0200] = 0x42;
+    let movie = Movie::new(&CPU::new(rom.clone()), MovieStart::PowerOn);
+    let mut cpu = CPU::new(rom);
+    movie.load_start_state(&mut cpu).unwrap();
+    cpu.mmu.bank_00.load_boot_rom(vec![0; 0x100]);
+    assert!(movie.load_start_state(&mut cpu).is_err());
+    cpu.skip_boot_rom();
+    assert!(movie.load_start_state(&mut cpu).is_ok());
+}
+
+#[test]
+pub fn movie_clock_test() {
+    use crate::mmu::MemoryOperations;
+
+    // HuC3 with a clock
+    let mut rom = vec![0; 0x8000];
+    rom[0x0147] = 0xFE;
+    let mut cpu = CPU::new(rom);
+    let movie = Movie::new(&cpu, MovieStart::PowerOn);
+
+    // Latch the time and read the minute of the day from RTC memory 0 to 2
+    let read_minute_of_day = |cpu: &mut CPU| {
+        cpu.mmu.write_byte(0x0000, 0x0B);
+        for command in [0x60, 0x40, 0x50] {
+            cpu.mmu.write_byte(0xA000, command);
+        }
+        (0..3).fold(0, |minutes, nibble| {
+            cpu.mmu.write_byte(0x0000, 0x0B);
+            cpu.mmu.write_byte(0xA000, 0x10);
+            cpu.mmu.write_byte(0x0000, 0x0C);
+            minutes | ((cpu.mmu.read_byte(0xA000) as i64 & 0x0F) << (nibble * 4))
+        })
+    };
+
+    // About 10 minutes of frames later the clock has advanced by 10 minutes, regardless of the host clock
+    movie.sync_clock(&mut cpu, 0);
+    let start = read_minute_of_day(&mut cpu);
+    movie.sync_clock(&mut cpu, 35_837);
+    assert_eq!((read_minute_of_day(&mut cpu) - start).rem_euclid(24 * 60), 10);
+    assert_eq!(movie.start_minutes.rem_euclid(24 * 60), start);
+}
+
+#[test]
+pub fn movie_tilt_test() {
+    use crate::mmu::MemoryOperations;
+
+    // MBC7 with an accelerometer
+    let mut rom = vec![0; 0x8000];
+    rom[0x0147] = 0x22;
+    let mut cpu = CPU::new(rom);
+    cpu.skip_boot_rom();
+
+    let mut movie = Movie::new(&cpu, MovieStart::Snapshot(cpu.save_snapshot()));
+    movie.record_frame(MovieFrame { buttons: 0x00, tilt: (1.0, -0.5) });
+    let movie = Movie::from_bytes(&movie.to_bytes()).unwrap();
+
+    // The tilt of the frame is latched like the one from the keyboard
+    let mut playing = MovieState::Playing { movie, frame: 0 };
+    run_frame(&mut cpu, &mut playing);
+    cpu.mmu.write_byte(0x0000, 0x0A);
+    cpu.mmu.write_byte(0x4000, 0x40);
+    cpu.mmu.write_byte(0xA000, 0x55);
+    cpu.mmu.write_byte(0xA010, 0xAA);
+    assert_eq!(cpu.mmu.read_byte(0xA020) as u16 | (cpu.mmu.read_byte(0xA030) as u16) << 8, 0x81D0 + 0x70);
+    assert_eq!(cpu.mmu.read_byte(0xA040) as u16 | (cpu.mmu.read_byte(0xA050) as u16) << 8, 0x81D0 - 0x38);
+}
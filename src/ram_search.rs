@@ -0,0 +1,316 @@
+use std::ops::RangeInclusive;
+
+use crate::mmu::{MemoryOperations, MMU};
+
+/// Cartridge RAM is only searched in the bank currently mapped
+const CARTRIDGE_RAM: RangeInclusive<u16> = 0xA000..=0xBFFF;
+const WRAM: RangeInclusive<u16> = 0xC000..=0xDFFF;
+const HRAM: RangeInclusive<u16> = 0xFF80..=0xFFFE;
+
+/// How the bytes at an address are interpreted, 16-bit values are little endian like on the CPU
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueFormat {
+    Byte,
+    Word,
+    /// Two decimal digits per byte, common for scores, e.g. 0x42 is 42
+    BcdByte,
+    BcdWord,
+}
+
+pub const VALUE_FORMATS: [ValueFormat; 4] =
+    [ValueFormat::Byte, ValueFormat::Word, ValueFormat::BcdByte, ValueFormat::BcdWord];
+
+impl ValueFormat {
+    pub fn get_size(self) -> u16 {
+        match self {
+            ValueFormat::Byte | ValueFormat::BcdByte => 1,
+            ValueFormat::Word | ValueFormat::BcdWord => 2,
+        }
+    }
+
+    pub fn get_name(self) -> &'static str {
+        match self {
+            ValueFormat::Byte => "8-bit",
+            ValueFormat::Word => "16-bit",
+            ValueFormat::BcdByte => "8-bit BCD",
+            ValueFormat::BcdWord => "16-bit BCD",
+        }
+    }
+
+    /// Read the value at the address, None for BCD values with digits above 9
+    pub fn read(self, mmu: &MMU, address: u16) -> Option<u32> {
+        let bytes = (0..self.get_size()).map(|offset| mmu.read_byte(address + offset));
+
+        match self {
+            ValueFormat::Byte | ValueFormat::Word => {
+                Some(bytes.rev().fold(0, |value, byte| (value << 8) | byte as u32))
+            }
+            ValueFormat::BcdByte | ValueFormat::BcdWord => bytes.rev().try_fold(0, |value, byte| {
+                let (high, low) = (byte >> 4, byte & 0x0F);
+                (high <= 9 && low <= 9).then_some(value * 100 + high as u32 * 10 + low as u32)
+            }),
+        }
+    }
+
+    /// The bytes holding the value starting at the lowest address, None if it doesn't fit
+    pub fn encode(self, value: u32) -> Option<Vec<u8>> {
+        let size = self.get_size() as usize;
+
+        match self {
+            ValueFormat::Byte | ValueFormat::Word => {
+                (value < 1 << (8 * size)).then(|| value.to_le_bytes()[..size].to_vec())
+            }
+            ValueFormat::BcdByte | ValueFormat::BcdWord => (value < 100u32.pow(size as u32)).then(|| {
+                (0..size as u32)
+                    .map(|index| {
+                        let digits = value / 100u32.pow(index) % 100;
+                        (((digits / 10) << 4) | (digits % 10)) as u8
+                    })
+                    .collect()
+            }),
+        }
+    }
+
+    /// Write the value, the parts that don't fit are dropped
+    pub fn write(self, mmu: &mut MMU, address: u16, value: u32) {
+        for (offset, byte) in self.encode(value).unwrap_or_default().into_iter().enumerate() {
+            mmu.write_byte(address + offset as u16, byte);
+        }
+    }
+
+    /// GameShark codes writing the value every frame, one per byte
+    pub fn to_gameshark_codes(self, address: u16, value: u32) -> Vec<String> {
+        self.encode(value)
+            .unwrap_or_default()
+            .into_iter()
+            .enumerate()
+            .map(|(offset, byte)| {
+                let [low, high] = (address + offset as u16).to_le_bytes();
+                format!("01{:02X}{:02X}{:02X}", byte, low, high)
+            })
+            .collect()
+    }
+}
+
+/// Narrows down the candidates, filters without a value compare against the previous search
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    Equal(u32),
+    NotEqual(u32),
+    Greater(u32),
+    Less(u32),
+    Increased,
+    Decreased,
+    Changed,
+    Unchanged,
+    ChangedBy(i64),
+}
+
+impl Filter {
+    fn matches(self, previous: u32, current: u32) -> bool {
+        match self {
+            Filter::Equal(value) => current == value,
+            Filter::NotEqual(value) => current != value,
+            Filter::Greater(value) => current > value,
+            Filter::Less(value) => current < value,
+            Filter::Increased => current > previous,
+            Filter::Decreased => current < previous,
+            Filter::Changed => current != previous,
+            Filter::Unchanged => current == previous,
+            Filter::ChangedBy(difference) => current as i64 - previous as i64 == difference,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Candidate {
+    pub address: u16,
+    /// The value at the last search
+    pub previous: u32,
+}
+
+/// An address shown with its current value, frozen ones are rewritten every frame
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Watch {
+    pub address: u16,
+    pub format: ValueFormat,
+    pub frozen: Option<u32>,
+}
+
+/// Finds the addresses of values like lives or health by comparing snapshots of the RAM over time
+/// Searches WRAM, HRAM & the mapped cartridge RAM through `MMU::read_byte`, so the CPU sees the same values
+pub struct RamSearch {
+    format: ValueFormat,
+    candidates: Vec<Candidate>,
+    watches: Vec<Watch>,
+    /// Frozen values aren't written while set, e.g. during movies which don't contain them
+    suspended: bool,
+}
+
+impl RamSearch {
+    pub fn new(format: ValueFormat) -> Self {
+        Self {
+            format,
+            candidates: Vec::new(),
+            watches: Vec::new(),
+            suspended: false,
+        }
+    }
+
+    pub fn get_format(&self) -> ValueFormat {
+        self.format
+    }
+
+    /// Snapshot the RAM, every address with a valid value becomes a candidate
+    pub fn start(&mut self, mmu: &MMU, format: ValueFormat) {
+        self.format = format;
+
+        let mut regions = vec![WRAM, HRAM];
+        if !mmu.get_cartridge_ram().is_empty() {
+            regions.insert(0, CARTRIDGE_RAM);
+        }
+
+        // Values can't span two regions
+        self.candidates = regions
+            .into_iter()
+            .flat_map(|region| *region.start()..=*region.end() + 1 - format.get_size())
+            .filter_map(|address| {
+                let previous = format.read(mmu, address)?;
+                Some(Candidate { address, previous })
+            })
+            .collect();
+    }
+
+    /// Keep the candidates matching the filter and remember their current values for the next one
+    pub fn filter(&mut self, mmu: &MMU, filter: Filter) {
+        let format = self.format;
+
+        self.candidates.retain_mut(|candidate| match format.read(mmu, candidate.address) {
+            Some(current) if filter.matches(candidate.previous, current) => {
+                candidate.previous = current;
+                true
+            }
+            _ => false,
+        });
+    }
+
+    pub fn get_candidates(&self) -> &[Candidate] {
+        &self.candidates
+    }
+
+    pub fn watch(&mut self, address: u16) {
+        let format = self.format;
+        if !self.watches.iter().any(|watch| watch.address == address && watch.format == format) {
+            self.watches.push(Watch { address, format, frozen: None });
+        }
+    }
+
+    pub fn unwatch(&mut self, index: usize) {
+        self.watches.remove(index);
+    }
+
+    pub fn get_watches(&self) -> &[Watch] {
+        &self.watches
+    }
+
+    /// Stop writing the frozen values, new freezes are refused meanwhile
+    pub fn set_suspended(&mut self, suspended: bool) {
+        self.suspended = suspended;
+    }
+
+    /// Freeze a watched address at its current value, or unfreeze it
+    /// Fails if the current value isn't valid in the format of the watch or freezes are suspended
+    pub fn set_frozen(&mut self, mmu: &MMU, index: usize, frozen: bool) -> Result<(), String> {
+        if frozen && self.suspended {
+            return Err("Values can't be frozen while a movie is recording or playing".to_string());
+        }
+
+        let watch = &mut self.watches[index];
+        watch.frozen = match frozen {
+            true => Some(watch.format.read(mmu, watch.address).ok_or_else(|| {
+                format!("{:04X} doesn't hold a valid {} value", watch.address, watch.format.get_name())
+            })?),
+            false => None,
+        };
+        Ok(())
+    }
+
+    /// Rewrite the frozen values, call once per frame
+    /// Values in cartridge RAM are skipped while it isn't mapped, writes would reach the MBC instead
+    pub fn apply_freezes(&self, mmu: &mut MMU) {
+        if self.suspended {
+            return;
+        }
+
+        for watch in &self.watches {
+            let mapped = (0..watch.format.get_size()).all(|offset| mmu.is_ram_mapped(watch.address + offset));
+            if let (Some(value), true) = (watch.frozen, mapped) {
+                watch.format.write(mmu, watch.address, value);
+            }
+        }
+    }
+
+    /// GameShark codes keeping a watched address at its frozen or current value
+    pub fn export_gameshark_codes(&self, mmu: &MMU, index: usize) -> Vec<String> {
+        let watch = &self.watches[index];
+        watch
+            .frozen
+            .or_else(|| watch.format.read(mmu, watch.address))
+            .map_or(Vec::new(), |value| watch.format.to_gameshark_codes(watch.address, value))
+    }
+}
+
+#[test]
+pub fn ram_search_test() {
+    use crate::cpu::CPU;
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.skip_boot_rom();
+    cpu.mmu.write_byte(0xC123, 3);
+    cpu.mmu.write_byte(0xFF90, 3);
+
+    // The lives at 0xC123 go from 3 to 2, some other byte stays at 3
+    let mut search = RamSearch::new(ValueFormat::Byte);
+    search.start(&cpu.mmu, ValueFormat::Byte);
+    assert_eq!(search.get_candidates().len(), 0x2000 + 0x7F);
+    search.filter(&cpu.mmu, Filter::Equal(3));
+    assert_eq!(search.get_candidates().len(), 2);
+    cpu.mmu.write_byte(0xC123, 2);
+    search.filter(&cpu.mmu, Filter::ChangedBy(-1));
+    assert_eq!(search.get_candidates(), [Candidate { address: 0xC123, previous: 2 }]);
+
+    search.watch(0xC123);
+    search.set_frozen(&cpu.mmu, 0, true).unwrap();
+    cpu.mmu.write_byte(0xC123, 1);
+    search.apply_freezes(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0xC123), 2);
+
+    // Suspended freezes aren't written
+    search.set_suspended(true);
+    cpu.mmu.write_byte(0xC123, 1);
+    search.apply_freezes(&mut cpu.mmu);
+    assert_eq!(cpu.mmu.read_byte(0xC123), 1);
+    assert!(search.set_frozen(&cpu.mmu, 0, true).is_err());
+    search.set_suspended(false);
+    assert_eq!(search.export_gameshark_codes(&cpu.mmu, 0), ["010223C1"]);
+
+    // A score of 1234 stored as BCD
+    cpu.mmu.write_byte(0xD000, 0x34);
+    cpu.mmu.write_byte(0xD001, 0x12);
+    search.start(&cpu.mmu, ValueFormat::BcdWord);
+    search.filter(&cpu.mmu, Filter::Equal(1234));
+    assert_eq!(search.get_candidates(), [Candidate { address: 0xD000, previous: 1234 }]);
+    cpu.mmu.write_byte(0xD001, 0x13);
+    search.filter(&cpu.mmu, Filter::Increased);
+    assert_eq!(search.get_candidates().len(), 1);
+
+    assert_eq!(ValueFormat::Word.to_gameshark_codes(0xD000, 0x1234), ["013400D0", "011201D0"]);
+    assert_eq!(ValueFormat::BcdByte.encode(100), None);
+    assert_eq!(ValueFormat::BcdByte.read(&cpu.mmu, 0xD000), Some(34));
+
+    // 0x13AB isn't BCD, so it can't be frozen
+    cpu.mmu.write_byte(0xD000, 0xAB);
+    search.watch(0xD000);
+    assert!(search.set_frozen(&cpu.mmu, 1, true).is_err());
+    assert_eq!(search.get_watches()[1].frozen, None);
+}
@@ -2,6 +2,8 @@ pub mod tiles;
 pub mod views;
 pub mod utils;
 pub mod line_rendering;
+pub mod screenshot;
+pub mod recording;
 
 // Disable for now
 //#[cfg(test)]
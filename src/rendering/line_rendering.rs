@@ -1,5 +1,11 @@
-use crate::cpu::{interrupts::PpuMode, CPU};
-use macroquad::{color::Color, texture::Image};
+use crate::{
+    cpu::{interrupts::PpuMode, CPU},
+    save_state::{SaveState, StateReader, StateWriter},
+};
+use macroquad::{
+    color::{Color, GREEN},
+    texture::Image,
+};
 
 // Dots are PPU Cycle conters per Frame
 const DOTS_PER_CYCLE: u32 = 4;
@@ -11,13 +17,39 @@ const MIN_DRAW_DOTS: u32 = 172;
 const SCANLINES_ACTUAL: u8 = 144;
 const SCANLINES_EXTRA: u8 = 10;
 
+pub const DOTS_PER_FRAME: u32 = DOTS_PER_LINE * (SCANLINES_ACTUAL + SCANLINES_EXTRA) as u32;
+const LAST_LINE: u8 = SCANLINES_ACTUAL + SCANLINES_EXTRA - 1;
+
 const TILES_PER_LINE: u16 = 21;
 
+/// WX is the window position plus 7
+const WINDOW_X_OFFSET: i32 = 7;
+/// The highest WX at which the window is still visible
+const WINDOW_X_MAX: u8 = 166;
+
+pub const SCREEN_WIDTH: u16 = 160;
+pub const SCREEN_HEIGHT: u16 = 144;
+
+pub const PALETTE: [Color; 4] = [
+    Color::new(232.0/255.0, 252.0/255.0, 204.0/255.0, 1.00),
+    Color::new(172.0/255.0, 212.0/255.0, 144.0/255.0, 1.00),
+    Color::new(084.0/255.0, 140.0/255.0, 112.0/255.0, 1.00),
+    Color::new(020.0/255.0, 044.0/255.0, 056.0/255.0, 1.00),
+];
+
 // Mode 2
 pub fn oam_scan(_cpu: &CPU) {}
 
+/// The part of the window drawn on a scanline
+pub struct WindowLine {
+    /// The line within the window, based on the internal line counter
+    pub line: u8,
+    /// The screen x coordinate the window starts at, may be negative
+    pub x: i32,
+}
+
 // Mode 3
-pub fn draw_line(cpu: &mut CPU, game_diplay: &mut Image, palette: &[Color; 4]) {
+pub fn draw_line(cpu: &mut CPU, game_diplay: &mut Image, palette: &[Color; 4], window: Option<WindowLine>) {
 
     let scx = cpu.get_lcd_scx();
     let scy = cpu.get_lcd_scy();
@@ -54,33 +86,24 @@ pub fn draw_line(cpu: &mut CPU, game_diplay: &mut Image, palette: &[Color; 4]) {
     }
 
     // Draw Window
-    if cpu.get_lcdc_window_enable() {
-        let wd_offset_y = cpu.get_window_wy();
-        let wd_offset_x = cpu.get_window_wx() as i32 - 7;
-
-        // Fetch Window Line Data for the 20 tiles on screen
-        for xtile in 0..20 {
-            // Fetch Window Line Data
-
-            if line >= wd_offset_y {
-                let wd_tile_idx = cpu.get_vram_tile_map_entry(
-                    cpu.get_lcdc_window_tile_high_map(),
-                    ((line - wd_offset_y) as u16 / 8) * 32 + (xtile - wd_offset_x) as u16,
-                );
-                let wd_line = cpu.get_vram_tile_line(high_adressing, wd_tile_idx as u16, line % 8);
-    
-                // Draw the 8 pixels in a tile line
-                for x_pixel in 0..8 as usize {
-                    let x_coord: i32 = xtile as i32 * 8 + x_pixel as i32 + wd_offset_x;
-                    if x_coord >= 0 && line >= wd_offset_y {
-                        game_diplay.set_pixel(
-                            x_coord as u32,
-                            line as u32,
-                            palette[wd_line[x_pixel] as usize],
-                        );
-                    }
-                }
-            }
+    if let Some(window) = window {
+        let window_tile_row = window.line as u16 / 8;
+
+        for x in window.x.max(0)..SCREEN_WIDTH as i32 {
+            // Pixel within the window
+            let window_x = (x - window.x) as u16;
+
+            let wd_tile_idx = cpu.get_vram_tile_map_entry(
+                cpu.get_lcdc_window_tile_high_map(),
+                window_tile_row * 32 + window_x / 8,
+            );
+            let wd_line = cpu.get_vram_tile_line(high_adressing, wd_tile_idx as u16, window.line % 8);
+
+            game_diplay.set_pixel(
+                x as u32,
+                line as u32,
+                palette[wd_line[window_x as usize % 8] as usize],
+            );
         }
     }
 
@@ -132,8 +155,25 @@ pub fn draw_line(cpu: &mut CPU, game_diplay: &mut Image, palette: &[Color; 4]) {
 }
 
 pub struct Ppu {
-    frame_cycles: u32,
+    /// The dot within the frame the PPU is stepped at next
+    dot: u32,
+    /// The scanline the PPU is on, unlike LY this doesn't read 0 during the last line
+    line: u8,
     enabled: bool,
+    /// Set when a frame has been completed, see `take_frame_ready`
+    frame_ready: bool,
+    final_image: Image,
+    palette: [Color; 4],
+    /// The internal line counter of the window, only advanced on lines the window was drawn on
+    window_line: u8,
+    /// Whether LY matched WY during this frame, the window isn't drawn before
+    window_y_triggered: bool,
+    /// Set when the window was drawn with WX=166, the window then covers the whole next line
+    window_full_line: bool,
+    /// Whether the PPU is on the first line after the LCD has been enabled
+    first_line: bool,
+    /// Whether the current frame is the first one after the LCD has been enabled
+    blank_frame: bool,
 }
 
 impl Default for Ppu {
@@ -145,89 +185,329 @@ impl Default for Ppu {
 impl Ppu {
     pub fn new() -> Self {
         Ppu {
-            frame_cycles: 0,
+            dot: 0,
+            line: 0,
             enabled: false,
+            frame_ready: false,
+            final_image: Image::gen_image_color(SCREEN_WIDTH, SCREEN_HEIGHT, GREEN),
+            palette: PALETTE,
+            window_line: 0,
+            window_y_triggered: false,
+            window_full_line: false,
+            first_line: false,
+            blank_frame: false,
         }
     }
 
-    pub fn step(&mut self, cpu: &mut CPU, final_image: &mut Image, palette: &[Color; 4]) {
-        if cpu.get_lcdc_ppu_enabled() && !self.enabled {
-            self.frame_cycles = 0;
-            self.enabled = true;
+    /// Turn the LCD on, the first line is one M-cycle shorter than usual
+    /// See: https://gbdev.io/pandocs/LCDC.html#lcdc7--lcd-enable
+    fn enable(&mut self, cpu: &mut CPU) {
+        self.enabled = true;
+        self.dot = 2 * DOTS_PER_CYCLE;
+        self.line = 0;
+        self.first_line = true;
+        self.blank_frame = true;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.window_full_line = false;
+
+        cpu.set_lcd_y_coordinate(0);
+        cpu.set_ppu_mode(PpuMode::HorizontalBlank);
+    }
+
+    /// Turn the LCD off, this can happen at any point of the frame
+    fn disable(&mut self, cpu: &mut CPU) {
+        self.enabled = false;
+        self.dot = 0;
+        self.line = 0;
+        self.first_line = false;
+
+        // LY & STAT are reset, so VRAM & OAM are accessible while the LCD is off
+        cpu.set_lcd_y_coordinate(0);
+        cpu.set_ppu_mode(PpuMode::HorizontalBlank);
+
+        self.clear_image();
+    }
+
+    /// Fill the image with the lightest color
+    fn clear_image(&mut self) {
+        let blank: [u8; 4] = self.palette[0].into();
+        for pixel in self.final_image.get_image_data_mut() {
+            *pixel = blank;
+        }
+    }
+
+    /// Get the window for the current scanline and advance the window line counter
+    /// Returns None if the window isn't drawn on this line
+    /// See: https://gbdev.io/pandocs/Scrolling.html#ff4aff4b--wy-wx-window-y-position-x-position-plus-7
+    fn next_window_line(&mut self, cpu: &mut CPU, scanline: u8) -> Option<WindowLine> {
+        if cpu.get_window_wy() == scanline {
+            self.window_y_triggered = true;
+        }
+
+        let full_line = std::mem::take(&mut self.window_full_line);
+
+        if !cpu.get_lcdc_window_enable() || !self.window_y_triggered {
+            return None;
+        }
+
+        let wx = cpu.get_window_wx();
+        let x = match wx {
+            _ if full_line => 0,
+            // The window is shifted by the fine background scroll
+            0 => -WINDOW_X_OFFSET - (cpu.get_lcd_scx() % 8) as i32,
+            1..=WINDOW_X_MAX => wx as i32 - WINDOW_X_OFFSET,
+            _ => return None,
+        };
+
+        if wx == WINDOW_X_MAX {
+            self.window_full_line = true;
         }
 
-        // Clear the screen if the PPU is disabled
-        if !cpu.get_lcdc_ppu_enabled() && self.enabled{
-            self.enabled = false;
+        let line = self.window_line;
+        self.window_line = self.window_line.wrapping_add(1);
 
-            for pixel in final_image.get_image_data_mut() {
-                pixel[0] = 0;
-                pixel[1] = 227;
-                pixel[2] = 48;
-                pixel[3] = 255;
+        Some(WindowLine { line, x })
+    }
+
+    /// Advance the PPU to its next mode or line change
+    /// Returns the dots till the PPU has to be stepped again, see `Event::Ppu`
+    pub fn step(&mut self, cpu: &mut CPU) -> u32 {
+        if !cpu.get_lcdc_ppu_enabled() {
+            if self.enabled {
+                self.disable(cpu);
+            } else {
+                // Frames are still presented at the same rate, so the frontend keeps running
+                self.frame_ready = true;
             }
-            return;
+            return DOTS_PER_FRAME;
         }
 
-        // A dot is a PPU cycle; the PPU runs faster than the CPU; the emulation code will execute all the work on render mode transitions
-        let dot = self.frame_cycles * DOTS_PER_CYCLE;
-        self.frame_cycles += 1;
+        if !self.enabled {
+            self.enable(cpu);
+            // The first line after enabling the LCD has no OAM Scan, STAT reads mode 0 instead
+            let dots = SCAN_DOTS - self.dot;
+            self.dot += dots;
+            return dots;
+        }
 
         let ppu_mode = PpuMode::try_from(cpu.get_ppu_mode()).expect("Invalid PPU Mode");
-        let scanline = cpu.get_lcd_y_coordinate();
 
-        match ppu_mode {
+        let dots = match ppu_mode {
+            PpuMode::HorizontalBlank if self.first_line => {
+                self.first_line = false;
+                cpu.set_ppu_mode(PpuMode::Drawing);
+                MIN_DRAW_DOTS
+            }
             PpuMode::OamScan => {
-                if dot % DOTS_PER_LINE == SCAN_DOTS - DOTS_PER_CYCLE {
-                    oam_scan(cpu);
-                    cpu.set_ppu_mode(PpuMode::Drawing);
-                } else if dot % DOTS_PER_LINE >= SCAN_DOTS {
-                    panic!("dot must be < 80 in OAM Scan Mode");
-                }
+                oam_scan(cpu);
+                cpu.set_ppu_mode(PpuMode::Drawing);
+                MIN_DRAW_DOTS
             }
             PpuMode::Drawing => {
                 // TODO Implement Variable Drawing Mode duration
-                if dot % DOTS_PER_LINE == SCAN_DOTS + MIN_DRAW_DOTS - DOTS_PER_CYCLE {
-                    draw_line(cpu, final_image, palette);
-                    cpu.set_ppu_mode(PpuMode::HorizontalBlank);
-                } else if dot % DOTS_PER_LINE >= SCAN_DOTS + MIN_DRAW_DOTS {
-                    panic!("dot has an invalid value");
+                // The first frame after enabling the LCD isn't displayed
+                if !self.blank_frame {
+                    let window = self.next_window_line(cpu, self.line);
+                    draw_line(cpu, &mut self.final_image, &self.palette, window);
                 }
+                cpu.set_ppu_mode(PpuMode::HorizontalBlank);
+                DOTS_PER_LINE - SCAN_DOTS - MIN_DRAW_DOTS
             }
             PpuMode::HorizontalBlank => {
-                if dot % DOTS_PER_LINE == DOTS_PER_LINE - DOTS_PER_CYCLE {
-                    cpu.set_lcd_y_coordinate(scanline + 1);
-
-                    // Check if in extra scanlines area
-                    if scanline + 1 < SCANLINES_ACTUAL {
-                        cpu.set_ppu_mode(PpuMode::OamScan);
-                    } else {
-                        // Set the VBlank interrupt since we are done with the frame
-                        cpu.set_vblank_interrupt();
-                        cpu.set_ppu_mode(PpuMode::VerticalBlank);
-                    };
-                }
-            }
-            PpuMode::VerticalBlank => {
-                //log::info!("Dot: {}", dot % DOTS_PER_LINE);
-                if dot % DOTS_PER_LINE == DOTS_PER_LINE - DOTS_PER_CYCLE {
-                    cpu.set_lcd_y_coordinate(scanline + 1);
-
-                    if scanline + 1 == SCANLINES_ACTUAL + SCANLINES_EXTRA - 1 {
-                        self.frame_cycles = 0;
-                        cpu.set_lcd_y_coordinate(0);
-                        cpu.set_ppu_mode(PpuMode::OamScan)
-                    }
+                self.line += 1;
+                cpu.set_lcd_y_coordinate(self.line);
+
+                // Check if in extra scanlines area
+                if self.line < SCANLINES_ACTUAL {
+                    cpu.set_ppu_mode(PpuMode::OamScan);
+                    SCAN_DOTS
+                } else {
+                    // Set the VBlank interrupt since we are done with the frame
+                    cpu.set_vblank_interrupt();
+                    cpu.set_ppu_mode(PpuMode::VerticalBlank);
+                    DOTS_PER_LINE
                 }
             }
+            PpuMode::VerticalBlank => self.step_vblank(cpu),
+        };
+
+        self.dot += dots;
+        dots
+    }
+
+    /// Advance to the next line during VBlank, the last line wraps to the next frame
+    fn step_vblank(&mut self, cpu: &mut CPU) -> u32 {
+        if self.line < LAST_LINE {
+            self.line += 1;
+            cpu.set_lcd_y_coordinate(self.line);
+
+            // LY reads 153 only for the first M-cycle of the last line
+            // See: https://gbdev.io/pandocs/STAT.html#ff44--ly-lcd-y-coordinate-read-only
+            return if self.line == LAST_LINE { DOTS_PER_CYCLE } else { DOTS_PER_LINE };
         }
+
+        if self.dot % DOTS_PER_LINE != 0 {
+            cpu.set_lcd_y_coordinate(0);
+            return DOTS_PER_LINE - DOTS_PER_CYCLE;
+        }
+
+        self.dot = 0;
+        self.line = 0;
+        self.frame_ready = true;
+        self.blank_frame = false;
+        self.window_line = 0;
+        self.window_y_triggered = false;
+        self.window_full_line = false;
+        cpu.set_ppu_mode(PpuMode::OamScan);
+        SCAN_DOTS
     }
 
     pub fn get_dot(&self) -> u32 {
-        self.frame_cycles * DOTS_PER_CYCLE
+        self.dot
+    }
+
+    /// Returns whether a frame has been completed since the last call
+    pub fn take_frame_ready(&mut self) -> bool {
+        std::mem::take(&mut self.frame_ready)
+    }
+
+    pub fn get_image(&self) -> &Image {
+        &self.final_image
+    }
+}
+
+/// The image is included, so a restored state can be shown right away
+impl SaveState for Ppu {
+    fn save_state(&self, state: &mut StateWriter) {
+        state.write(&self.dot);
+        state.write(&self.line);
+        state.write(&self.enabled);
+        state.write(&self.frame_ready);
+        state.write_bytes(&self.final_image.bytes);
+        state.write(&self.window_line);
+        state.write(&self.window_y_triggered);
+        state.write(&self.window_full_line);
+        state.write(&self.first_line);
+        state.write(&self.blank_frame);
+    }
+
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String> {
+        self.dot = state.read()?;
+        self.line = state.read()?;
+        self.enabled = state.read()?;
+        self.frame_ready = state.read()?;
+        state.read_bytes(&mut self.final_image.bytes)?;
+        self.window_line = state.read()?;
+        self.window_y_triggered = state.read()?;
+        self.window_full_line = state.read()?;
+        self.first_line = state.read()?;
+        self.blank_frame = state.read()?;
+        Ok(())
     }
+}
+
+#[test]
+pub fn last_line_test() {
+    use crate::mmu::MemoryOperations;
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+    cpu.set_ppu_mode(PpuMode::OamScan);
+    cpu.mmu.write_byte(0xFF40, 0x80);
+
+    // Run till line 153 starts
+    let last_line = SCANLINES_ACTUAL + SCANLINES_EXTRA - 1;
+    while cpu.get_lcd_y_coordinate() != last_line {
+        cpu.idle_cycle();
+    }
+
+    // LY reads 0 after the first M-cycle, but the frame only ends with the line
+    cpu.idle_cycle();
+    assert_eq!(cpu.get_lcd_y_coordinate(), 0);
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::VerticalBlank as u8);
+
+    for _ in 1..DOTS_PER_LINE / DOTS_PER_CYCLE - 1 {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::VerticalBlank as u8);
+    cpu.idle_cycle();
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::OamScan as u8);
+    assert!(cpu.get_ppu_mut().take_frame_ready());
+}
+
+#[test]
+pub fn window_line_counter_test() {
+    use crate::mmu::MemoryOperations;
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+    let mut ppu = Ppu::new();
+
+    // Window enabled, WY = 2, WX = 7
+    cpu.mmu.write_byte(0xFF40, 0b1010_0000);
+    cpu.mmu.write_byte(0xFF4A, 2);
+    cpu.mmu.write_byte(0xFF4B, 7);
+
+    assert!(ppu.next_window_line(&mut cpu, 0).is_none());
+    assert!(ppu.next_window_line(&mut cpu, 1).is_none());
+    let window = ppu.next_window_line(&mut cpu, 2).unwrap();
+    assert_eq!((window.line, window.x), (0, 0));
+
+    // The counter doesn't advance while the window is disabled
+    cpu.mmu.write_byte(0xFF40, 0b1000_0000);
+    assert!(ppu.next_window_line(&mut cpu, 3).is_none());
+    cpu.mmu.write_byte(0xFF40, 0b1010_0000);
+    let window = ppu.next_window_line(&mut cpu, 4).unwrap();
+    assert_eq!((window.line, window.x), (1, 0));
+
+    // WX = 166 shows a single pixel and covers the whole next line
+    cpu.mmu.write_byte(0xFF4B, 166);
+    let window = ppu.next_window_line(&mut cpu, 5).unwrap();
+    assert_eq!((window.line, window.x), (2, 159));
+    cpu.mmu.write_byte(0xFF4B, 200);
+    let window = ppu.next_window_line(&mut cpu, 6).unwrap();
+    assert_eq!((window.line, window.x), (3, 0));
+    assert!(ppu.next_window_line(&mut cpu, 7).is_none());
+
+    // WX = 0 is shifted by the fine scroll
+    cpu.mmu.write_byte(0xFF4B, 0);
+    cpu.mmu.write_byte(0xFF43, 3);
+    let window = ppu.next_window_line(&mut cpu, 8).unwrap();
+    assert_eq!((window.line, window.x), (4, -10));
+}
+
+#[test]
+pub fn lcd_enable_test() {
+    use crate::mmu::MemoryOperations;
 
-    pub fn get_frame_cycles(&self) -> u32 {
-        self.frame_cycles
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.mmu.set_bootrom_enabled(false);
+    cpu.mmu.write_byte(0xFF40, 0x80);
+
+    // The first line has no OAM Scan and is one M-cycle shorter
+    cpu.idle_cycle();
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::HorizontalBlank as u8);
+    for _ in 0..(SCAN_DOTS / DOTS_PER_CYCLE) - 2 {
+        cpu.idle_cycle();
+    }
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::Drawing as u8);
+
+    // Turning the LCD off in the middle of a line resets LY & STAT
+    while cpu.get_lcd_y_coordinate() != 10 {
+        cpu.idle_cycle();
+    }
+    cpu.mmu.write_byte(0xFF40, 0x00);
+    cpu.idle_cycle();
+    assert_eq!(cpu.get_lcd_y_coordinate(), 0);
+    assert_eq!(cpu.get_ppu_mode(), PpuMode::HorizontalBlank as u8);
+
+    // No interrupts are requested, but frames are still presented
+    cpu.mmu.write_byte(0xFF0F, 0);
+    cpu.get_ppu_mut().take_frame_ready();
+    for _ in 0..DOTS_PER_FRAME / DOTS_PER_CYCLE {
+        cpu.idle_cycle();
     }
+    assert_eq!(cpu.mmu.read_byte(0xFF0F), 0b1110_0000);
+    assert!(cpu.get_ppu_mut().take_frame_ready());
 }
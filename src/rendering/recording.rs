@@ -0,0 +1,149 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use crate::{
+    cpu::{CPU, CPU_FREQUENCY},
+    rendering::line_rendering::{DOTS_PER_FRAME, SCREEN_HEIGHT, SCREEN_WIDTH},
+};
+
+/// Lossless formats, GIF isn't offered as its delays can't match the frame rate exactly
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VideoFormat {
+    /// YUV4MPEG2 with full resolution chroma, most encoders read it directly
+    /// See: https://wiki.multimedia.cx/index.php/YUV4MPEG2
+    Y4m,
+    /// Frames of 160x144 RGBA pixels without any header, e.g. for
+    /// `ffmpeg -f rawvideo -pixel_format rgba -video_size 160x144 -framerate 262144/4389 -i video.rgba`
+    RawRgba,
+}
+
+impl VideoFormat {
+    pub fn get_extension(self) -> &'static str {
+        match self {
+            VideoFormat::Y4m => "y4m",
+            VideoFormat::RawRgba => "rgba",
+        }
+    }
+}
+
+/// The exact frame rate of the PPU as a reduced fraction, 262144/4389 or about 59.7275
+pub fn get_frame_rate() -> (u64, u64) {
+    let (mut a, mut b) = (CPU_FREQUENCY as u64, DOTS_PER_FRAME as u64);
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+
+    (CPU_FREQUENCY as u64 / a, DOTS_PER_FRAME as u64 / a)
+}
+
+/// Writes every frame it is given, so it has to be fed every emulated frame instead of the rendered ones
+pub struct VideoRecorder<W: Write> {
+    writer: W,
+    format: VideoFormat,
+    frames: u32,
+}
+
+impl VideoRecorder<BufWriter<File>> {
+    pub fn create(path: &Path, format: VideoFormat) -> Result<Self, String> {
+        let file = File::create(path).map_err(|e| format!("Unable to create {}: {}", path.display(), e))?;
+        Self::new(BufWriter::new(file), format)
+    }
+}
+
+impl<W: Write> VideoRecorder<W> {
+    pub fn new(mut writer: W, format: VideoFormat) -> Result<Self, String> {
+        if format == VideoFormat::Y4m {
+            let (numerator, denominator) = get_frame_rate();
+            let header = format!(
+                "YUV4MPEG2 W{} H{} F{}:{} Ip A1:1 C444 XCOLORRANGE=FULL\n",
+                SCREEN_WIDTH, SCREEN_HEIGHT, numerator, denominator
+            );
+            writer.write_all(header.as_bytes()).map_err(|e| e.to_string())?;
+        }
+
+        Ok(Self {
+            writer,
+            format,
+            frames: 0,
+        })
+    }
+
+    pub fn get_frames(&self) -> u32 {
+        self.frames
+    }
+
+    /// Append a frame of 160x144 RGBA pixels
+    pub fn write_frame(&mut self, rgba: &[u8]) -> Result<(), String> {
+        match self.format {
+            VideoFormat::Y4m => {
+                self.writer.write_all(b"FRAME\n").map_err(|e| e.to_string())?;
+                self.writer.write_all(&rgba_to_yuv444(rgba)).map_err(|e| e.to_string())?;
+            }
+            VideoFormat::RawRgba => self.writer.write_all(rgba).map_err(|e| e.to_string())?,
+        }
+
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Flush the remaining frames and return the writer
+    pub fn finish(mut self) -> Result<W, String> {
+        self.writer.flush().map_err(|e| e.to_string())?;
+        Ok(self.writer)
+    }
+}
+
+/// Run the CPU without a window and record the given amount of frames, e.g. for automated bug reports
+pub fn record_headless<W: Write>(cpu: &mut CPU, recorder: &mut VideoRecorder<W>, frames: u32) -> Result<(), String> {
+    let mut recorded = 0;
+    while recorded < frames {
+        cpu.step()?;
+
+        if cpu.get_ppu_mut().take_frame_ready() {
+            recorder.write_frame(&cpu.get_ppu().get_image().bytes)?;
+            recorded += 1;
+        }
+    }
+
+    Ok(())
+}
+
+/// Planar full range BT.601 YUV, the Y plane followed by the U & V planes
+fn rgba_to_yuv444(rgba: &[u8]) -> Vec<u8> {
+    let pixels = rgba.len() / 4;
+    let mut yuv = vec![0; pixels * 3];
+
+    for (index, pixel) in rgba.chunks_exact(4).enumerate() {
+        let (r, g, b) = (pixel[0] as f32, pixel[1] as f32, pixel[2] as f32);
+
+        yuv[index] = (0.299 * r + 0.587 * g + 0.114 * b).round() as u8;
+        yuv[pixels + index] = (128.0 - 0.168736 * r - 0.331264 * g + 0.5 * b).round().clamp(0.0, 255.0) as u8;
+        yuv[2 * pixels + index] = (128.0 + 0.5 * r - 0.418688 * g - 0.081312 * b).round().clamp(0.0, 255.0) as u8;
+    }
+
+    yuv
+}
+
+#[test]
+pub fn video_recorder_test() {
+    assert_eq!(get_frame_rate(), (262144, 4389));
+
+    let mut cpu = CPU::new(vec![0; 0x8000]);
+    cpu.skip_boot_rom();
+    let mut recorder = VideoRecorder::new(Vec::new(), VideoFormat::Y4m).unwrap();
+    record_headless(&mut cpu, &mut recorder, 2).unwrap();
+    assert_eq!(recorder.get_frames(), 2);
+
+    let video = recorder.finish().unwrap();
+    let header = b"YUV4MPEG2 W160 H144 F262144:4389 Ip A1:1 C444 XCOLORRANGE=FULL\n";
+    assert!(video.starts_with(header));
+    let frame_size = b"FRAME\n".len() + 3 * SCREEN_WIDTH as usize * SCREEN_HEIGHT as usize;
+    assert_eq!(video.len(), header.len() + 2 * frame_size);
+
+    // White stays white, gray has no color
+    assert_eq!(rgba_to_yuv444(&[0xFF, 0xFF, 0xFF, 0xFF]), [0xFF, 0x80, 0x80]);
+    assert_eq!(rgba_to_yuv444(&[0x55, 0x55, 0x55, 0xFF]), [0x55, 0x80, 0x80]);
+}
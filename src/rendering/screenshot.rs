@@ -0,0 +1,222 @@
+use std::{
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use macroquad::texture::Image;
+
+/// See: https://www.w3.org/TR/png/#5PNG-file-signature
+const PNG_SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+/// 8 bits per channel of RGBA, the layout of macroquad's images
+const PNG_BIT_DEPTH: u8 = 8;
+const PNG_COLOR_TYPE_RGBA: u8 = 6;
+/// Every row starts with the filter applied to it, none in this case
+const PNG_FILTER_NONE: u8 = 0;
+
+/// Deflate with a 32 KiB window, the check bits make the header divisible by 31
+const ZLIB_HEADER: [u8; 2] = [0x78, 0x01];
+/// The image data isn't compressed, stored deflate blocks can hold up to 65535 bytes
+const MAX_STORED_BLOCK_SIZE: usize = 0xFFFF;
+const ADLER_MODULO: u32 = 65521;
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Save the frame and optionally debug views next to each other in `directory`
+/// The files are named after the ROM title & the current time, the debug views get their name appended
+/// Returns the paths of the saved files
+pub fn save_screenshot(
+    directory: &Path,
+    title: &str,
+    scale: u32,
+    frame: &Image,
+    debug_views: &[(&str, &Image)],
+) -> Result<Vec<PathBuf>, String> {
+    std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+    let base_name = get_unused_base_name(directory, title, "png");
+
+    let mut paths = Vec::new();
+    for (suffix, image) in [("", frame)].into_iter().chain(debug_views.iter().copied()) {
+        let name = match suffix {
+            "" => format!("{}.png", base_name),
+            suffix => format!("{}_{}.png", base_name, suffix),
+        };
+        let path = directory.join(name);
+
+        save_png(image, scale, &path)?;
+        paths.push(path);
+    }
+
+    Ok(paths)
+}
+
+/// Save an image as PNG, scaled by an integer factor without smoothing
+pub fn save_png(image: &Image, scale: u32, path: &Path) -> Result<(), String> {
+    let png = match scale {
+        0 | 1 => encode_png(image),
+        scale => encode_png(&scale_image(image, scale)),
+    };
+
+    std::fs::write(path, png).map_err(|e| format!("Unable to write {}: {}", path.display(), e))
+}
+
+pub fn scale_image(image: &Image, scale: u32) -> Image {
+    let scale = scale as usize;
+    let width = image.width() * scale;
+    let height = image.height() * scale;
+
+    let mut bytes = Vec::with_capacity(width * height * 4);
+    for row in image.bytes.chunks(image.width() * 4) {
+        let scaled_row: Vec<u8> = row
+            .chunks(4)
+            .flat_map(|pixel| pixel.repeat(scale))
+            .collect();
+
+        for _ in 0..scale {
+            bytes.extend_from_slice(&scaled_row);
+        }
+    }
+
+    Image {
+        bytes,
+        width: width as u16,
+        height: height as u16,
+    }
+}
+
+/// Encode an image as an RGBA PNG, the data is stored without compression
+/// See: https://www.w3.org/TR/png/
+pub fn encode_png(image: &Image) -> Vec<u8> {
+    let mut header = Vec::new();
+    header.extend_from_slice(&(image.width as u32).to_be_bytes());
+    header.extend_from_slice(&(image.height as u32).to_be_bytes());
+    // No compression & filter method other than the defaults, no interlacing
+    header.extend_from_slice(&[PNG_BIT_DEPTH, PNG_COLOR_TYPE_RGBA, 0, 0, 0]);
+
+    let mut scanlines = Vec::with_capacity(image.bytes.len() + image.height());
+    for row in image.bytes.chunks(image.width() * 4) {
+        scanlines.push(PNG_FILTER_NONE);
+        scanlines.extend_from_slice(row);
+    }
+
+    let mut png = PNG_SIGNATURE.to_vec();
+    write_chunk(&mut png, b"IHDR", &header);
+    write_chunk(&mut png, b"IDAT", &encode_zlib_stored(&scanlines));
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    // The CRC covers the kind & the data, but not the length
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(kind);
+    hasher.update(data);
+
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    png.extend_from_slice(&hasher.finalize().to_be_bytes());
+}
+
+/// A zlib stream of stored deflate blocks
+/// See: https://www.rfc-editor.org/rfc/rfc1951#section-3.2.4
+fn encode_zlib_stored(data: &[u8]) -> Vec<u8> {
+    let mut blocks: Vec<&[u8]> = data.chunks(MAX_STORED_BLOCK_SIZE).collect();
+    if blocks.is_empty() {
+        blocks.push(&[]);
+    }
+
+    let mut output = ZLIB_HEADER.to_vec();
+    let last_block = blocks.len() - 1;
+    for (index, block) in blocks.into_iter().enumerate() {
+        // BFINAL on the last block, BTYPE 00 for no compression
+        output.push((index == last_block) as u8);
+        output.extend_from_slice(&(block.len() as u16).to_le_bytes());
+        output.extend_from_slice(&(!(block.len() as u16)).to_le_bytes());
+        output.extend_from_slice(block);
+    }
+
+    // Adler-32 checksum of the uncompressed data
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % ADLER_MODULO;
+        (a, (b + a) % ADLER_MODULO)
+    });
+    output.extend_from_slice(&((b << 16) | a).to_be_bytes());
+    output
+}
+
+/// Titles can contain characters that aren't allowed in file names
+fn sanitize_title(title: &str) -> String {
+    let title: String = title
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    match title.is_empty() {
+        true => "screenshot".to_string(),
+        false => title,
+    }
+}
+
+/// A file name without extension built from the ROM title & the current time, e.g. `TETRIS_2024-05-01_12-30-00`
+/// Files taken within the same second get a counter appended
+pub fn get_unused_base_name(directory: &Path, title: &str, extension: &str) -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let base_name = format!("{}_{}", sanitize_title(title), format_timestamp(seconds));
+
+    (1..)
+        .map(|count| match count {
+            1 => base_name.clone(),
+            count => format!("{}_{}", base_name, count),
+        })
+        .find(|name| !directory.join(format!("{}.{}", name, extension)).exists())
+        .unwrap()
+}
+
+/// Format seconds since the Unix epoch as UTC, e.g. `2024-05-01_12-30-00`
+fn format_timestamp(seconds: u64) -> String {
+    let days = (seconds / SECONDS_PER_DAY) as i64;
+    let time = seconds % SECONDS_PER_DAY;
+
+    // Convert the days to a date in the proleptic Gregorian calendar, eras are 400 years long
+    // See: https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    // Months start in March, so the leap day is at the end of the year
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = era * 400 + year_of_era + (month <= 2) as i64;
+
+    format!(
+        "{:04}-{:02}-{:02}_{:02}-{:02}-{:02}",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[test]
+pub fn png_test() {
+    use macroquad::color::Color;
+
+    let mut image = Image::gen_image_color(3, 2, Color::from_rgba(0x12, 0x34, 0x56, 0xFF));
+    image.set_pixel(2, 1, Color::from_rgba(0xFF, 0x00, 0x80, 0x40));
+
+    let png = encode_png(&scale_image(&image, 2));
+    let decoded = Image::from_file_with_format(&png, None).unwrap();
+    assert_eq!((decoded.width, decoded.height), (6, 4));
+    assert_eq!(decoded.get_pixel(1, 1), image.get_pixel(0, 0));
+    assert_eq!(decoded.get_pixel(5, 3), image.get_pixel(2, 1));
+
+    assert_eq!(sanitize_title("POKEMON RED"), "POKEMON_RED");
+    assert_eq!(format_timestamp(0), "1970-01-01_00-00-00");
+    assert_eq!(format_timestamp(951_782_400 + 3661), "2000-02-29_01-01-01");
+}
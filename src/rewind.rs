@@ -0,0 +1,233 @@
+use std::collections::VecDeque;
+
+use crate::cpu::CPU;
+
+/// Only runs of at least this many unchanged bytes end a literal, shorter ones are cheaper to copy
+const MIN_ZERO_RUN: usize = 4;
+
+/// Keeps the recent past of the machine to step back through it while the rewind key is held
+/// Only the newest snapshot is kept as is, every older one is stored as the difference to the next newer one
+/// Snapshots of consecutive frames barely differ, so these deltas are mostly runs of zeros that compress well
+pub struct RewindBuffer {
+    /// Frames between two snapshots
+    interval: u32,
+    frames_since_snapshot: u32,
+    /// The oldest snapshots are dropped once the buffer uses more bytes than this
+    memory_limit: usize,
+    newest: Option<Vec<u8>>,
+    /// Compressed deltas, the oldest first, each one turns a snapshot into the one taken before it
+    deltas: VecDeque<Vec<u8>>,
+    memory_usage: usize,
+}
+
+impl RewindBuffer {
+    pub fn new(interval: u32, memory_limit: usize) -> Self {
+        Self {
+            interval: interval.max(1),
+            frames_since_snapshot: 0,
+            memory_limit,
+            newest: None,
+            deltas: VecDeque::new(),
+            memory_usage: 0,
+        }
+    }
+
+    /// Call once per emulated frame, a snapshot is taken every `interval` frames
+    pub fn on_frame(&mut self, cpu: &CPU) {
+        self.frames_since_snapshot += 1;
+        if self.frames_since_snapshot >= self.interval {
+            self.frames_since_snapshot = 0;
+            self.push(cpu.save_snapshot());
+        }
+    }
+
+    /// Go back to the previous snapshot, returns false if there is none
+    pub fn rewind(&mut self, cpu: &mut CPU) -> bool {
+        let Some(snapshot) = self.pop() else {
+            return false;
+        };
+
+        if let Err(e) = cpu.load_snapshot(&snapshot) {
+            log::warn!("Unable to rewind: {}", e);
+            self.clear();
+            return false;
+        }
+
+        // The oldest snapshot is kept, so holding the rewind key stays there instead of running again
+        if self.is_empty() {
+            self.push(snapshot);
+        }
+
+        // Start counting again, so the next snapshot isn't taken right away
+        self.frames_since_snapshot = 0;
+        true
+    }
+
+    pub fn push(&mut self, snapshot: Vec<u8>) {
+        self.memory_usage += snapshot.len();
+
+        if let Some(previous) = self.newest.replace(snapshot) {
+            self.memory_usage -= previous.len();
+            let delta = encode_delta(self.newest.as_ref().unwrap(), &previous);
+            self.memory_usage += delta.len();
+            self.deltas.push_back(delta);
+        }
+
+        while self.memory_usage > self.memory_limit {
+            match self.deltas.pop_front() {
+                Some(delta) => self.memory_usage -= delta.len(),
+                None => break,
+            }
+        }
+    }
+
+    /// Remove and return the newest snapshot
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let snapshot = self.newest.take()?;
+        self.memory_usage -= snapshot.len();
+
+        if let Some(delta) = self.deltas.pop_back() {
+            let previous = decode_delta(&snapshot, &delta);
+            self.memory_usage += previous.len();
+            self.memory_usage -= delta.len();
+            self.newest = Some(previous);
+        }
+
+        Some(snapshot)
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.memory_usage = 0;
+    }
+
+    /// Amount of snapshots that can be rewound to
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    pub fn get_memory_usage(&self) -> usize {
+        self.memory_usage
+    }
+}
+
+fn write_length(output: &mut Vec<u8>, mut value: usize) {
+    // LEB128, 7 bits per byte with the highest bit set if more bytes follow
+    while value >= 0x80 {
+        output.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    output.push(value as u8);
+}
+
+fn read_length(input: &[u8], position: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = input[*position];
+        *position += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
+
+/// XOR the snapshots and store the result as pairs of a zero run & literal bytes
+/// The shorter snapshot is padded with zeros, the length of the target is stored first
+fn encode_delta(source: &[u8], target: &[u8]) -> Vec<u8> {
+    let length = source.len().max(target.len());
+    let xor = |index: usize| source.get(index).unwrap_or(&0) ^ target.get(index).unwrap_or(&0);
+    let is_zero_run = |index: usize| (index..(index + MIN_ZERO_RUN).min(length)).all(|index| xor(index) == 0);
+
+    let mut output = Vec::new();
+    write_length(&mut output, target.len());
+
+    let mut index = 0;
+    while index < length {
+        let zeros_start = index;
+        while index < length && xor(index) == 0 {
+            index += 1;
+        }
+
+        let literal_start = index;
+        while index < length && !is_zero_run(index) {
+            index += 1;
+        }
+
+        write_length(&mut output, literal_start - zeros_start);
+        write_length(&mut output, index - literal_start);
+        output.extend((literal_start..index).map(xor));
+    }
+
+    output
+}
+
+/// Apply a delta of `encode_delta` to its source to get the target back
+fn decode_delta(source: &[u8], delta: &[u8]) -> Vec<u8> {
+    let mut position = 0;
+    let target_length = read_length(delta, &mut position);
+
+    let mut target = source.to_vec();
+    target.resize(source.len().max(target_length), 0);
+
+    let mut index = 0;
+    while position < delta.len() {
+        index += read_length(delta, &mut position);
+        let literal_length = read_length(delta, &mut position);
+
+        for (byte, difference) in target[index..index + literal_length]
+            .iter_mut()
+            .zip(&delta[position..position + literal_length])
+        {
+            *byte ^= difference;
+        }
+        index += literal_length;
+        position += literal_length;
+    }
+
+    target.truncate(target_length);
+    target
+}
+
+#[test]
+pub fn rewind_buffer_test() {
+    let snapshots: Vec<Vec<u8>> = (0..10u8)
+        .map(|frame| {
+            let mut snapshot = vec![0x55; 0x1000 + frame as usize];
+            snapshot[0x10] = frame;
+            snapshot[0x800] = frame.wrapping_mul(3);
+            snapshot
+        })
+        .collect();
+
+    let mut buffer = RewindBuffer::new(1, usize::MAX);
+    for snapshot in &snapshots {
+        buffer.push(snapshot.clone());
+    }
+    assert_eq!(buffer.len(), 10);
+    // Only the newest snapshot is kept in full
+    assert!(buffer.get_memory_usage() < 2 * snapshots[9].len());
+
+    for snapshot in snapshots.iter().rev() {
+        assert_eq!(buffer.pop().as_ref(), Some(snapshot));
+    }
+    assert_eq!(buffer.pop(), None);
+    assert_eq!(buffer.get_memory_usage(), 0);
+
+    // The oldest snapshots are dropped to stay below the limit
+    let mut buffer = RewindBuffer::new(1, 0x1040);
+    for snapshot in &snapshots {
+        buffer.push(snapshot.clone());
+    }
+    assert!(buffer.get_memory_usage() <= 0x1040);
+    assert!(buffer.len() < 10);
+    assert_eq!(buffer.pop().as_ref(), Some(&snapshots[9]));
+    assert_eq!(buffer.pop().as_ref(), Some(&snapshots[8]));
+}
@@ -0,0 +1,176 @@
+/// Snapshots of the whole machine, e.g. for the rewind buffer
+/// The format is a plain sequence of little-endian values, it is only meant for the same build & ROM
+pub trait SaveState {
+    /// Append the state to the snapshot
+    fn save_state(&self, state: &mut StateWriter);
+
+    /// Restore the state in the same order it has been saved in
+    fn load_state(&mut self, state: &mut StateReader) -> Result<(), String>;
+}
+
+/// Values that can be written to & read from a snapshot
+pub trait StateValue: Sized {
+    fn write_to(&self, state: &mut StateWriter);
+    fn read_from(state: &mut StateReader) -> Result<Self, String>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    data: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write<T: StateValue>(&mut self, value: &T) {
+        value.write_to(self);
+    }
+
+    /// Write a memory region, the length is stored so it can be checked on load
+    pub fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write(&(bytes.len() as u32));
+        self.data.extend_from_slice(bytes);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.data
+    }
+}
+
+pub struct StateReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, position: 0 }
+    }
+
+    pub fn read<T: StateValue>(&mut self) -> Result<T, String> {
+        T::read_from(self)
+    }
+
+    /// Read a memory region into `target`, which has to have the same size as the saved one
+    pub fn read_bytes(&mut self, target: &mut [u8]) -> Result<(), String> {
+        let length = self.read::<u32>()? as usize;
+        if length != target.len() {
+            return Err(format!("Expected {} bytes, but the snapshot contains {}", target.len(), length));
+        }
+
+        target.copy_from_slice(self.take(length)?);
+        Ok(())
+    }
+
+    /// Whether the whole snapshot has been read
+    pub fn is_at_end(&self) -> bool {
+        self.position == self.data.len()
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + length)
+            .ok_or_else(|| "Unexpected end of the snapshot".to_string())?;
+        self.position += length;
+        Ok(bytes)
+    }
+}
+
+macro_rules! impl_state_value_for_integers {
+    ($($integer:ty),*) => {
+        $(
+            impl StateValue for $integer {
+                fn write_to(&self, state: &mut StateWriter) {
+                    state.data.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_from(state: &mut StateReader) -> Result<Self, String> {
+                    let bytes = state.take(std::mem::size_of::<$integer>())?;
+                    Ok(<$integer>::from_le_bytes(bytes.try_into().unwrap()))
+                }
+            }
+        )*
+    };
+}
+
+impl_state_value_for_integers!(u8, u16, u32, u64, i32, i64, f32);
+
+impl StateValue for bool {
+    fn write_to(&self, state: &mut StateWriter) {
+        state.write(&(*self as u8));
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        match state.read::<u8>()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(format!("Invalid boolean {:#04X} in the snapshot", value)),
+        }
+    }
+}
+
+impl<T: StateValue> StateValue for Option<T> {
+    fn write_to(&self, state: &mut StateWriter) {
+        state.write(&self.is_some());
+        if let Some(value) = self {
+            state.write(value);
+        }
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        match state.read::<bool>()? {
+            true => Ok(Some(state.read()?)),
+            false => Ok(None),
+        }
+    }
+}
+
+impl<T: StateValue + Copy + Default, const N: usize> StateValue for [T; N] {
+    fn write_to(&self, state: &mut StateWriter) {
+        for value in self {
+            state.write(value);
+        }
+    }
+
+    fn read_from(state: &mut StateReader) -> Result<Self, String> {
+        let mut values = [T::default(); N];
+        for value in values.iter_mut() {
+            *value = state.read()?;
+        }
+        Ok(values)
+    }
+}
+
+#[test]
+pub fn state_round_trip_test() {
+    let mut writer = StateWriter::new();
+    writer.write(&0x1234u16);
+    writer.write(&true);
+    writer.write(&Some(-5i64));
+    writer.write(&[1u8, 2, 3]);
+    writer.write_bytes(&[0xAA; 4]);
+    let data = writer.into_bytes();
+
+    let mut reader = StateReader::new(&data);
+    assert_eq!(reader.read::<u16>(), Ok(0x1234));
+    assert_eq!(reader.read::<bool>(), Ok(true));
+    assert_eq!(reader.read::<Option<i64>>(), Ok(Some(-5)));
+    assert_eq!(reader.read::<[u8; 3]>(), Ok([1, 2, 3]));
+    let mut memory = [0; 4];
+    assert_eq!(reader.read_bytes(&mut memory), Ok(()));
+    assert_eq!(memory, [0xAA; 4]);
+    assert!(reader.is_at_end());
+
+    // Regions of a different size & truncated snapshots are rejected
+    let mut reader = StateReader::new(&data[..data.len() - 1]);
+    assert!(reader.read::<u16>().is_ok());
+    assert!(reader.read::<bool>().is_ok());
+    assert!(reader.read::<Option<i64>>().is_ok());
+    assert!(reader.read::<[u8; 3]>().is_ok());
+    assert!(reader.read_bytes(&mut [0; 4]).is_err());
+    let mut reader = StateReader::new(&data[data.len() - 8..]);
+    assert!(reader.read_bytes(&mut [0; 3]).is_err());
+}